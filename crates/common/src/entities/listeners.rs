@@ -11,6 +11,7 @@ pub struct Model {
     pub protocol: String,
     pub tls_policy_id: Option<Uuid>,
     pub enabled: bool,
+    pub options: Option<Json>,
     pub created_at: DateTimeWithTimeZone,
     pub updated_at: DateTimeWithTimeZone,
 }
@@ -13,6 +13,7 @@ pub struct Model {
     pub priority: i32,
     pub upstream_pool_id: Uuid,
     pub enabled: bool,
+    pub options: Option<Json>,
     pub created_at: DateTimeWithTimeZone,
     pub updated_at: DateTimeWithTimeZone,
 }
@@ -9,6 +9,7 @@ pub struct Model {
     pub mode: String,
     pub domains: Vec<String>,
     pub status: String,
+    pub options: Option<Json>,
     pub created_at: DateTimeWithTimeZone,
     pub updated_at: DateTimeWithTimeZone,
 }
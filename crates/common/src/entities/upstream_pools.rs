@@ -9,6 +9,7 @@ pub struct Model {
     pub name: String,
     pub policy: String,
     pub health_check: Option<Json>,
+    pub options: Option<Json>,
     pub created_at: DateTimeWithTimeZone,
     pub updated_at: DateTimeWithTimeZone,
 }
@@ -10,6 +10,10 @@ pub struct Model {
     pub address: String,
     pub weight: i32,
     pub enabled: bool,
+    /// 故障转移层级，数值越小越优先；高层级健康容量不足时才使用低层级（备用）目标
+    pub priority: i32,
+    /// 通过摘流接口临时摘除的时间；非空时数据平面不再向该目标分配新请求
+    pub drained_at: Option<DateTimeWithTimeZone>,
     pub created_at: DateTimeWithTimeZone,
     pub updated_at: DateTimeWithTimeZone,
 }
@@ -0,0 +1,70 @@
+//! gRPC 状态码
+//!
+//! 状态码名称与数值的互转，供 control-plane 校验与 data-plane 指标、重试判定共享使用。
+
+/// gRPC 状态码名称，下标即状态码
+const CODE_NAMES: [&str; 17] = [
+    "OK",
+    "CANCELLED",
+    "UNKNOWN",
+    "INVALID_ARGUMENT",
+    "DEADLINE_EXCEEDED",
+    "NOT_FOUND",
+    "ALREADY_EXISTS",
+    "PERMISSION_DENIED",
+    "RESOURCE_EXHAUSTED",
+    "FAILED_PRECONDITION",
+    "ABORTED",
+    "OUT_OF_RANGE",
+    "UNIMPLEMENTED",
+    "INTERNAL",
+    "UNAVAILABLE",
+    "DATA_LOSS",
+    "UNAUTHENTICATED",
+];
+
+pub const UNKNOWN: u32 = 2;
+
+/// 状态码名称（未知状态码返回 `UNKNOWN`）
+pub fn code_name(code: u32) -> &'static str {
+    CODE_NAMES
+        .get(code as usize)
+        .copied()
+        .unwrap_or(CODE_NAMES[UNKNOWN as usize])
+}
+
+/// 解析 `UNAVAILABLE`（大小写不敏感）或数字形式的状态码
+pub fn parse_code(value: &str) -> Option<u32> {
+    let value = value.trim();
+    if let Ok(code) = value.parse::<u32>() {
+        return ((code as usize) < CODE_NAMES.len()).then_some(code);
+    }
+    CODE_NAMES
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case(value))
+        .map(|code| code as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_code() {
+        assert_eq!(parse_code("UNAVAILABLE"), Some(14));
+        assert_eq!(parse_code("unavailable"), Some(14));
+        assert_eq!(parse_code(" 4 "), Some(4));
+        assert_eq!(parse_code("16"), Some(16));
+
+        assert_eq!(parse_code("17"), None, "超出范围的数字状态码");
+        assert_eq!(parse_code("NOT_A_CODE"), None);
+        assert_eq!(parse_code(""), None);
+    }
+
+    #[test]
+    fn test_code_name() {
+        assert_eq!(code_name(0), "OK");
+        assert_eq!(code_name(14), "UNAVAILABLE");
+        assert_eq!(code_name(99), "UNKNOWN");
+    }
+}
@@ -1,7 +1,9 @@
 pub mod config;
 pub mod entities;
 pub mod error;
+pub mod grpc;
 pub mod models;
+pub mod net;
 pub mod snapshot;
 pub mod state;
 pub mod validation;
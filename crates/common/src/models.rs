@@ -10,6 +10,7 @@ pub struct CreateListener {
     pub protocol: String,
     pub tls_policy_id: Option<Uuid>,
     pub enabled: Option<bool>,
+    pub options: Option<JsonValue>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,6 +20,7 @@ pub struct UpdateListener {
     pub protocol: Option<String>,
     pub tls_policy_id: Option<Uuid>,
     pub enabled: Option<bool>,
+    pub options: Option<JsonValue>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,6 +31,7 @@ pub struct CreateRoute {
     pub priority: i32,
     pub upstream_pool_id: Uuid,
     pub enabled: Option<bool>,
+    pub options: Option<JsonValue>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,6 +41,7 @@ pub struct UpdateRoute {
     pub priority: Option<i32>,
     pub upstream_pool_id: Option<Uuid>,
     pub enabled: Option<bool>,
+    pub options: Option<JsonValue>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -45,6 +49,7 @@ pub struct CreateUpstreamPool {
     pub name: String,
     pub policy: String,
     pub health_check: Option<JsonValue>,
+    pub options: Option<JsonValue>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -52,6 +57,7 @@ pub struct UpdateUpstreamPool {
     pub name: Option<String>,
     pub policy: Option<String>,
     pub health_check: Option<JsonValue>,
+    pub options: Option<JsonValue>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -59,6 +65,7 @@ pub struct CreateUpstreamTarget {
     pub address: String,
     pub weight: Option<i32>,
     pub enabled: Option<bool>,
+    pub priority: Option<i32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -66,12 +73,20 @@ pub struct UpdateUpstreamTarget {
     pub address: Option<String>,
     pub weight: Option<i32>,
     pub enabled: Option<bool>,
+    pub priority: Option<i32>,
+}
+
+/// 临时摘流开关：摘流期间数据平面不再向目标分配新请求，不修改目标的 `enabled`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TargetDrainRequest {
+    pub draining: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateTlsPolicy {
     pub mode: String,
     pub domains: Vec<String>,
+    pub options: Option<JsonValue>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -79,6 +94,7 @@ pub struct UpdateTlsPolicy {
     pub mode: Option<String>,
     pub domains: Option<Vec<String>>,
     pub status: Option<String>,
+    pub options: Option<JsonValue>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -95,13 +111,397 @@ pub struct NodeHeartbeatRequest {
     pub metadata: Option<JsonValue>,
 }
 
+/// 节点心跳上报的运行状态（node_status.metadata）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NodeMetadata {
+    /// 正在摘流的上游目标
+    #[serde(default)]
+    pub draining_targets: Vec<DrainingTarget>,
+}
+
+/// 已从配置中移除、禁用或被临时摘流，但仍有进行中请求的上游目标
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DrainingTarget {
+    pub pool_id: Uuid,
+    pub target_id: Uuid,
+    pub address: String,
+    /// 进行中的请求数
+    pub inflight: usize,
+    /// 距摘流超时的剩余秒数，超时后仍未完成的请求将被中断
+    pub remaining_secs: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RouteMatch {
+    /// 主机名，精确匹配或 `*.example.com` 单级通配；比较前去除请求中的端口
     pub host: Option<String>,
+    /// 主机名正则（匹配去除端口并转为小写后的主机名）
+    pub host_regex: Option<String>,
     pub path_prefix: Option<String>,
     pub path_regex: Option<String>,
     pub method: Option<Vec<String>>,
-    pub headers: Option<HashMap<String, String>>,
-    pub query: Option<HashMap<String, String>>,
+    pub headers: Option<HashMap<String, ValueMatch>>,
+    pub query: Option<HashMap<String, ValueMatch>>,
+    /// Cookie 匹配，键为 Cookie 名称
+    pub cookies: Option<HashMap<String, ValueMatch>>,
+    /// 客户端来源网段（CIDR），与经可信代理解析后的客户端 IP 比较
+    pub source_cidrs: Option<Vec<String>>,
+    /// 客户端证书主题（如 `CN=partner,O=Example`），仅对 https 监听器上经校验的客户端证书生效
+    pub client_cert_subject: Option<ValueMatch>,
     pub ws: Option<bool>,
 }
+
+/// 请求头 / 查询参数 / Cookie 的匹配条件：
+/// 字符串为精确匹配（键不存在时按空串比较），字符串数组为任一值精确匹配，对象为带运算符的条件
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ValueMatch {
+    Exact(String),
+    AnyOf(Vec<String>),
+    Rule(ValueMatchRule),
+}
+
+/// 带运算符的匹配条件；同时设置的条件需全部满足，键不存在时不满足任何取值条件
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ValueMatchRule {
+    /// true 要求键存在，false 要求键不存在（不能与取值条件同时使用）
+    pub present: Option<bool>,
+    /// 允许的取值（任一精确匹配）
+    pub values: Option<Vec<String>>,
+    pub prefix: Option<String>,
+    pub suffix: Option<String>,
+    pub regex: Option<String>,
+    /// 取值比较是否忽略大小写（同样作用于 regex）
+    #[serde(default)]
+    pub ignore_case: bool,
+    /// 对整体结果取反
+    #[serde(default)]
+    pub invert: bool,
+}
+
+impl ValueMatch {
+    /// 统一为运算符形式
+    pub fn to_rule(&self) -> ValueMatchRule {
+        match self {
+            Self::Exact(value) => ValueMatchRule {
+                values: Some(vec![value.clone()]),
+                ..Default::default()
+            },
+            Self::AnyOf(values) => ValueMatchRule {
+                values: Some(values.clone()),
+                ..Default::default()
+            },
+            Self::Rule(rule) => rule.clone(),
+        }
+    }
+}
+
+impl ValueMatchRule {
+    /// 是否设置了取值条件
+    pub fn has_value_condition(&self) -> bool {
+        self.values.is_some()
+            || self.prefix.is_some()
+            || self.suffix.is_some()
+            || self.regex.is_some()
+    }
+}
+
+/// 默认的请求 ID 头名称
+pub const DEFAULT_REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// 监听器扩展配置（listeners.options）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ListenerOptions {
+    #[serde(default)]
+    pub request_id: Option<RequestIdOptions>,
+    #[serde(default)]
+    pub forwarded: Option<ForwardedOptions>,
+    /// 入站 PROXY protocol：off（默认）| optional | required；https 监听器在 TLS 握手之前读取
+    pub proxy_protocol: Option<String>,
+    /// 允许发送 PROXY 头的对端网段（CIDR）；启用 proxy_protocol 时必填
+    #[serde(default)]
+    pub proxy_protocol_trusted_cidrs: Vec<String>,
+    #[serde(default)]
+    pub http2: Option<Http2Options>,
+    #[serde(default)]
+    pub error_pages: Option<ErrorPagesOptions>,
+    #[serde(default)]
+    pub maintenance: Option<MaintenanceOptions>,
+    #[serde(default)]
+    pub limits: Option<RequestLimitsOptions>,
+    /// 安全响应头；https 监听器未配置时按默认值生效
+    #[serde(default)]
+    pub security_headers: Option<SecurityHeadersOptions>,
+}
+
+/// 路由扩展配置（routes.options）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RouteOptions {
+    /// 路由级错误页，优先于监听器配置
+    #[serde(default)]
+    pub error_pages: Option<ErrorPagesOptions>,
+    #[serde(default)]
+    pub maintenance: Option<MaintenanceOptions>,
+    /// 路由级请求大小限制，与监听器限制同时生效
+    #[serde(default)]
+    pub limits: Option<RequestLimitsOptions>,
+    /// WebSocket 连接管理，仅用于 ws 路由
+    #[serde(default)]
+    pub websocket: Option<WebSocketOptions>,
+    /// 故障注入，用于演练上游延迟与失败
+    #[serde(default)]
+    pub fault: Option<FaultOptions>,
+    /// 外部授权：转发前先向授权服务发送子请求
+    #[serde(default)]
+    pub ext_auth: Option<ExtAuthOptions>,
+    /// 路由级安全响应头，逐项覆盖监听器配置
+    #[serde(default)]
+    pub security_headers: Option<SecurityHeadersOptions>,
+}
+
+/// 安全响应头策略：各字段逐项覆盖上一级（https 默认值 → 监听器 → 路由），未设置的字段沿用上一级；
+/// 取值为空字符串的头不发送
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SecurityHeadersOptions {
+    /// false 时不添加也不移除任何响应头
+    pub enabled: Option<bool>,
+    /// `Strict-Transport-Security`，仅在 https 监听器上发送
+    #[serde(default)]
+    pub hsts: Option<HstsOptions>,
+    /// `X-Content-Type-Options`（nosniff）
+    pub content_type_options: Option<String>,
+    /// `X-Frame-Options`（DENY | SAMEORIGIN）
+    pub frame_options: Option<String>,
+    /// `Referrer-Policy`
+    pub referrer_policy: Option<String>,
+    /// `Content-Security-Policy`
+    pub content_security_policy: Option<String>,
+    /// `Permissions-Policy`
+    pub permissions_policy: Option<String>,
+    /// 移除上游响应的 `Server` 与 `X-Powered-By` 头
+    pub hide_server_headers: Option<bool>,
+    /// 上游响应已带同名头时覆盖为网关配置的值（默认保留上游的值）
+    pub override_upstream: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HstsOptions {
+    pub enabled: Option<bool>,
+    /// `max-age`（秒），缺省 31536000
+    pub max_age_secs: Option<u64>,
+    pub include_subdomains: Option<bool>,
+    /// 提交 HSTS 预加载列表；要求同时开启 include_subdomains 且 max_age_secs 不小于一年
+    pub preload: Option<bool>,
+}
+
+/// 外部授权（forward auth）：2xx 放行，其他响应（状态、响应头与响应体）原样返回客户端
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExtAuthOptions {
+    /// 授权服务地址（http/https URL），与 upstream_pool_id 二选一
+    pub url: Option<String>,
+    /// 以上游池作为授权服务，按池策略选取目标
+    pub upstream_pool_id: Option<Uuid>,
+    /// 使用上游池时子请求的路径（可含查询串），缺省 `/`
+    pub path: Option<String>,
+    /// 随子请求发送的原始请求头
+    #[serde(default)]
+    pub request_headers: Vec<String>,
+    /// 放行时复制到上游请求的授权响应头；客户端请求中的同名头总会先被移除
+    #[serde(default)]
+    pub response_headers: Vec<String>,
+    /// 子请求超时（毫秒），缺省 1000
+    pub timeout_ms: Option<u64>,
+    /// 授权结果缓存秒数，缺省不缓存
+    pub cache_ttl_secs: Option<u64>,
+}
+
+/// 故障注入：只对请求头满足全部条件的请求生效，延迟与中止按比例独立抽样
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FaultOptions {
+    /// 生效条件，键为请求头名称，条件格式同 match_expr.headers；不能为空
+    #[serde(default)]
+    pub headers: HashMap<String, ValueMatch>,
+    pub delay: Option<FaultDelayOptions>,
+    pub abort: Option<FaultAbortOptions>,
+}
+
+/// 转发前延迟请求；fixed_ms 与 random_ms 二选一
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FaultDelayOptions {
+    /// 注入比例（0-100，可为小数）
+    pub percentage: f64,
+    /// 固定延迟（毫秒）
+    pub fixed_ms: Option<u64>,
+    /// 随机延迟范围 `[最小, 最大]`（毫秒）
+    pub random_ms: Option<[u64; 2]>,
+}
+
+/// 不转发上游，直接以指定状态码响应或重置连接；status 与 reset 二选一
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FaultAbortOptions {
+    /// 注入比例（0-100，可为小数）
+    pub percentage: f64,
+    pub status: Option<u16>,
+    /// 不返回响应，直接关闭客户端连接（HTTP/2 下为重置该请求的流）
+    #[serde(default)]
+    pub reset: bool,
+}
+
+/// ws 路由的连接管理；未设置的项不限制
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WebSocketOptions {
+    /// 路由最大并发连接数，达到上限时新的升级请求返回 503
+    pub max_connections: Option<u32>,
+    /// 双向均无数据帧超过该时间后关闭连接（秒）
+    pub idle_timeout_secs: Option<u64>,
+    /// 连接最大存活时间（秒）
+    pub max_lifetime_secs: Option<u64>,
+    /// 向上游发送 Ping 的间隔（秒），上游的 Pong 经网关转发给客户端
+    pub ping_interval_secs: Option<u64>,
+    /// 路由或目标被新配置移除后，关闭存量连接前的等待时间（秒），缺省 30
+    pub drain_timeout_secs: Option<u64>,
+}
+
+/// 请求大小限制；未设置的项不限制
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RequestLimitsOptions {
+    /// 请求体最大字节数，超出返回 413（按 Content-Length 提前拒绝，分块传输时在转发过程中计数）
+    pub max_body_bytes: Option<u64>,
+    /// 请求头名称与取值的总字节数上限，超出返回 431
+    pub max_header_bytes: Option<u32>,
+    /// 请求头数量上限，超出返回 431
+    pub max_header_count: Option<u32>,
+    /// 请求目标（路径与查询串）最大长度，超出返回 414
+    pub max_uri_length: Option<u32>,
+}
+
+/// 维护模式：开启后匹配的请求直接返回 503（不转发上游），来自放行网段的客户端除外
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MaintenanceOptions {
+    #[serde(default)]
+    pub enabled: bool,
+    /// 响应体文本；缺省时按错误页配置渲染 503
+    pub message: Option<String>,
+    /// `Retry-After` 响应头（秒）
+    pub retry_after: Option<u32>,
+    /// 放行的客户端网段（CIDR），与经可信代理解析后的客户端 IP 比较
+    #[serde(default)]
+    pub bypass_cidrs: Vec<String>,
+}
+
+/// 网关本地错误（无监听器 / 无路由 / 无可用上游 / 上游连接失败或超时等）的响应配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ErrorPagesOptions {
+    /// 按状态码（如 `502`）或状态类别（如 `5xx`）配置的候选响应，按 Accept 头协商选择
+    #[serde(default)]
+    pub pages: HashMap<String, Vec<ErrorPage>>,
+    /// 是否提供 RFC 7807 问题文档（`application/problem+json`）作为候选响应
+    #[serde(default)]
+    pub problem_json: bool,
+}
+
+/// 错误响应体；body 中的 `{{status}}`、`{{reason}}`、`{{request_id}}` 会被替换
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorPage {
+    pub content_type: String,
+    pub body: String,
+}
+
+/// 下游 HTTP/2 配置（https 监听器是否协商 h2 由 TLS 策略的 ALPN 决定）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Http2Options {
+    /// http 监听器是否接受 h2c prior knowledge 连接，缺省 false
+    pub h2c: Option<bool>,
+    /// 单连接最大并发流数
+    pub max_concurrent_streams: Option<u32>,
+    /// 请求头列表最大字节数（SETTINGS_MAX_HEADER_LIST_SIZE）
+    pub max_header_list_size: Option<u32>,
+}
+
+/// 请求 ID 生成与透传配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RequestIdOptions {
+    /// 请求 ID 头名称，缺省为 `x-request-id`
+    pub header: Option<String>,
+    /// 允许沿用入站请求 ID 的客户端网段（CIDR）；为空时总是重新生成
+    #[serde(default)]
+    pub trusted_cidrs: Vec<String>,
+}
+
+/// 转发头（X-Forwarded-* / Forwarded）与可信代理配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ForwardedOptions {
+    /// 是否向上游写入转发头，缺省为 true
+    pub enabled: Option<bool>,
+    /// 可信代理网段（CIDR）；仅信任来自这些地址的入站转发头
+    #[serde(default)]
+    pub trusted_proxies: Vec<String>,
+    /// 来自可信代理时的处理方式：append（追加，默认）| replace（以真实客户端 IP 重写）
+    pub mode: Option<String>,
+}
+
+/// 上游池扩展配置（upstream_pools.options）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PoolOptions {
+    /// 新建上游连接时发送的 PROXY protocol 版本：v1 | v2，缺省不发送
+    pub proxy_protocol: Option<String>,
+    /// 上游协议：http1（默认）| h2（TLS + ALPN h2）| h2c（明文 HTTP/2 prior knowledge）
+    pub protocol: Option<String>,
+    /// h2 上游的 TLS SNI，缺省取目标地址中的主机名
+    pub sni: Option<String>,
+    /// 连接上游失败或命中 grpc_retry_on 时的最大重试次数，缺省 0（不重试）
+    pub max_retries: Option<u32>,
+    /// 触发重试的 gRPC 状态（名称如 `UNAVAILABLE` 或数字），仅对 trailers-only 响应生效
+    #[serde(default)]
+    pub grpc_retry_on: Vec<String>,
+    /// 目标被移除、禁用或摘流后，进行中请求与空闲 keepalive 连接的最长保留时间，缺省 30 秒
+    pub drain_timeout_secs: Option<u64>,
+    /// 服务发现：配置后池内目标取自发现源，忽略 upstream_targets 中的静态目标
+    pub discovery: Option<DiscoveryOptions>,
+    /// 上游连接复用（keepalive）设置，缺省沿用 Pingora 默认行为
+    pub keepalive: Option<KeepaliveOptions>,
+    /// 某一优先级层级的健康容量（健康目标权重占比，百分数）低于该值时，同时使用下一层级的目标，缺省 50
+    pub failover_threshold: Option<u8>,
+}
+
+/// 上游连接池与 keepalive 设置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct KeepaliveOptions {
+    /// 是否复用上游连接，缺省 true；false 时每个请求结束后关闭连接（仅 http1）
+    pub enabled: Option<bool>,
+    /// 池内保留的空闲连接上限（仅 http1，近似值），缺省不限制（受全局连接池大小约束）
+    pub pool_size: Option<usize>,
+    /// 空闲连接的最长保留秒数，缺省取 drain_timeout_secs，且不超过该值
+    pub idle_timeout_secs: Option<u64>,
+    /// 单个连接最多承载的请求数，达到后关闭连接（仅 http1），缺省不限制
+    pub max_requests: Option<u64>,
+}
+
+/// 上游服务发现配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DiscoveryOptions {
+    /// 发现源：dns（A/AAAA 记录）| srv（SRV 记录提供端口与权重）| file（本地 JSON/YAML 文件）
+    #[serde(rename = "type")]
+    pub r#type: String,
+    /// dns / srv：要解析的域名，如 `api.internal` 或 `_http._tcp.api.internal`
+    pub name: Option<String>,
+    /// dns：目标端口（A/AAAA 记录不含端口）
+    pub port: Option<u16>,
+    /// dns：目标权重，缺省 1
+    pub weight: Option<i32>,
+    /// file：目标列表文件路径，内容为 `{"targets": [{"address": "host:port", "weight": 1}]}`
+    pub path: Option<String>,
+    /// 重新解析（file 为检查文件变更）的间隔秒数，dns / srv 缺省 30，file 缺省 5
+    pub interval_secs: Option<u64>,
+}
+
+/// TLS 策略扩展配置（tls_policies.options）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TlsPolicyOptions {
+    /// 服务端 ALPN 协议列表（按优先级）：`h2` | `http/1.1`，缺省仅 `http/1.1`
+    pub alpn: Option<Vec<String>>,
+    /// 客户端证书 CA（PEM，可含多个证书）；配置后 https 监听器请求并校验客户端证书，
+    /// 未提供证书的连接仍可建立，由路由的 `client_cert_subject` 条件决定是否放行
+    pub client_ca_pem: Option<String>,
+}
@@ -0,0 +1,209 @@
+//! 网络地址工具
+//!
+//! 提供 CIDR 网段解析与匹配，供 control-plane 校验与 data-plane 运行时共享使用。
+
+use std::fmt;
+use std::net::IpAddr;
+
+/// IP 网段（CIDR），不带前缀长度时视为单个地址
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IpCidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpCidr {
+    /// 解析 `10.0.0.0/8`、`2001:db8::/32` 或单个 IP
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let (addr, prefix) = match value.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (value, None),
+        };
+        let addr: IpAddr = addr.parse().ok()?;
+        let max = max_prefix(addr);
+        let prefix = match prefix {
+            Some(prefix) => prefix.parse::<u8>().ok().filter(|p| *p <= max)?,
+            None => max,
+        };
+        Some(Self { addr, prefix })
+    }
+
+    /// 判断地址是否落在网段内（IPv4-mapped IPv6 地址按 IPv4 处理）
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, canonical_ip(ip)) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => prefix_matches(
+                u32::from(net) as u128,
+                u32::from(ip) as u128,
+                32,
+                self.prefix,
+            ),
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                prefix_matches(u128::from(net), u128::from(ip), 128, self.prefix)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for IpCidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+/// 判断地址是否命中任一网段
+pub fn contains_ip(cidrs: &[IpCidr], ip: IpAddr) -> bool {
+    cidrs.iter().any(|cidr| cidr.contains(ip))
+}
+
+/// 将 IPv4-mapped IPv6 地址还原为 IPv4
+pub fn canonical_ip(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+        IpAddr::V4(_) => ip,
+    }
+}
+
+/// 规范化请求主机名用于匹配：去除端口与结尾的点并转为小写，IPv6 字面量去除方括号
+pub fn normalize_host(value: &str) -> String {
+    let value = value.trim();
+    let host = match value.strip_prefix('[') {
+        Some(rest) => rest.split_once(']').map(|(host, _)| host).unwrap_or(rest),
+        None => match value.rsplit_once(':') {
+            Some((host, port))
+                if !host.contains(':') && port.bytes().all(|b| b.is_ascii_digit()) =>
+            {
+                host
+            }
+            _ => value,
+        },
+    };
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// 主机名匹配：精确匹配（忽略大小写），或 `*.example.com` 匹配恰好一级子域名
+pub fn host_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(suffix) => host
+            .split_once('.')
+            .is_some_and(|(label, rest)| !label.is_empty() && rest.eq_ignore_ascii_case(suffix)),
+        None => pattern.eq_ignore_ascii_case(host),
+    }
+}
+
+/// sockaddr_un.sun_path 的长度（108 字节）减去结尾的 NUL
+const MAX_UNIX_SOCKET_PATH: usize = 107;
+
+/// 上游目标地址中的 Unix domain socket 路径：`unix:/path/to/socket`，路径须为绝对路径
+pub fn unix_socket_path(address: &str) -> Option<&str> {
+    address.strip_prefix("unix:").filter(|path| {
+        path.starts_with('/') && path.len() > 1 && path.len() <= MAX_UNIX_SOCKET_PATH
+    })
+}
+
+fn max_prefix(addr: IpAddr) -> u8 {
+    match addr {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    }
+}
+
+fn prefix_matches(net: u128, ip: u128, bits: u8, prefix: u8) -> bool {
+    if prefix == 0 {
+        return true;
+    }
+    let shift = u32::from(bits - prefix);
+    net.checked_shr(shift).unwrap_or(0) == ip.checked_shr(shift).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_cidr() {
+        assert!(IpCidr::parse("10.0.0.0/8").is_some());
+        assert!(IpCidr::parse("192.168.1.10").is_some());
+        assert!(IpCidr::parse("2001:db8::/32").is_some());
+        assert!(IpCidr::parse(" 127.0.0.1/32 ").is_some());
+
+        assert!(
+            IpCidr::parse("10.0.0.0/33").is_none(),
+            "IPv4 前缀不能超过 32"
+        );
+        assert!(IpCidr::parse("2001:db8::/129").is_none());
+        assert!(IpCidr::parse("10.0.0/8").is_none());
+        assert!(IpCidr::parse("example.com").is_none());
+        assert!(IpCidr::parse("").is_none());
+    }
+
+    #[test]
+    fn test_cidr_contains() {
+        let net = IpCidr::parse("10.1.0.0/16").unwrap();
+        assert!(net.contains(ip("10.1.2.3")));
+        assert!(!net.contains(ip("10.2.0.1")));
+
+        let single = IpCidr::parse("192.168.1.10").unwrap();
+        assert!(single.contains(ip("192.168.1.10")));
+        assert!(!single.contains(ip("192.168.1.11")));
+
+        let any = IpCidr::parse("0.0.0.0/0").unwrap();
+        assert!(any.contains(ip("8.8.8.8")));
+        assert!(!any.contains(ip("::1")), "IPv4 网段不匹配 IPv6 地址");
+
+        let v6 = IpCidr::parse("2001:db8::/32").unwrap();
+        assert!(v6.contains(ip("2001:db8::1")));
+        assert!(!v6.contains(ip("2001:db9::1")));
+    }
+
+    #[test]
+    fn test_ipv4_mapped_address() {
+        let net = IpCidr::parse("127.0.0.0/8").unwrap();
+        assert!(net.contains(ip("::ffff:127.0.0.1")));
+        assert!(contains_ip(&[net], ip("::ffff:127.0.0.1")));
+        assert!(!contains_ip(&[], ip("127.0.0.1")));
+    }
+
+    #[test]
+    fn test_normalize_host() {
+        assert_eq!(normalize_host("Example.COM"), "example.com");
+        assert_eq!(normalize_host("example.com:8443"), "example.com");
+        assert_eq!(normalize_host("example.com."), "example.com");
+        assert_eq!(normalize_host("10.0.0.1:80"), "10.0.0.1");
+        assert_eq!(normalize_host("[2001:db8::1]:443"), "2001:db8::1");
+        assert_eq!(normalize_host("2001:db8::1"), "2001:db8::1");
+    }
+
+    #[test]
+    fn test_host_matches() {
+        assert!(host_matches("example.com", "EXAMPLE.com"));
+        assert!(!host_matches("example.com", "api.example.com"));
+
+        assert!(host_matches("*.example.com", "api.example.com"));
+        assert!(
+            !host_matches("*.example.com", "a.b.example.com"),
+            "通配符只匹配一级子域名"
+        );
+        assert!(!host_matches("*.example.com", "example.com"));
+        assert!(!host_matches("*.example.com", ".example.com"));
+    }
+
+    #[test]
+    fn test_unix_socket_path() {
+        assert_eq!(
+            unix_socket_path("unix:/run/app/http.sock"),
+            Some("/run/app/http.sock")
+        );
+        assert_eq!(unix_socket_path("unix:run/app.sock"), None, "须为绝对路径");
+        assert_eq!(unix_socket_path("unix:/"), None);
+        assert_eq!(unix_socket_path("unix:"), None);
+        let long = format!("unix:/{}", "a".repeat(MAX_UNIX_SOCKET_PATH));
+        assert_eq!(unix_socket_path(&long), None, "超出 sun_path 长度");
+        assert_eq!(unix_socket_path("127.0.0.1:8080"), None);
+    }
+}
@@ -159,7 +159,7 @@ impl ValidationContext {
                 errors.push(ValidationError::DuplicatePort { port });
             }
 
-            // 根据协议验证端口范围
+            // 根据协议验证端口范围（tcp 与 http 同为明文端口，共用 HTTP_PORT_RANGE）
             let is_https = listener.protocol.eq_ignore_ascii_case("https");
 
             if is_https {
@@ -299,6 +299,7 @@ mod tests {
             enabled,
             name: "test".to_string(),
             tls_policy_id: None,
+            options: None,
             created_at: Default::default(),
             updated_at: Default::default(),
         }
@@ -7,14 +7,16 @@ use crate::{txn, txn_with};
 use axum::Json;
 use axum::extract::{Path, Query, State};
 use axum::http::HeaderMap;
-use axum::routing::{get, patch, post};
+use axum::routing::{get, patch, post, put};
 use chrono::Utc;
 use gateway_common::entities::{
     audit_logs, config_versions, listeners, node_status, routes, tls_policies, upstream_pools,
     upstream_targets,
 };
 use gateway_common::models::*;
+use gateway_common::net::{IpCidr, normalize_host, unix_socket_path};
 use gateway_common::snapshot::{PublishedSnapshotResponse, Snapshot, build_snapshot};
+use regex::Regex;
 use sea_orm::sea_query::Expr;
 use sea_orm::{
     ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter, QueryOrder, Set,
@@ -26,6 +28,7 @@ use tower::ServiceBuilder;
 use tower_http::services::{ServeDir, ServeFile};
 use tower_http::trace::TraceLayer;
 use uuid::Uuid;
+use x509_parser::pem::Pem;
 
 type ListenerModel = listeners::Model;
 type RouteModel = routes::Model;
@@ -45,6 +48,7 @@ struct NodeStatusView {
     consistent: bool,
     heartbeat_at: String,
     metadata: Option<JsonValue>,
+    draining_targets: Vec<DrainingTarget>,
 }
 
 pub fn router(state: AppState) -> axum::Router {
@@ -65,11 +69,19 @@ pub fn router(state: AppState) -> axum::Router {
                 .patch(update_listener)
                 .delete(delete_listener),
         )
+        .route(
+            "/api/v1/listeners/{id}/maintenance",
+            put(set_listener_maintenance),
+        )
         .route("/api/v1/routes", post(create_route).get(list_routes))
         .route(
             "/api/v1/routes/{id}",
             get(get_route).patch(update_route).delete(delete_route),
         )
+        .route(
+            "/api/v1/routes/{id}/maintenance",
+            put(set_route_maintenance),
+        )
         .route("/api/v1/upstreams", post(create_pool).get(list_pools))
         .route(
             "/api/v1/upstreams/{id}",
@@ -81,6 +93,7 @@ pub fn router(state: AppState) -> axum::Router {
             patch(update_target).delete(delete_target),
         )
         .route("/api/v1/targets", get(list_targets))
+        .route("/api/v1/targets/{id}/drain", put(set_target_drain))
         .route(
             "/api/v1/tls/policies",
             post(create_tls_policy).get(list_tls),
@@ -122,6 +135,7 @@ async fn create_listener(
             protocol: Set(payload.protocol),
             tls_policy_id: Set(payload.tls_policy_id),
             enabled: Set(payload.enabled.unwrap_or(true)),
+            options: Set(payload.options),
             ..Default::default()
         };
         Ok::<_, GatewayError>(active.insert(txn).await?)
@@ -189,6 +203,9 @@ async fn update_listener(
             if let Some(enabled) = payload.enabled {
                 active.enabled = Set(enabled);
             }
+            if let Some(options) = payload.options {
+                active.options = Set(Some(options));
+            }
             active.updated_at = Set(Utc::now().into());
 
             let updated = active.update(txn).await?;
@@ -245,6 +262,7 @@ async fn create_route(
             priority: Set(payload.priority),
             upstream_pool_id: Set(payload.upstream_pool_id),
             enabled: Set(payload.enabled.unwrap_or(true)),
+            options: Set(payload.options),
             ..Default::default()
         };
         Ok::<_, anyhow::Error>(active.insert(txn).await?)
@@ -312,6 +330,9 @@ async fn update_route(
             if let Some(upstream_pool_id) = payload.upstream_pool_id {
                 active.upstream_pool_id = Set(upstream_pool_id);
             }
+            if let Some(options) = payload.options {
+                active.options = Set(Some(options));
+            }
             if let Some(enabled) = payload.enabled {
                 active.enabled = Set(enabled);
             }
@@ -369,6 +390,7 @@ async fn create_pool(
             name: Set(payload.name),
             policy: Set(payload.policy),
             health_check: Set(payload.health_check),
+            options: Set(payload.options),
             ..Default::default()
         };
         Ok::<_, anyhow::Error>(active.insert(txn).await?)
@@ -426,6 +448,9 @@ async fn update_pool(
             if let Some(health_check) = payload.health_check {
                 active.health_check = Set(Some(health_check));
             }
+            if let Some(options) = payload.options {
+                active.options = Set(Some(options));
+            }
             active.updated_at = Set(Utc::now().into());
 
             let updated = active.update(txn).await?;
@@ -482,6 +507,7 @@ async fn create_target(
             address: Set(payload.address),
             weight: Set(payload.weight.unwrap_or(1)),
             enabled: Set(payload.enabled.unwrap_or(true)),
+            priority: Set(payload.priority.unwrap_or(0)),
             ..Default::default()
         };
         Ok::<_, anyhow::Error>(active.insert(txn).await?)
@@ -523,6 +549,9 @@ async fn update_target(
             if let Some(enabled) = payload.enabled {
                 active.enabled = Set(enabled);
             }
+            if let Some(priority) = payload.priority {
+                active.priority = Set(priority);
+            }
             active.updated_at = Set(Utc::now().into());
 
             let updated = active.update(txn).await?;
@@ -594,6 +623,7 @@ async fn create_tls_policy(
             mode: Set(payload.mode),
             domains: Set(payload.domains),
             status: Set("pending".to_string()),
+            options: Set(payload.options),
             ..Default::default()
         };
         Ok::<_, anyhow::Error>(active.insert(txn).await?)
@@ -640,6 +670,9 @@ async fn update_tls_policy(
             if let Some(status) = payload.status {
                 active.status = Set(status);
             }
+            if let Some(options) = payload.options {
+                active.options = Set(Some(options));
+            }
             active.updated_at = Set(Utc::now().into());
 
             let updated = active.update(txn).await?;
@@ -707,14 +740,8 @@ struct ValidateResponse {
 
 async fn validate_config(State(state): State<AppState>) -> Result<Json<ValidateResponse>> {
     let snapshot = build_snapshot(&state.db).await?;
-
     let validation_ctx = ValidationContext::new(state.http_port_range, state.https_port_range);
-    let validation_errors = validation_ctx.validate_snapshot(&snapshot);
-
-    let mut errors: Vec<String> = validation_errors.iter().map(|e| e.description()).collect();
-
-    // 添加业务逻辑验证
-    validate_snapshot_business_rules(&snapshot, &mut errors);
+    let errors = snapshot_errors(&validation_ctx, &snapshot);
 
     Ok(Json(ValidateResponse {
         valid: errors.is_empty(),
@@ -727,15 +754,8 @@ async fn publish_config(
     Json(payload): Json<PublishRequest>,
 ) -> Result<Json<ConfigVersionModel>> {
     let snapshot = build_snapshot(&state.db).await?;
-
     let validation_ctx = ValidationContext::new(state.http_port_range, state.https_port_range);
-    let validation_errors = validation_ctx.validate_snapshot(&snapshot);
-
-    let mut errors: Vec<String> = validation_errors.iter().map(|e| e.description()).collect();
-
-    // 添加业务逻辑验证
-    validate_snapshot_business_rules(&snapshot, &mut errors);
-
+    let errors = snapshot_errors(&validation_ctx, &snapshot);
     if !errors.is_empty() {
         return Err(GatewayError::validation(errors.join("; ")));
     }
@@ -746,20 +766,7 @@ async fn publish_config(
     let version = txn_with!(
         &state.db,
         |txn, actor, snapshot_json| {
-            config_versions::Entity::update_many()
-                .col_expr(config_versions::Column::Status, Expr::value("archived"))
-                .filter(config_versions::Column::Status.eq("published"))
-                .exec(txn)
-                .await?;
-
-            let active = config_versions::ActiveModel {
-                id: Set(Uuid::new_v4()),
-                snapshot_json: Set(snapshot_json),
-                status: Set("published".to_string()),
-                created_by: Set(actor.clone()),
-                ..Default::default()
-            };
-            let version = active.insert(txn).await?;
+            let version = insert_published_version(txn, snapshot_json, &actor).await?;
             Ok::<_, anyhow::Error>(version)
         },
         &actor,
@@ -777,6 +784,272 @@ async fn publish_config(
     Ok(Json(version))
 }
 
+/// 维护模式开关的目标
+#[derive(Debug, Clone, Copy)]
+enum MaintenanceTarget {
+    Listener(Uuid),
+    Route(Uuid),
+}
+
+impl MaintenanceTarget {
+    fn label(self) -> String {
+        match self {
+            Self::Listener(id) => format!("listener {}", id),
+            Self::Route(id) => format!("route {}", id),
+        }
+    }
+
+    fn audit_action(self) -> &'static str {
+        match self {
+            Self::Listener(_) => "listener.maintenance",
+            Self::Route(_) => "route.maintenance",
+        }
+    }
+}
+
+async fn set_listener_maintenance(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<MaintenanceOptions>,
+) -> Result<Json<ConfigVersionModel>> {
+    let actor = actor_from_headers(&headers);
+    let version =
+        publish_maintenance(&state, actor, MaintenanceTarget::Listener(id), payload).await?;
+    Ok(Json(version))
+}
+
+async fn set_route_maintenance(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<MaintenanceOptions>,
+) -> Result<Json<ConfigVersionModel>> {
+    let actor = actor_from_headers(&headers);
+    let version = publish_maintenance(&state, actor, MaintenanceTarget::Route(id), payload).await?;
+    Ok(Json(version))
+}
+
+/// 在同一事务中写入维护配置并发布新版本。
+///
+/// 新版本基于当前已发布的快照，只替换目标的 `options.maintenance`，
+/// 不会把其他尚未发布的草稿修改一并发布。
+async fn publish_maintenance(
+    state: &AppState,
+    actor: String,
+    target: MaintenanceTarget,
+    maintenance: MaintenanceOptions,
+) -> Result<ConfigVersionModel> {
+    let validation_ctx = ValidationContext::new(state.http_port_range, state.https_port_range);
+    let maintenance_json = serde_json::to_value(&maintenance)?;
+    let audit_actor = actor.clone();
+
+    let (version, snapshot, audit_diff) = txn!(&state.db, |txn| {
+        let published = config_versions::Entity::find()
+            .filter(config_versions::Column::Status.eq("published"))
+            .one(txn)
+            .await?
+            .ok_or_else(|| GatewayError::not_found("no published config"))?;
+        let mut snapshot: Snapshot = serde_json::from_value(published.snapshot_json)?;
+
+        let (before, after) = match target {
+            MaintenanceTarget::Listener(id) => {
+                let listener = listeners::Entity::find_by_id(id)
+                    .one(txn)
+                    .await?
+                    .ok_or_else(|| GatewayError::not_found("listener not found"))?;
+                let published = snapshot
+                    .listeners
+                    .iter_mut()
+                    .find(|l| l.id == id)
+                    .ok_or_else(|| {
+                        GatewayError::validation(format!("listener {} is not published", id))
+                    })?;
+                let owner = target.label();
+                let before = listener.options.clone();
+                let after = with_maintenance(&owner, before.clone(), &maintenance_json)?;
+                published.options = Some(with_maintenance(
+                    &owner,
+                    published.options.take(),
+                    &maintenance_json,
+                )?);
+
+                let mut active: listeners::ActiveModel = listener.into();
+                active.options = Set(Some(after.clone()));
+                active.updated_at = Set(Utc::now().into());
+                active.update(txn).await?;
+                (before, after)
+            }
+            MaintenanceTarget::Route(id) => {
+                let route = routes::Entity::find_by_id(id)
+                    .one(txn)
+                    .await?
+                    .ok_or_else(|| GatewayError::not_found("route not found"))?;
+                let published =
+                    snapshot
+                        .routes
+                        .iter_mut()
+                        .find(|r| r.id == id)
+                        .ok_or_else(|| {
+                            GatewayError::validation(format!("route {} is not published", id))
+                        })?;
+                let owner = target.label();
+                let before = route.options.clone();
+                let after = with_maintenance(&owner, before.clone(), &maintenance_json)?;
+                published.options = Some(with_maintenance(
+                    &owner,
+                    published.options.take(),
+                    &maintenance_json,
+                )?);
+
+                let mut active: routes::ActiveModel = route.into();
+                active.options = Set(Some(after.clone()));
+                active.updated_at = Set(Utc::now().into());
+                active.update(txn).await?;
+                (before, after)
+            }
+        };
+
+        let errors = snapshot_errors(&validation_ctx, &snapshot);
+        if !errors.is_empty() {
+            return Err(GatewayError::validation(errors.join("; ")));
+        }
+        let snapshot_json = serde_json::to_value(&snapshot)?;
+        let version = insert_published_version(txn, snapshot_json, &actor).await?;
+        let diff = json!({
+            "target": target.label(),
+            "version_id": version.id,
+            "before": before,
+            "after": after,
+        });
+        Ok::<_, GatewayError>((version, snapshot, diff))
+    })?;
+
+    state.snapshots.apply(snapshot).await?;
+
+    spawn_audit(
+        state.db.clone(),
+        audit_actor,
+        target.audit_action().to_string(),
+        audit_diff,
+    );
+    Ok(version)
+}
+
+/// 将维护配置写入 options 对象，保留其他扩展配置
+fn with_maintenance(
+    owner: &str,
+    options: Option<JsonValue>,
+    maintenance: &JsonValue,
+) -> Result<JsonValue> {
+    let mut options = match options {
+        Some(JsonValue::Object(map)) => map,
+        None | Some(JsonValue::Null) => serde_json::Map::new(),
+        Some(_) => {
+            return Err(GatewayError::validation(format!(
+                "{} invalid options",
+                owner
+            )));
+        }
+    };
+    options.insert("maintenance".to_string(), maintenance.clone());
+    Ok(JsonValue::Object(options))
+}
+
+async fn set_target_drain(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<TargetDrainRequest>,
+) -> Result<Json<ConfigVersionModel>> {
+    let actor = actor_from_headers(&headers);
+    let validation_ctx = ValidationContext::new(state.http_port_range, state.https_port_range);
+    let audit_actor = actor.clone();
+
+    // 与维护模式相同：基于已发布快照只改动该目标，不会一并发布其他草稿修改
+    let (version, snapshot, audit_diff) = txn!(&state.db, |txn| {
+        let published = config_versions::Entity::find()
+            .filter(config_versions::Column::Status.eq("published"))
+            .one(txn)
+            .await?
+            .ok_or_else(|| GatewayError::not_found("no published config"))?;
+        let mut snapshot: Snapshot = serde_json::from_value(published.snapshot_json)?;
+
+        let target = upstream_targets::Entity::find_by_id(id)
+            .one(txn)
+            .await?
+            .ok_or_else(|| GatewayError::not_found("upstream target not found"))?;
+        let published_target = snapshot
+            .upstream_targets
+            .iter_mut()
+            .find(|t| t.id == id)
+            .ok_or_else(|| {
+                GatewayError::validation(format!("upstream target {} is not published", id))
+            })?;
+
+        let before = target.drained_at;
+        let after = match (payload.draining, before) {
+            (true, Some(drained_at)) => Some(drained_at),
+            (true, None) => Some(Utc::now().into()),
+            (false, _) => None,
+        };
+        published_target.drained_at = after;
+
+        let mut active: upstream_targets::ActiveModel = target.into();
+        active.drained_at = Set(after);
+        active.update(txn).await?;
+
+        let errors = snapshot_errors(&validation_ctx, &snapshot);
+        if !errors.is_empty() {
+            return Err(GatewayError::validation(errors.join("; ")));
+        }
+        let snapshot_json = serde_json::to_value(&snapshot)?;
+        let version = insert_published_version(txn, snapshot_json, &actor).await?;
+        let diff = json!({
+            "id": id,
+            "version_id": version.id,
+            "before": before,
+            "after": after,
+        });
+        Ok::<_, GatewayError>((version, snapshot, diff))
+    })?;
+
+    state.snapshots.apply(snapshot).await?;
+
+    spawn_audit(
+        state.db.clone(),
+        audit_actor,
+        "upstream_target.drain".to_string(),
+        audit_diff,
+    );
+    Ok(Json(version))
+}
+
+/// 归档当前发布版本并写入新的发布版本
+async fn insert_published_version<C>(
+    db: &C,
+    snapshot_json: JsonValue,
+    actor: &str,
+) -> std::result::Result<ConfigVersionModel, sea_orm::DbErr>
+where
+    C: ConnectionTrait,
+{
+    config_versions::Entity::update_many()
+        .col_expr(config_versions::Column::Status, Expr::value("archived"))
+        .filter(config_versions::Column::Status.eq("published"))
+        .exec(db)
+        .await?;
+
+    let active = config_versions::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        snapshot_json: Set(snapshot_json),
+        status: Set("published".to_string()),
+        created_by: Set(actor.to_string()),
+        ..Default::default()
+    };
+    active.insert(db).await
+}
+
 async fn rollback_config(
     State(state): State<AppState>,
     Json(payload): Json<RollbackRequest>,
@@ -918,6 +1191,12 @@ async fn list_nodes(State(state): State<AppState>) -> Result<Json<Vec<NodeStatus
                 (Some(published), Some(node)) => published == node,
                 _ => false,
             };
+            let draining_targets = n
+                .metadata
+                .clone()
+                .and_then(|metadata| serde_json::from_value::<NodeMetadata>(metadata).ok())
+                .map(|metadata| metadata.draining_targets)
+                .unwrap_or_default();
             NodeStatusView {
                 id: n.id,
                 node_id: n.node_id,
@@ -926,6 +1205,7 @@ async fn list_nodes(State(state): State<AppState>) -> Result<Json<Vec<NodeStatus
                 consistent,
                 heartbeat_at: n.heartbeat_at.to_rfc3339(),
                 metadata: n.metadata,
+                draining_targets,
             }
         })
         .collect();
@@ -1028,6 +1308,16 @@ fn hex_value(byte: u8) -> Option<u8> {
     }
 }
 
+/// 快照的端口范围校验与业务逻辑校验错误
+fn snapshot_errors(validation_ctx: &ValidationContext, snapshot: &Snapshot) -> Vec<String> {
+    let validation_errors = validation_ctx.validate_snapshot(snapshot);
+    let mut errors: Vec<String> = validation_errors.iter().map(|e| e.description()).collect();
+
+    // 添加业务逻辑验证
+    validate_snapshot_business_rules(snapshot, &mut errors);
+    errors
+}
+
 /// 验证快照的业务逻辑规则
 ///
 /// 此函数专注于验证业务逻辑约束，如：
@@ -1074,6 +1364,24 @@ fn validate_snapshot_business_rules(snapshot: &Snapshot, errors: &mut Vec<String
     validate_route_conflicts(&snapshot.routes, errors);
 
     // 验证每个路由
+    let listener_protocols: HashMap<Uuid, &str> = snapshot
+        .listeners
+        .iter()
+        .map(|l| (l.id, l.protocol.as_str()))
+        .collect();
+    let pool_protocols: HashMap<Uuid, String> = snapshot
+        .upstream_pools
+        .iter()
+        .map(|pool| {
+            let protocol = pool
+                .options
+                .clone()
+                .and_then(|options| serde_json::from_value::<PoolOptions>(options).ok())
+                .and_then(|options| options.protocol)
+                .unwrap_or_else(|| "http1".to_string());
+            (pool.id, protocol)
+        })
+        .collect();
     for route in &snapshot.routes {
         validate_route(
             route,
@@ -1082,6 +1390,8 @@ fn validate_snapshot_business_rules(snapshot: &Snapshot, errors: &mut Vec<String
             &pool_ids,
             errors,
         );
+        validate_route_listener_protocol(route, &listener_protocols, errors);
+        validate_route_pool_protocol(route, &pool_protocols, errors);
     }
 }
 
@@ -1095,20 +1405,89 @@ struct RouteConflictKey {
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
 enum RouteMatchKey {
     Port,
-    Match(CanonicalRouteMatch),
+    Match(Box<CanonicalRouteMatch>),
 }
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
 struct CanonicalRouteMatch {
     host: Option<String>,
+    host_regex: Option<String>,
     path_prefix: Option<String>,
     path_regex: Option<String>,
     method: Option<Vec<String>>,
-    headers: Option<BTreeMap<String, String>>,
-    query: Option<BTreeMap<String, String>>,
+    headers: Option<BTreeMap<String, CanonicalValueMatch>>,
+    query: Option<BTreeMap<String, CanonicalValueMatch>>,
+    cookies: Option<BTreeMap<String, CanonicalValueMatch>>,
+    source_cidrs: Option<Vec<String>>,
+    client_cert_subject: Option<CanonicalValueMatch>,
     ws: Option<bool>,
 }
 
+/// 头 / 查询参数 / Cookie 条件的归一化形式：简写统一为运算符形式，取值排序去重，
+/// 忽略大小写时取值统一转为小写
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+struct CanonicalValueMatch {
+    present: Option<bool>,
+    values: Option<Vec<String>>,
+    prefix: Option<String>,
+    suffix: Option<String>,
+    regex: Option<String>,
+    ignore_case: bool,
+    invert: bool,
+}
+
+impl CanonicalValueMatch {
+    fn from_value(value: &ValueMatch) -> Self {
+        let rule = value.to_rule();
+        let ignore_case = rule.ignore_case;
+        let fold = |value: String| {
+            if ignore_case {
+                value.to_lowercase()
+            } else {
+                value
+            }
+        };
+        let values = rule.values.clone().map(|values| {
+            let mut values: Vec<String> = values.into_iter().map(fold).collect();
+            values.sort();
+            values.dedup();
+            values
+        });
+        // 设置了取值条件时 present=true 是冗余的
+        let present = match rule.present {
+            Some(true) if rule.has_value_condition() => None,
+            present => present,
+        };
+        Self {
+            present,
+            values,
+            prefix: rule.prefix.map(fold),
+            suffix: rule.suffix.map(fold),
+            regex: rule.regex,
+            ignore_case,
+            invert: rule.invert,
+        }
+    }
+}
+
+fn canonical_value_matches(
+    map: Option<HashMap<String, ValueMatch>>,
+    fold_key: bool,
+) -> Option<BTreeMap<String, CanonicalValueMatch>> {
+    map.map(|map| {
+        map.into_iter()
+            .map(|(key, value)| {
+                let key = if fold_key {
+                    key.to_ascii_lowercase()
+                } else {
+                    key
+                };
+                (key, CanonicalValueMatch::from_value(&value))
+            })
+            .collect()
+    })
+}
+
 impl CanonicalRouteMatch {
     fn from_match_expr(kind: &str, expr: &JsonValue) -> Option<Self> {
         let parsed: RouteMatch = serde_json::from_value(expr.clone()).ok()?;
@@ -1126,14 +1505,19 @@ impl CanonicalRouteMatch {
             method = None;
         }
 
-        let headers = parsed.headers.map(|map| {
-            map.into_iter()
-                .map(|(k, v)| (k.to_ascii_lowercase(), v))
-                .collect::<BTreeMap<_, _>>()
+        let headers = canonical_value_matches(parsed.headers, true);
+        let query = canonical_value_matches(parsed.query, false);
+        let cookies = canonical_value_matches(parsed.cookies, false);
+        let source_cidrs = parsed.source_cidrs.map(|cidrs| {
+            let mut cidrs: Vec<String> = cidrs.iter().map(|c| c.trim().to_string()).collect();
+            cidrs.sort();
+            cidrs.dedup();
+            cidrs
         });
-        let query = parsed
-            .query
-            .map(|map| map.into_iter().collect::<BTreeMap<_, _>>());
+        let client_cert_subject = parsed
+            .client_cert_subject
+            .as_ref()
+            .map(CanonicalValueMatch::from_value);
 
         let ws = if kind.eq_ignore_ascii_case("ws") {
             Some(true)
@@ -1142,12 +1526,16 @@ impl CanonicalRouteMatch {
         };
 
         Some(Self {
-            host: parsed.host.map(|h| h.to_ascii_lowercase()),
+            host: parsed.host.map(|h| normalize_host(&h)),
+            host_regex: parsed.host_regex,
             path_prefix: parsed.path_prefix,
             path_regex: parsed.path_regex,
             method,
             headers,
             query,
+            cookies,
+            source_cidrs,
+            client_cert_subject,
             ws,
         })
     }
@@ -1161,11 +1549,13 @@ fn validate_route_conflicts(
     for route in routes.iter().filter(|r| r.enabled) {
         let kind = route.r#type.to_ascii_lowercase();
         let match_key = match kind.as_str() {
-            "port" => RouteMatchKey::Port,
-            "path" | "ws" => match CanonicalRouteMatch::from_match_expr(&kind, &route.match_expr) {
-                Some(match_expr) => RouteMatchKey::Match(match_expr),
-                None => continue,
-            },
+            "port" | "tcp" => RouteMatchKey::Port,
+            "path" | "ws" | "sni" => {
+                match CanonicalRouteMatch::from_match_expr(&kind, &route.match_expr) {
+                    Some(match_expr) => RouteMatchKey::Match(Box::new(match_expr)),
+                    None => continue,
+                }
+            }
             _ => continue,
         };
 
@@ -1195,7 +1585,7 @@ fn validate_listener(
         ));
     }
     match listener.protocol.to_ascii_lowercase().as_str() {
-        "http" | "https" => {}
+        "http" | "https" | "tcp" | "tls_passthrough" => {}
         _ => errors.push(format!(
             "listener {} invalid protocol {}",
             listener.id, listener.protocol
@@ -1211,6 +1601,154 @@ fn validate_listener(
             )),
         }
     }
+    if let Some(options) = &listener.options {
+        match serde_json::from_value::<ListenerOptions>(options.clone()) {
+            Ok(options) => validate_listener_options(listener, &options, errors),
+            Err(_) => errors.push(format!("listener {} invalid options", listener.id)),
+        }
+    }
+}
+
+fn validate_listener_options(
+    listener: &gateway_common::entities::listeners::Model,
+    options: &ListenerOptions,
+    errors: &mut Vec<String>,
+) {
+    if let Some(request_id) = &options.request_id {
+        if let Some(header) = &request_id.header
+            && !is_header_name(header)
+        {
+            errors.push(format!(
+                "listener {} request_id header {} invalid",
+                listener.id, header
+            ));
+        }
+        for cidr in &request_id.trusted_cidrs {
+            if IpCidr::parse(cidr).is_none() {
+                errors.push(format!(
+                    "listener {} request_id trusted_cidrs invalid cidr {}",
+                    listener.id, cidr
+                ));
+            }
+        }
+    }
+    if let Some(forwarded) = &options.forwarded {
+        for cidr in &forwarded.trusted_proxies {
+            if IpCidr::parse(cidr).is_none() {
+                errors.push(format!(
+                    "listener {} forwarded trusted_proxies invalid cidr {}",
+                    listener.id, cidr
+                ));
+            }
+        }
+        if let Some(mode) = &forwarded.mode {
+            match mode.to_ascii_lowercase().as_str() {
+                "append" | "replace" => {}
+                _ => errors.push(format!(
+                    "listener {} forwarded invalid mode {}",
+                    listener.id, mode
+                )),
+            }
+        }
+    }
+    // tcp / tls_passthrough 监听器不解析 HTTP，HTTP 层配置对其无效
+    if listener.protocol.eq_ignore_ascii_case("tcp")
+        || listener.protocol.eq_ignore_ascii_case("tls_passthrough")
+    {
+        reject_options(
+            &format!("listener {}", listener.id),
+            &format!("{} listener", listener.protocol),
+            &[
+                ("request_id", options.request_id.is_some()),
+                ("forwarded", options.forwarded.is_some()),
+                ("http2", options.http2.is_some()),
+                ("error_pages", options.error_pages.is_some()),
+                ("maintenance", options.maintenance.is_some()),
+                ("limits", options.limits.is_some()),
+                ("security_headers", options.security_headers.is_some()),
+            ],
+            errors,
+        );
+    }
+    if let Some(error_pages) = &options.error_pages {
+        validate_error_pages(&format!("listener {}", listener.id), error_pages, errors);
+    }
+    if let Some(maintenance) = &options.maintenance {
+        validate_maintenance(&format!("listener {}", listener.id), maintenance, errors);
+    }
+    if let Some(limits) = &options.limits {
+        validate_request_limits(&format!("listener {}", listener.id), limits, errors);
+    }
+    if let Some(security_headers) = &options.security_headers {
+        validate_security_headers(
+            &format!("listener {}", listener.id),
+            security_headers,
+            errors,
+        );
+    }
+    if let Some(http2) = &options.http2 {
+        if http2.h2c == Some(true) && !listener.protocol.eq_ignore_ascii_case("http") {
+            errors.push(format!(
+                "listener {} h2c only supported on http listener",
+                listener.id
+            ));
+        }
+        if http2.max_concurrent_streams == Some(0) {
+            errors.push(format!(
+                "listener {} http2 max_concurrent_streams must be positive",
+                listener.id
+            ));
+        }
+        if http2.max_header_list_size == Some(0) {
+            errors.push(format!(
+                "listener {} http2 max_header_list_size must be positive",
+                listener.id
+            ));
+        }
+    }
+    for cidr in &options.proxy_protocol_trusted_cidrs {
+        if IpCidr::parse(cidr).is_none() {
+            errors.push(format!(
+                "listener {} proxy_protocol_trusted_cidrs invalid cidr {}",
+                listener.id, cidr
+            ));
+        }
+    }
+    if let Some(mode) = &options.proxy_protocol {
+        match mode.to_ascii_lowercase().as_str() {
+            "off" => {}
+            "optional" | "required" => {
+                // 未限定来源时任意客户端都能伪造源地址
+                if options.proxy_protocol_trusted_cidrs.is_empty() {
+                    errors.push(format!(
+                        "listener {} proxy_protocol requires proxy_protocol_trusted_cidrs",
+                        listener.id
+                    ));
+                }
+            }
+            _ => errors.push(format!(
+                "listener {} invalid proxy_protocol {}",
+                listener.id, mode
+            )),
+        }
+    }
+}
+
+/// 对不支持的配置项逐个报错；`fields` 为（配置项名称, 是否已设置）
+fn reject_options(owner: &str, target: &str, fields: &[(&str, bool)], errors: &mut Vec<String>) {
+    for (name, set) in fields {
+        if *set {
+            errors.push(format!("{} {} not supported on {}", owner, name, target));
+        }
+    }
+}
+
+/// 判断是否为合法的 HTTP 头名称（RFC 7230 token）
+fn is_header_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
 }
 
 fn validate_upstream_pool(
@@ -1225,6 +1763,13 @@ fn validate_upstream_pool(
         )),
     }
 
+    if let Some(options) = &pool.options {
+        match serde_json::from_value::<PoolOptions>(options.clone()) {
+            Ok(options) => validate_pool_options(pool.id, &options, errors),
+            Err(_) => errors.push(format!("upstream pool {} invalid options", pool.id)),
+        }
+    }
+
     if let Some(health_check) = &pool.health_check {
         let obj = match health_check.as_object() {
             Some(obj) => obj,
@@ -1271,41 +1816,191 @@ fn validate_upstream_pool(
     }
 }
 
-fn validate_upstream_target(
-    target: &gateway_common::entities::upstream_targets::Model,
-    pool_ids: &HashSet<Uuid>,
-    errors: &mut Vec<String>,
-) {
-    if !pool_ids.contains(&target.pool_id) {
+/// 上游池允许配置的最大重试次数
+const MAX_POOL_RETRIES: u32 = 5;
+
+fn validate_pool_options(pool_id: Uuid, options: &PoolOptions, errors: &mut Vec<String>) {
+    if let Some(version) = &options.proxy_protocol {
+        match version.to_ascii_lowercase().as_str() {
+            "v1" | "v2" => {}
+            _ => errors.push(format!(
+                "upstream pool {} invalid proxy_protocol {}",
+                pool_id, version
+            )),
+        }
+    }
+    if let Some(protocol) = &options.protocol {
+        match protocol.to_ascii_lowercase().as_str() {
+            "http1" => {}
+            // PROXY 头在连接建立（含 TLS 与 HTTP/2 握手）之后才写入，会混入 TLS / HTTP/2 数据流
+            "h2" | "h2c" => {
+                if options.proxy_protocol.is_some() {
+                    errors.push(format!(
+                        "upstream pool {} proxy_protocol not supported with protocol {}",
+                        pool_id, protocol
+                    ));
+                }
+            }
+            _ => errors.push(format!(
+                "upstream pool {} invalid protocol {}",
+                pool_id, protocol
+            )),
+        }
+    }
+    if let Some(sni) = &options.sni
+        && (!is_sni_pattern(sni) || sni.starts_with("*."))
+    {
+        errors.push(format!("upstream pool {} invalid sni {}", pool_id, sni));
+    }
+    if let Some(max_retries) = options.max_retries
+        && max_retries > MAX_POOL_RETRIES
+    {
         errors.push(format!(
-            "upstream target {} pool not found {}",
-            target.id, target.pool_id
+            "upstream pool {} max_retries must be <= {}",
+            pool_id, MAX_POOL_RETRIES
         ));
     }
-    if target.weight < 1 {
+    for status in &options.grpc_retry_on {
+        if gateway_common::grpc::parse_code(status).is_none() {
+            errors.push(format!(
+                "upstream pool {} invalid grpc_retry_on status {}",
+                pool_id, status
+            ));
+        }
+    }
+    if options.drain_timeout_secs == Some(0) {
         errors.push(format!(
-            "upstream target {} invalid weight {}",
-            target.id, target.weight
+            "upstream pool {} drain_timeout_secs must be positive",
+            pool_id
         ));
     }
-    if parse_host_port(&target.address).is_none() {
+    if let Some(discovery) = &options.discovery {
+        validate_discovery(pool_id, discovery, errors);
+    }
+    if let Some(threshold) = options.failover_threshold
+        && !(1..=100).contains(&threshold)
+    {
         errors.push(format!(
-            "upstream target {} invalid address {}",
-            target.id, target.address
+            "upstream pool {} failover_threshold must be between 1 and 100",
+            pool_id
         ));
     }
+    if let Some(keepalive) = &options.keepalive {
+        if keepalive.idle_timeout_secs == Some(0) {
+            errors.push(format!(
+                "upstream pool {} keepalive idle_timeout_secs must be positive",
+                pool_id
+            ));
+        }
+        if keepalive.max_requests == Some(0) {
+            errors.push(format!(
+                "upstream pool {} keepalive max_requests must be positive",
+                pool_id
+            ));
+        }
+    }
 }
 
-fn validate_tls_policy(
-    policy: &gateway_common::entities::tls_policies::Model,
-    errors: &mut Vec<String>,
-) {
-    if policy.domains.is_empty() {
-        errors.push(format!("tls policy {} domains empty", policy.id));
-    }
-    match policy.mode.to_ascii_lowercase().as_str() {
-        "auto" | "manual" => {}
-        _ => errors.push(format!(
+fn validate_discovery(pool_id: Uuid, discovery: &DiscoveryOptions, errors: &mut Vec<String>) {
+    let name = discovery
+        .name
+        .as_deref()
+        .filter(|name| !name.trim().is_empty());
+    match discovery.r#type.to_ascii_lowercase().as_str() {
+        "dns" => {
+            if name.is_none() {
+                errors.push(format!(
+                    "upstream pool {} discovery dns requires name",
+                    pool_id
+                ));
+            }
+            if discovery.port.is_none_or(|port| port == 0) {
+                errors.push(format!(
+                    "upstream pool {} discovery dns requires port",
+                    pool_id
+                ));
+            }
+            if discovery.weight.is_some_and(|weight| weight < 1) {
+                errors.push(format!(
+                    "upstream pool {} discovery weight must be positive",
+                    pool_id
+                ));
+            }
+        }
+        "srv" => {
+            if name.is_none() {
+                errors.push(format!(
+                    "upstream pool {} discovery srv requires name",
+                    pool_id
+                ));
+            }
+        }
+        "file" => {
+            if discovery
+                .path
+                .as_deref()
+                .is_none_or(|path| path.trim().is_empty())
+            {
+                errors.push(format!(
+                    "upstream pool {} discovery file requires path",
+                    pool_id
+                ));
+            }
+        }
+        other => errors.push(format!(
+            "upstream pool {} invalid discovery type {}",
+            pool_id, other
+        )),
+    }
+    if discovery.interval_secs == Some(0) {
+        errors.push(format!(
+            "upstream pool {} discovery interval_secs must be positive",
+            pool_id
+        ));
+    }
+}
+
+fn validate_upstream_target(
+    target: &gateway_common::entities::upstream_targets::Model,
+    pool_ids: &HashSet<Uuid>,
+    errors: &mut Vec<String>,
+) {
+    if !pool_ids.contains(&target.pool_id) {
+        errors.push(format!(
+            "upstream target {} pool not found {}",
+            target.id, target.pool_id
+        ));
+    }
+    if target.weight < 1 {
+        errors.push(format!(
+            "upstream target {} invalid weight {}",
+            target.id, target.weight
+        ));
+    }
+    if target.priority < 0 {
+        errors.push(format!(
+            "upstream target {} invalid priority {}",
+            target.id, target.priority
+        ));
+    }
+    if unix_socket_path(&target.address).is_none() && parse_host_port(&target.address).is_none() {
+        errors.push(format!(
+            "upstream target {} invalid address {}",
+            target.id, target.address
+        ));
+    }
+}
+
+fn validate_tls_policy(
+    policy: &gateway_common::entities::tls_policies::Model,
+    errors: &mut Vec<String>,
+) {
+    if policy.domains.is_empty() {
+        errors.push(format!("tls policy {} domains empty", policy.id));
+    }
+    match policy.mode.to_ascii_lowercase().as_str() {
+        "auto" | "manual" => {}
+        _ => errors.push(format!(
             "tls policy {} invalid mode {}",
             policy.id, policy.mode
         )),
@@ -1317,6 +2012,31 @@ fn validate_tls_policy(
             policy.id, policy.status
         )),
     }
+    if let Some(options) = &policy.options {
+        match serde_json::from_value::<TlsPolicyOptions>(options.clone()) {
+            Ok(options) => {
+                if let Some(alpn) = &options.alpn {
+                    if alpn.is_empty() {
+                        errors.push(format!("tls policy {} alpn empty", policy.id));
+                    }
+                    for proto in alpn {
+                        if proto != "h2" && proto != "http/1.1" {
+                            errors.push(format!(
+                                "tls policy {} unsupported alpn {}",
+                                policy.id, proto
+                            ));
+                        }
+                    }
+                }
+                if let Some(pem) = &options.client_ca_pem
+                    && !is_valid_ca_pem(pem)
+                {
+                    errors.push(format!("tls policy {} invalid client_ca_pem", policy.id));
+                }
+            }
+            Err(_) => errors.push(format!("tls policy {} invalid options", policy.id)),
+        }
+    }
 }
 
 fn validate_route(
@@ -1350,12 +2070,42 @@ fn validate_route(
             route.id, route.priority
         ));
     }
+    if let Some(options) = &route.options {
+        match serde_json::from_value::<RouteOptions>(options.clone()) {
+            Ok(options) => validate_route_options(route, &options, pool_ids, errors),
+            Err(_) => errors.push(format!("route {} invalid options", route.id)),
+        }
+    }
 
     match route.r#type.to_ascii_lowercase().as_str() {
-        "port" => {}
+        "port" | "tcp" => {}
+        "sni" => match serde_json::from_value::<RouteMatch>(route.match_expr.clone()) {
+            Ok(parsed) => {
+                if parsed.path_prefix.is_some()
+                    || parsed.path_regex.is_some()
+                    || parsed.host_regex.is_some()
+                    || parsed.method.is_some()
+                    || parsed.headers.is_some()
+                    || parsed.query.is_some()
+                    || parsed.cookies.is_some()
+                    || parsed.source_cidrs.is_some()
+                    || parsed.client_cert_subject.is_some()
+                    || parsed.ws.is_some()
+                {
+                    errors.push(format!("route {} sni only supports host", route.id));
+                }
+                if let Some(host) = &parsed.host
+                    && !is_sni_pattern(host)
+                {
+                    errors.push(format!("route {} invalid sni host {}", route.id, host));
+                }
+            }
+            Err(_) => errors.push(format!("invalid match_expr for route {}", route.id)),
+        },
         "path" => match serde_json::from_value::<RouteMatch>(route.match_expr.clone()) {
             Ok(parsed) => {
                 if parsed.host.is_none()
+                    && parsed.host_regex.is_none()
                     && parsed.path_prefix.is_none()
                     && parsed.path_regex.is_none()
                 {
@@ -1364,6 +2114,8 @@ fn validate_route(
                         route.id
                     ));
                 }
+                validate_route_host(route.id, &parsed, errors);
+                validate_route_values(route.id, &parsed, errors);
             }
             Err(_) => errors.push(format!("invalid match_expr for route {}", route.id)),
         },
@@ -1373,6 +2125,7 @@ fn validate_route(
                     errors.push(format!("ws route must require ws for route {}", route.id));
                 }
                 if parsed.host.is_none()
+                    && parsed.host_regex.is_none()
                     && parsed.path_prefix.is_none()
                     && parsed.path_regex.is_none()
                 {
@@ -1381,6 +2134,8 @@ fn validate_route(
                         route.id
                     ));
                 }
+                validate_route_host(route.id, &parsed, errors);
+                validate_route_values(route.id, &parsed, errors);
             }
             Err(_) => errors.push(format!("invalid match_expr for route {}", route.id)),
         },
@@ -1391,6 +2146,513 @@ fn validate_route(
     }
 }
 
+fn validate_route_options(
+    route: &gateway_common::entities::routes::Model,
+    options: &RouteOptions,
+    pool_ids: &HashSet<Uuid>,
+    errors: &mut Vec<String>,
+) {
+    // tcp / sni 路由不解析 HTTP，HTTP 层配置对其无效
+    if matches!(route.r#type.to_ascii_lowercase().as_str(), "tcp" | "sni") {
+        reject_options(
+            &format!("route {}", route.id),
+            &format!("{} route", route.r#type),
+            &[
+                ("error_pages", options.error_pages.is_some()),
+                ("maintenance", options.maintenance.is_some()),
+                ("limits", options.limits.is_some()),
+                ("fault", options.fault.is_some()),
+                ("ext_auth", options.ext_auth.is_some()),
+                ("security_headers", options.security_headers.is_some()),
+            ],
+            errors,
+        );
+    }
+    if let Some(error_pages) = &options.error_pages {
+        validate_error_pages(&format!("route {}", route.id), error_pages, errors);
+    }
+    if let Some(maintenance) = &options.maintenance {
+        validate_maintenance(&format!("route {}", route.id), maintenance, errors);
+    }
+    if let Some(limits) = &options.limits {
+        validate_request_limits(&format!("route {}", route.id), limits, errors);
+    }
+    if let Some(websocket) = &options.websocket {
+        if !route.r#type.eq_ignore_ascii_case("ws") {
+            errors.push(format!(
+                "route {} websocket only supported on ws route",
+                route.id
+            ));
+        }
+        let fields = [
+            ("max_connections", websocket.max_connections.map(u64::from)),
+            ("idle_timeout_secs", websocket.idle_timeout_secs),
+            ("max_lifetime_secs", websocket.max_lifetime_secs),
+            ("ping_interval_secs", websocket.ping_interval_secs),
+        ];
+        for (name, value) in fields {
+            if value == Some(0) {
+                errors.push(format!(
+                    "route {} websocket {} must be positive",
+                    route.id, name
+                ));
+            }
+        }
+    }
+    if let Some(fault) = &options.fault {
+        validate_fault(route.id, fault, errors);
+    }
+    if let Some(ext_auth) = &options.ext_auth {
+        validate_ext_auth(route.id, ext_auth, pool_ids, errors);
+    }
+    if let Some(security_headers) = &options.security_headers {
+        validate_security_headers(&format!("route {}", route.id), security_headers, errors);
+    }
+}
+
+/// HSTS 预加载列表要求的最小 max-age（一年）
+const HSTS_PRELOAD_MIN_AGE: u64 = 31_536_000;
+
+const REFERRER_POLICIES: [&str; 8] = [
+    "no-referrer",
+    "no-referrer-when-downgrade",
+    "origin",
+    "origin-when-cross-origin",
+    "same-origin",
+    "strict-origin",
+    "strict-origin-when-cross-origin",
+    "unsafe-url",
+];
+
+fn validate_security_headers(
+    owner: &str,
+    options: &SecurityHeadersOptions,
+    errors: &mut Vec<String>,
+) {
+    if let Some(hsts) = &options.hsts {
+        if hsts.max_age_secs == Some(0) {
+            errors.push(format!(
+                "{} security_headers hsts max_age_secs must be positive",
+                owner
+            ));
+        }
+        if hsts.preload == Some(true)
+            && (hsts.include_subdomains != Some(true)
+                || hsts
+                    .max_age_secs
+                    .is_some_and(|age| age < HSTS_PRELOAD_MIN_AGE))
+        {
+            errors.push(format!(
+                "{} security_headers hsts preload requires include_subdomains and max_age_secs >= {}",
+                owner, HSTS_PRELOAD_MIN_AGE
+            ));
+        }
+    }
+    let values = [
+        ("content_type_options", &options.content_type_options),
+        ("frame_options", &options.frame_options),
+        ("referrer_policy", &options.referrer_policy),
+        ("content_security_policy", &options.content_security_policy),
+        ("permissions_policy", &options.permissions_policy),
+    ];
+    for (name, value) in values {
+        let Some(value) = value else {
+            continue;
+        };
+        if !value.bytes().all(|b| (0x20..0x7f).contains(&b)) {
+            errors.push(format!(
+                "{} security_headers {} invalid value {}",
+                owner, name, value
+            ));
+        }
+    }
+    if let Some(value) = &options.content_type_options
+        && !value.is_empty()
+        && !value.eq_ignore_ascii_case("nosniff")
+    {
+        errors.push(format!(
+            "{} security_headers invalid content_type_options {}",
+            owner, value
+        ));
+    }
+    if let Some(value) = &options.frame_options
+        && !value.is_empty()
+        && !value.eq_ignore_ascii_case("deny")
+        && !value.eq_ignore_ascii_case("sameorigin")
+    {
+        errors.push(format!(
+            "{} security_headers invalid frame_options {}",
+            owner, value
+        ));
+    }
+    if let Some(value) = &options.referrer_policy
+        && !value.is_empty()
+        && !value.split(',').all(|policy| {
+            REFERRER_POLICIES
+                .iter()
+                .any(|known| policy.trim().eq_ignore_ascii_case(known))
+        })
+    {
+        errors.push(format!(
+            "{} security_headers invalid referrer_policy {}",
+            owner, value
+        ));
+    }
+}
+
+fn validate_ext_auth(
+    route_id: Uuid,
+    ext_auth: &ExtAuthOptions,
+    pool_ids: &HashSet<Uuid>,
+    errors: &mut Vec<String>,
+) {
+    match (&ext_auth.url, ext_auth.upstream_pool_id) {
+        (Some(url), None) => {
+            let valid = ["http://", "https://"].iter().any(|scheme| {
+                url.len() > scheme.len()
+                    && url
+                        .get(..scheme.len())
+                        .is_some_and(|prefix| prefix.eq_ignore_ascii_case(scheme))
+            });
+            if !valid {
+                errors.push(format!("route {} ext_auth invalid url {}", route_id, url));
+            }
+        }
+        (None, Some(pool_id)) => {
+            if !pool_ids.contains(&pool_id) {
+                errors.push(format!(
+                    "route {} ext_auth upstream pool not found {}",
+                    route_id, pool_id
+                ));
+            }
+        }
+        _ => errors.push(format!(
+            "route {} ext_auth requires one of url or upstream_pool_id",
+            route_id
+        )),
+    }
+    if let Some(path) = &ext_auth.path
+        && (ext_auth.upstream_pool_id.is_none() || !path.starts_with('/'))
+    {
+        errors.push(format!("route {} ext_auth invalid path {}", route_id, path));
+    }
+    for (kind, headers) in [
+        ("request_headers", &ext_auth.request_headers),
+        ("response_headers", &ext_auth.response_headers),
+    ] {
+        for header in headers {
+            if !is_header_name(header) {
+                errors.push(format!(
+                    "route {} ext_auth {} {} invalid",
+                    route_id, kind, header
+                ));
+            }
+        }
+    }
+    if ext_auth.timeout_ms == Some(0) {
+        errors.push(format!(
+            "route {} ext_auth timeout_ms must be positive",
+            route_id
+        ));
+    }
+}
+
+fn validate_fault(route_id: Uuid, fault: &FaultOptions, errors: &mut Vec<String>) {
+    // 必须限定生效的请求，避免误对全部流量注入故障
+    if fault.headers.is_empty() {
+        errors.push(format!("route {} fault requires headers", route_id));
+    }
+    for (key, value) in &fault.headers {
+        validate_value_match(
+            &format!("route {} fault header {}", route_id, key),
+            value,
+            errors,
+        );
+    }
+    if fault.delay.is_none() && fault.abort.is_none() {
+        errors.push(format!("route {} fault requires delay or abort", route_id));
+    }
+    let valid_percentage = |percentage: f64| (0.0..=100.0).contains(&percentage);
+    if let Some(delay) = &fault.delay {
+        if !valid_percentage(delay.percentage) {
+            errors.push(format!(
+                "route {} fault delay percentage must be between 0 and 100",
+                route_id
+            ));
+        }
+        match (delay.fixed_ms, delay.random_ms) {
+            (Some(_), None) => {}
+            (None, Some([min, max])) if min <= max => {}
+            (None, Some(_)) => errors.push(format!(
+                "route {} fault delay random_ms min exceeds max",
+                route_id
+            )),
+            _ => errors.push(format!(
+                "route {} fault delay requires one of fixed_ms or random_ms",
+                route_id
+            )),
+        }
+    }
+    if let Some(abort) = &fault.abort {
+        if !valid_percentage(abort.percentage) {
+            errors.push(format!(
+                "route {} fault abort percentage must be between 0 and 100",
+                route_id
+            ));
+        }
+        match (abort.status, abort.reset) {
+            (None, true) => {}
+            (Some(status), false) if (200..=599).contains(&status) => {}
+            (Some(status), false) => errors.push(format!(
+                "route {} fault abort invalid status {}",
+                route_id, status
+            )),
+            _ => errors.push(format!(
+                "route {} fault abort requires one of status or reset",
+                route_id
+            )),
+        }
+    }
+}
+
+fn validate_request_limits(owner: &str, limits: &RequestLimitsOptions, errors: &mut Vec<String>) {
+    let fields = [
+        ("max_body_bytes", limits.max_body_bytes),
+        ("max_header_bytes", limits.max_header_bytes.map(u64::from)),
+        ("max_header_count", limits.max_header_count.map(u64::from)),
+        ("max_uri_length", limits.max_uri_length.map(u64::from)),
+    ];
+    for (name, value) in fields {
+        if value == Some(0) {
+            errors.push(format!("{} limits {} must be positive", owner, name));
+        }
+    }
+}
+
+fn validate_maintenance(owner: &str, options: &MaintenanceOptions, errors: &mut Vec<String>) {
+    for cidr in &options.bypass_cidrs {
+        if IpCidr::parse(cidr).is_none() {
+            errors.push(format!(
+                "{} maintenance bypass_cidrs invalid cidr {}",
+                owner, cidr
+            ));
+        }
+    }
+    if let Some(message) = &options.message
+        && message.len() > MAX_ERROR_PAGE_BYTES
+    {
+        errors.push(format!(
+            "{} maintenance message exceeds {} bytes",
+            owner, MAX_ERROR_PAGE_BYTES
+        ));
+    }
+}
+
+/// 单个错误响应体的最大字节数
+const MAX_ERROR_PAGE_BYTES: usize = 64 * 1024;
+
+/// 错误页的键为 400-599 的状态码或 `4xx` / `5xx`，每个键至少一个候选响应
+fn validate_error_pages(owner: &str, options: &ErrorPagesOptions, errors: &mut Vec<String>) {
+    for (key, pages) in &options.pages {
+        let valid_key = matches!(key.as_str(), "4xx" | "5xx")
+            || key
+                .parse::<u16>()
+                .is_ok_and(|status| (400..=599).contains(&status));
+        if !valid_key {
+            errors.push(format!("{} error_pages invalid status {}", owner, key));
+        }
+        if pages.is_empty() {
+            errors.push(format!("{} error_pages {} empty", owner, key));
+        }
+        for page in pages {
+            if !is_media_type(&page.content_type) {
+                errors.push(format!(
+                    "{} error_pages {} invalid content_type {}",
+                    owner, key, page.content_type
+                ));
+            }
+            if page.body.len() > MAX_ERROR_PAGE_BYTES {
+                errors.push(format!(
+                    "{} error_pages {} body exceeds {} bytes",
+                    owner, key, MAX_ERROR_PAGE_BYTES
+                ));
+            }
+        }
+    }
+}
+
+/// `type/subtype`，可带参数（如 `; charset=utf-8`），只允许可见 ASCII
+fn is_media_type(value: &str) -> bool {
+    let essence = value.split(';').next().unwrap_or("").trim();
+    let valid = |part: &str| {
+        !part.is_empty()
+            && part
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b"!#$&-^_.+".contains(&b))
+    };
+    value.bytes().all(|b| (0x20..0x7f).contains(&b))
+        && essence
+            .split_once('/')
+            .is_some_and(|(kind, subtype)| valid(kind) && valid(subtype))
+}
+
+/// L4 监听器只接受对应的 L4 路由：tcp 监听器对应 tcp 路由，tls_passthrough 监听器对应 sni 路由；
+/// HTTP 监听器不接受 L4 路由
+fn validate_route_listener_protocol(
+    route: &gateway_common::entities::routes::Model,
+    listener_protocols: &HashMap<Uuid, &str>,
+    errors: &mut Vec<String>,
+) {
+    let Some(protocol) = listener_protocols.get(&route.listener_id) else {
+        return;
+    };
+    let route_type = route.r#type.to_ascii_lowercase();
+    let allowed = match protocol.to_ascii_lowercase().as_str() {
+        "tcp" => route_type == "tcp",
+        "tls_passthrough" => route_type == "sni",
+        _ => route_type != "tcp" && route_type != "sni",
+    };
+    if !allowed {
+        errors.push(format!(
+            "route {} type {} not allowed on {} listener {}",
+            route.id, route.r#type, protocol, route.listener_id
+        ));
+    }
+    // 客户端证书只在网关终止 TLS 时可见
+    let client_cert = route
+        .match_expr
+        .get("client_cert_subject")
+        .is_some_and(|value| !value.is_null());
+    if client_cert && !protocol.eq_ignore_ascii_case("https") {
+        errors.push(format!(
+            "route {} client_cert_subject requires https listener",
+            route.id
+        ));
+    }
+}
+
+/// ws 路由的上游须为明文 HTTP/1.1：网关在升级后的连接上按 WebSocket 帧边界插入控制帧
+fn validate_route_pool_protocol(
+    route: &gateway_common::entities::routes::Model,
+    pool_protocols: &HashMap<Uuid, String>,
+    errors: &mut Vec<String>,
+) {
+    if !route.r#type.eq_ignore_ascii_case("ws") {
+        return;
+    }
+    let Some(protocol) = pool_protocols.get(&route.upstream_pool_id) else {
+        return;
+    };
+    if !protocol.eq_ignore_ascii_case("http1") {
+        errors.push(format!(
+            "route {} type ws requires http1 upstream pool, pool {} uses {}",
+            route.id, route.upstream_pool_id, protocol
+        ));
+    }
+}
+
+/// HTTP 路由的主机条件：host 为主机名或单级通配（端口在规范化时去除），host_regex 需可编译
+fn validate_route_host(route_id: Uuid, parsed: &RouteMatch, errors: &mut Vec<String>) {
+    if let Some(host) = &parsed.host
+        && !is_sni_pattern(&normalize_host(host))
+    {
+        errors.push(format!("route {} invalid host {}", route_id, host));
+    }
+    if let Some(expr) = &parsed.host_regex
+        && Regex::new(expr).is_err()
+    {
+        errors.push(format!("route {} invalid host_regex {}", route_id, expr));
+    }
+}
+
+/// 头 / 查询参数 / Cookie 与客户端证书主题条件
+fn validate_route_values(route_id: Uuid, parsed: &RouteMatch, errors: &mut Vec<String>) {
+    let groups = [
+        ("header", &parsed.headers),
+        ("query", &parsed.query),
+        ("cookie", &parsed.cookies),
+    ];
+    for (kind, map) in groups {
+        let Some(map) = map else {
+            continue;
+        };
+        for (key, value) in map {
+            if key.trim().is_empty() {
+                errors.push(format!("route {} empty {} name", route_id, kind));
+                continue;
+            }
+            validate_value_match(
+                &format!("route {} {} {}", route_id, kind, key),
+                value,
+                errors,
+            );
+        }
+    }
+    if let Some(value) = &parsed.client_cert_subject {
+        validate_value_match(
+            &format!("route {} client_cert_subject", route_id),
+            value,
+            errors,
+        );
+    }
+    if let Some(cidrs) = &parsed.source_cidrs {
+        if cidrs.is_empty() {
+            errors.push(format!("route {} source_cidrs empty", route_id));
+        }
+        for cidr in cidrs {
+            if IpCidr::parse(cidr).is_none() {
+                errors.push(format!("route {} invalid source cidr {}", route_id, cidr));
+            }
+        }
+    }
+}
+
+/// 取值列表非空、正则可编译、至少一个条件，present=false 不能与取值条件同时使用
+fn validate_value_match(subject: &str, value: &ValueMatch, errors: &mut Vec<String>) {
+    let rule = value.to_rule();
+    if rule.values.as_ref().is_some_and(|values| values.is_empty()) {
+        errors.push(format!("{} values empty", subject));
+    }
+    if rule.present == Some(false) && rule.has_value_condition() {
+        errors.push(format!(
+            "{} present=false conflicts with value conditions",
+            subject
+        ));
+    }
+    if rule.present.is_none() && !rule.has_value_condition() {
+        errors.push(format!("{} has no condition", subject));
+    }
+    if let Some(expr) = &rule.regex
+        && Regex::new(expr).is_err()
+    {
+        errors.push(format!("{} invalid regex {}", subject, expr));
+    }
+}
+
+/// PEM 中至少包含一个证书且全部可解析
+fn is_valid_ca_pem(pem: &str) -> bool {
+    let mut count = 0;
+    for block in Pem::iter_from_buffer(pem.as_bytes()) {
+        match block {
+            Ok(block) if block.parse_x509().is_ok() => count += 1,
+            _ => return false,
+        }
+    }
+    count > 0
+}
+
+/// SNI 匹配模式：域名或 `*.` 开头的单级通配域名
+fn is_sni_pattern(host: &str) -> bool {
+    let name = host.strip_prefix("*.").unwrap_or(host);
+    !name.is_empty()
+        && name.split('.').all(|label| {
+            !label.is_empty()
+                && label
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+        })
+}
+
 fn parse_host_port(address: &str) -> Option<(String, u16)> {
     if let Some(rest) = address.strip_prefix('[') {
         let end = rest.find(']')?;
@@ -1422,6 +2684,231 @@ mod tests {
     use sea_orm::DatabaseConnection;
     use tower::ServiceExt;
 
+    fn pool_errors(options: serde_json::Value) -> Vec<String> {
+        let options: PoolOptions = serde_json::from_value(options).unwrap();
+        let mut errors = Vec::new();
+        validate_pool_options(Uuid::nil(), &options, &mut errors);
+        errors
+    }
+
+    #[test]
+    fn pool_proxy_protocol_rejected_with_http2() {
+        for protocol in ["h2", "h2c", "H2"] {
+            let errors = pool_errors(serde_json::json!({
+                "protocol": protocol,
+                "proxy_protocol": "v2"
+            }));
+            assert_eq!(errors.len(), 1, "{}: {:?}", protocol, errors);
+            assert!(errors[0].contains("proxy_protocol not supported"));
+        }
+        assert!(
+            pool_errors(serde_json::json!({"protocol": "http1", "proxy_protocol": "v1"}))
+                .is_empty()
+        );
+        assert!(pool_errors(serde_json::json!({"proxy_protocol": "v2"})).is_empty());
+        assert!(pool_errors(serde_json::json!({"protocol": "h2"})).is_empty());
+    }
+
+    fn listener_errors(protocol: &str, options: serde_json::Value) -> Vec<String> {
+        let now = chrono::Utc::now().fixed_offset();
+        let listener = gateway_common::entities::listeners::Model {
+            id: Uuid::nil(),
+            name: "test".to_string(),
+            port: 8080,
+            protocol: protocol.to_string(),
+            tls_policy_id: None,
+            enabled: true,
+            options: Some(options.clone()),
+            created_at: now,
+            updated_at: now,
+        };
+        let options: ListenerOptions = serde_json::from_value(options).unwrap();
+        let mut errors = Vec::new();
+        validate_listener_options(&listener, &options, &mut errors);
+        errors
+    }
+
+    #[test]
+    fn stream_listener_rejects_http_options() {
+        let options = serde_json::json!({
+            "maintenance": {"enabled": true},
+            "security_headers": {"frame_options": "DENY"}
+        });
+        for protocol in ["tcp", "tls_passthrough"] {
+            let errors = listener_errors(protocol, options.clone());
+            assert_eq!(
+                errors,
+                vec![
+                    format!(
+                        "listener 00000000-0000-0000-0000-000000000000 maintenance not supported on {} listener",
+                        protocol
+                    ),
+                    format!(
+                        "listener 00000000-0000-0000-0000-000000000000 security_headers not supported on {} listener",
+                        protocol
+                    ),
+                ]
+            );
+        }
+        assert!(listener_errors("http", options).is_empty());
+    }
+
+    #[test]
+    fn listener_request_id_validation() {
+        let options = serde_json::json!({
+            "request_id": {"header": "X-Trace-Id", "trusted_cidrs": ["10.0.0.0/8", "::1/128"]}
+        });
+        assert!(listener_errors("http", options).is_empty());
+        let options = serde_json::json!({
+            "request_id": {"header": "bad header", "trusted_cidrs": ["10.0.0.0/33"]}
+        });
+        assert_eq!(
+            listener_errors("http", options),
+            vec![
+                "listener 00000000-0000-0000-0000-000000000000 request_id header bad header invalid",
+                "listener 00000000-0000-0000-0000-000000000000 request_id trusted_cidrs invalid cidr 10.0.0.0/33",
+            ]
+        );
+    }
+
+    #[test]
+    fn stream_listener_rejects_request_options() {
+        let options = serde_json::json!({
+            "request_id": {"header": "x-trace-id"},
+            "forwarded": {"enabled": true},
+            "http2": {"max_concurrent_streams": 100}
+        });
+        for protocol in ["tcp", "tls_passthrough"] {
+            let errors = listener_errors(protocol, options.clone());
+            assert_eq!(
+                errors,
+                ["request_id", "forwarded", "http2"]
+                    .iter()
+                    .map(|option| format!(
+                        "listener 00000000-0000-0000-0000-000000000000 {} not supported on {} listener",
+                        option, protocol
+                    ))
+                    .collect::<Vec<_>>()
+            );
+        }
+        assert!(listener_errors("https", options).is_empty());
+    }
+
+    #[test]
+    fn proxy_protocol_allowed_on_https_listener() {
+        let options = serde_json::json!({
+            "proxy_protocol": "required",
+            "proxy_protocol_trusted_cidrs": ["10.0.0.0/8"]
+        });
+        for protocol in ["http", "https", "tcp"] {
+            assert!(
+                listener_errors(protocol, options.clone()).is_empty(),
+                "{}",
+                protocol
+            );
+        }
+        assert_eq!(
+            listener_errors("https", serde_json::json!({"proxy_protocol": "optional"})),
+            vec![
+                "listener 00000000-0000-0000-0000-000000000000 proxy_protocol requires proxy_protocol_trusted_cidrs"
+            ]
+        );
+    }
+
+    fn route_errors(r#type: &str, options: serde_json::Value) -> Vec<String> {
+        let now = chrono::Utc::now().fixed_offset();
+        let route = gateway_common::entities::routes::Model {
+            id: Uuid::nil(),
+            listener_id: Uuid::nil(),
+            r#type: r#type.to_string(),
+            match_expr: serde_json::json!({}),
+            priority: 0,
+            upstream_pool_id: Uuid::nil(),
+            enabled: true,
+            options: Some(options.clone()),
+            created_at: now,
+            updated_at: now,
+        };
+        let options: RouteOptions = serde_json::from_value(options).unwrap();
+        let mut errors = Vec::new();
+        validate_route_options(&route, &options, &HashSet::new(), &mut errors);
+        errors
+    }
+
+    #[test]
+    fn stream_route_rejects_http_options() {
+        let options = serde_json::json!({
+            "maintenance": {"enabled": true},
+            "limits": {"max_body_bytes": 1024}
+        });
+        for r#type in ["tcp", "sni"] {
+            assert_eq!(
+                route_errors(r#type, options.clone()),
+                vec![
+                    format!(
+                        "route 00000000-0000-0000-0000-000000000000 maintenance not supported on {} route",
+                        r#type
+                    ),
+                    format!(
+                        "route 00000000-0000-0000-0000-000000000000 limits not supported on {} route",
+                        r#type
+                    ),
+                ]
+            );
+        }
+        assert!(route_errors("path", options).is_empty());
+    }
+
+    #[test]
+    fn ws_route_requires_http1_pool() {
+        let now = chrono::Utc::now().fixed_offset();
+        let route = |r#type: &str| gateway_common::entities::routes::Model {
+            id: Uuid::nil(),
+            listener_id: Uuid::nil(),
+            r#type: r#type.to_string(),
+            match_expr: serde_json::json!({}),
+            priority: 0,
+            upstream_pool_id: Uuid::nil(),
+            enabled: true,
+            options: None,
+            created_at: now,
+            updated_at: now,
+        };
+        let errors = |r#type: &str, protocol: &str| {
+            let pools = HashMap::from([(Uuid::nil(), protocol.to_string())]);
+            let mut errors = Vec::new();
+            validate_route_pool_protocol(&route(r#type), &pools, &mut errors);
+            errors
+        };
+        assert!(errors("ws", "http1").is_empty());
+        assert!(errors("WS", "HTTP1").is_empty());
+        assert_eq!(errors("ws", "h2").len(), 1);
+        assert_eq!(errors("ws", "h2c").len(), 1);
+        assert!(errors("path", "h2").is_empty(), "仅限制 ws 路由");
+    }
+
+    #[test]
+    fn route_host_with_port_is_normalized() {
+        let host_errors = |host: &str| {
+            let parsed: RouteMatch =
+                serde_json::from_value(serde_json::json!({"host": host})).unwrap();
+            let mut errors = Vec::new();
+            validate_route_host(Uuid::nil(), &parsed, &mut errors);
+            errors
+        };
+        for host in [
+            "example.com",
+            "example.com:8080",
+            "*.Example.com.:443",
+            "example.com.",
+        ] {
+            assert!(host_errors(host).is_empty(), "{}", host);
+        }
+        for host in ["", "exa mple.com", "*.*.example.com", "example.com:http"] {
+            assert_eq!(host_errors(host).len(), 1, "{}", host);
+        }
+    }
+
     #[tokio::test]
     async fn acme_challenge_route_uses_brace_params() {
         let (snapshots, _rx) = SnapshotStore::new(Snapshot::default());
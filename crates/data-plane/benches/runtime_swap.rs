@@ -0,0 +1,154 @@
+//! 并发发布下读取运行时配置的延迟对比：tokio RwLock 与 ArcSwap
+//!
+//! 读取方模拟请求路径（取配置并按端口查找监听器），发布方周期性构建并替换一份新配置。
+//! 旧配置在 RwLock 写锁内被替换与释放，等待写锁及写锁持有期间所有读取方都会被阻塞。
+//!
+//! 运行：`cargo bench -p gateway-data-plane --bench runtime_swap`
+
+use arc_swap::ArcSwap;
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+const READERS: usize = 8;
+const LISTENERS: u16 = 64;
+const ROUTES_PER_LISTENER: usize = 2_000;
+const PUBLISH_INTERVAL: Duration = Duration::from_millis(20);
+const RUN_FOR: Duration = Duration::from_secs(3);
+/// 超过该时长的读取视为被发布阻塞
+const STALL: Duration = Duration::from_micros(100);
+
+/// 与数据平面运行时配置规模相近的替身：按端口索引的监听器与其路由
+struct Config {
+    listeners_by_port: HashMap<u16, Vec<String>>,
+}
+
+fn build_config(version: usize) -> Config {
+    let listeners_by_port = (0..LISTENERS)
+        .map(|port| {
+            let routes = (0..ROUTES_PER_LISTENER)
+                .map(|i| format!("/v{}/listener-{}/route-{}", version, port, i))
+                .collect();
+            (8000 + port, routes)
+        })
+        .collect();
+    Config { listeners_by_port }
+}
+
+fn lookup(config: &Config, port: u16) -> usize {
+    config
+        .listeners_by_port
+        .get(&port)
+        .map(|routes| routes.len())
+        .unwrap_or_default()
+}
+
+enum Store {
+    Locked(RwLock<Config>),
+    Swapped(ArcSwap<Config>),
+}
+
+impl Store {
+    fn name(&self) -> &'static str {
+        match self {
+            Store::Locked(_) => "tokio::sync::RwLock",
+            Store::Swapped(_) => "ArcSwap",
+        }
+    }
+
+    async fn read(&self, port: u16) -> usize {
+        match self {
+            Store::Locked(lock) => lookup(&*lock.read().await, port),
+            Store::Swapped(swap) => lookup(&swap.load(), port),
+        }
+    }
+
+    async fn publish(&self, config: Config) {
+        match self {
+            Store::Locked(lock) => *lock.write().await = config,
+            Store::Swapped(swap) => swap.store(Arc::new(config)),
+        }
+    }
+}
+
+async fn run(store: Arc<Store>) {
+    let stop = Arc::new(AtomicBool::new(false));
+
+    let publisher = {
+        let store = store.clone();
+        let stop = stop.clone();
+        tokio::spawn(async move {
+            let mut published = 0;
+            while !stop.load(Ordering::Relaxed) {
+                tokio::time::sleep(PUBLISH_INTERVAL).await;
+                published += 1;
+                let config = tokio::task::spawn_blocking(move || build_config(published))
+                    .await
+                    .expect("build config");
+                store.publish(config).await;
+            }
+            published
+        })
+    };
+
+    let readers: Vec<_> = (0..READERS)
+        .map(|reader| {
+            let store = store.clone();
+            let stop = stop.clone();
+            tokio::spawn(async move {
+                let mut samples = Vec::new();
+                let mut port = 8000 + reader as u16;
+                while !stop.load(Ordering::Relaxed) {
+                    let start = Instant::now();
+                    black_box(store.read(port).await);
+                    samples.push(start.elapsed());
+                    port = 8000 + (port + 1) % LISTENERS;
+                    tokio::task::yield_now().await;
+                }
+                samples
+            })
+        })
+        .collect();
+
+    tokio::time::sleep(RUN_FOR).await;
+    stop.store(true, Ordering::Relaxed);
+
+    let published = publisher.await.expect("publisher");
+    let mut samples = Vec::new();
+    for reader in readers {
+        samples.extend(reader.await.expect("reader"));
+    }
+    samples.sort_unstable();
+
+    let percentile = |p: f64| samples[((samples.len() - 1) as f64 * p) as usize];
+    let stalled = samples.iter().filter(|sample| **sample >= STALL).count();
+    println!(
+        "{:<20} reads {:>10}  publishes {:>4}  stalled {:>6}  p50 {:>10.2?}  p99 {:>10.2?}  p99.9 {:>10.2?}  max {:>10.2?}",
+        store.name(),
+        samples.len(),
+        published,
+        stalled,
+        percentile(0.50),
+        percentile(0.99),
+        percentile(0.999),
+        samples[samples.len() - 1],
+    );
+}
+
+fn main() {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(READERS / 2)
+        .enable_all()
+        .build()
+        .expect("tokio runtime");
+    runtime.block_on(async {
+        run(Arc::new(Store::Locked(RwLock::new(build_config(0))))).await;
+        run(Arc::new(Store::Swapped(ArcSwap::from_pointee(
+            build_config(0),
+        ))))
+        .await;
+    });
+}
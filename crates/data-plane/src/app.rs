@@ -0,0 +1,555 @@
+use crate::l4::TcpProxy;
+use crate::proxy::{
+    Http2Config, Http2Limits, ListenerRuntime, PortTlsSelector, ProxyRouter, SharedRuntime,
+};
+use crate::proxy_protocol::{HEADER_READ_TIMEOUT, ProxyProtocolMode, read_header};
+use anyhow::Result;
+use async_trait::async_trait;
+use pingora::apps::{HttpServerApp, HttpServerOptions, ReusedHttpStream, ServerApp};
+use pingora::listeners::TlsAcceptCallbacks;
+use pingora::protocols::http::ServerSession;
+use pingora::protocols::http::v2::server::H2Options;
+use pingora::protocols::l4::stream::Stream as L4Stream;
+use pingora::protocols::raw_connect::ProxyDigest;
+use pingora::protocols::tls::server::handshake_with_callback;
+use pingora::protocols::tls::{SslDigest, TlsRef};
+use pingora::protocols::{
+    ALPN, GetProxyDigest, GetSocketDigest, GetTimingDigest, Peek, Shutdown, SocketDigest, Ssl,
+    Stream, TimingDigest, UniqueID, UniqueIDType,
+};
+use pingora::proxy::{HttpProxy, http_proxy_service};
+use pingora::server::ShutdownWatch;
+use pingora::server::configuration::ServerConf;
+use pingora::services::listening::Service;
+use pingora::tls::ssl::{SslAcceptor, SslMethod};
+use std::collections::HashMap;
+use std::io;
+use std::mem::ManuallyDrop;
+use std::os::fd::AsRawFd;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf};
+use tokio::time::{Duration, timeout};
+use tracing::{debug, warn};
+
+/// 数据平面的连接入口：按监听器配置解析 PROXY protocol 头，https 端口随后完成 TLS 握手，
+/// 再将连接交给 L4 转发（tcp / tls_passthrough 监听器）或 HTTP 代理处理
+pub struct GatewayApp {
+    http: Arc<HttpProxy<ProxyRouter>>,
+    tcp: TcpProxy,
+    /// https 端口的 TLS 终止；端口以明文 TCP 监听，PROXY 头位于 TLS 握手之前
+    tls: HashMap<u16, TlsPort>,
+    runtime: SharedRuntime,
+}
+
+impl GatewayApp {
+    pub fn new(conf: Arc<ServerConf>, router: ProxyRouter, runtime: SharedRuntime) -> Self {
+        Self {
+            http: Arc::new(into_app(http_proxy_service(&conf, router))),
+            tcp: TcpProxy::new(runtime.clone()),
+            tls: HashMap::new(),
+            runtime,
+        }
+    }
+
+    /// 在该端口上终止 TLS：证书与 ALPN 均按端口在运行时配置中动态选择
+    pub fn add_tls_port(&mut self, port: u16) -> Result<()> {
+        let tls = TlsPort::new(port, &self.runtime)?;
+        self.tls.insert(port, tls);
+        Ok(())
+    }
+}
+
+struct TlsPort {
+    acceptor: SslAcceptor,
+    callbacks: TlsAcceptCallbacks,
+}
+
+impl TlsPort {
+    fn new(port: u16, runtime: &SharedRuntime) -> Result<Self> {
+        let mut builder = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls())?;
+        let alpn_runtime = runtime.clone();
+        builder.set_alpn_select_callback(move |_, client| {
+            crate::proxy::select_alpn(&alpn_runtime, port, client)
+        });
+        Ok(Self {
+            acceptor: builder.build(),
+            callbacks: Box::new(PortTlsSelector::new(port, runtime.clone())),
+        })
+    }
+
+    /// 在读出 PROXY 头之后的连接上握手；`preface` 为探测 PROXY 头时多读出的 ClientHello 字节
+    async fn accept(&self, io: Stream, preface: Vec<u8>) -> Option<Stream> {
+        let io = Rewind::new(io, preface);
+        match handshake_with_callback(&self.acceptor, io, &self.callbacks).await {
+            Ok(stream) => Some(Box::new(stream)),
+            Err(err) => {
+                debug!("tls handshake failed: {}", err);
+                None
+            }
+        }
+    }
+}
+
+/// 监听器按各自的 HTTP/2 配置接入共享的 HTTP 代理：h2c 探测与 h2 SETTINGS 由外层应用决定，
+/// 请求处理委托给同一个代理实例
+struct HttpApp {
+    proxy: Arc<HttpProxy<ProxyRouter>>,
+    server_options: HttpServerOptions,
+    h2_options: Option<H2Options>,
+}
+
+impl HttpApp {
+    fn new(proxy: Arc<HttpProxy<ProxyRouter>>, http2: Http2Config) -> Self {
+        // 未启用 h2c 时不探测 prior knowledge 前言，`PRI *` 请求按 HTTP/1 解析失败后拒绝
+        let mut server_options = HttpServerOptions::default();
+        server_options.h2c = http2.h2c;
+        Self {
+            proxy,
+            server_options,
+            h2_options: h2_options(http2.limits),
+        }
+    }
+}
+
+#[async_trait]
+impl HttpServerApp for HttpApp {
+    async fn process_new_http(
+        self: &Arc<Self>,
+        session: ServerSession,
+        shutdown: &ShutdownWatch,
+    ) -> Option<ReusedHttpStream> {
+        self.proxy.process_new_http(session, shutdown).await
+    }
+
+    fn h2_options(&self) -> Option<H2Options> {
+        self.h2_options.clone()
+    }
+
+    fn server_options(&self) -> Option<&HttpServerOptions> {
+        Some(&self.server_options)
+    }
+}
+
+/// 监听器配置的 HTTP/2 连接级限制；未配置任何限制时沿用 Pingora 的缺省设置
+fn h2_options(limits: Http2Limits) -> Option<H2Options> {
+    if limits == Http2Limits::default() {
+        return None;
+    }
+    let mut h2 = H2Options::new();
+    if let Some(max) = limits.max_concurrent_streams {
+        h2.max_concurrent_streams(max);
+    }
+    if let Some(max) = limits.max_header_list_size {
+        h2.max_header_list_size(max);
+    }
+    Some(h2)
+}
+
+/// 连接的处理方式，由本地端口所属监听器的协议决定
+enum Handler {
+    /// tcp / tls_passthrough 监听器：不解析应用层协议，交给 L4 转发
+    Stream(Arc<ListenerRuntime>),
+    /// 其余监听器（含尚未配置监听器的预绑定端口）按监听器的 HTTP/2 配置交给 HTTP 代理
+    Http(Http2Config),
+}
+
+impl Handler {
+    fn for_listener(listener: Option<Arc<ListenerRuntime>>) -> Self {
+        match listener {
+            Some(listener) if listener.is_stream() => Self::Stream(listener),
+            Some(listener) => Self::Http(listener.http2),
+            None => Self::Http(Http2Config::default()),
+        }
+    }
+}
+
+#[async_trait]
+impl ServerApp for GatewayApp {
+    async fn process_new(
+        self: &Arc<Self>,
+        mut io: Stream,
+        shutdown: &ShutdownWatch,
+    ) -> Option<Stream> {
+        let addrs = io.get_socket_digest().and_then(|digest| {
+            let local = *digest.local_addr()?.as_inet()?;
+            let peer = *digest.peer_addr()?.as_inet()?;
+            Some((local, peer))
+        });
+        let listener = match addrs {
+            Some((local, _)) => self
+                .runtime
+                .load()
+                .listeners_by_port
+                .get(&local.port())
+                .cloned(),
+            None => None,
+        };
+        let peer_ip = addrs.map(|(_, peer)| peer.ip());
+        let mode = match &listener {
+            Some(listener) => listener.proxy_protocol.mode_for(peer_ip),
+            None => Some(ProxyProtocolMode::Off),
+        };
+        let Some(mode) = mode else {
+            warn!(
+                "rejecting connection: PROXY protocol header from untrusted peer {:?}",
+                peer_ip
+            );
+            return None;
+        };
+
+        let mut source = None;
+        let mut preface = Vec::new();
+        if mode != ProxyProtocolMode::Off {
+            let header = match timeout(HEADER_READ_TIMEOUT, read_header(&mut io, mode)).await {
+                Ok(Ok(header)) => header,
+                Ok(Err(err)) => {
+                    warn!("rejecting connection: {}", err);
+                    return None;
+                }
+                Err(_) => {
+                    warn!("rejecting connection: PROXY protocol header timeout");
+                    return None;
+                }
+            };
+            if let Some(addr) = header.source {
+                debug!("PROXY protocol source {}", addr);
+            }
+            source = header.source;
+            preface = header.preface;
+        }
+
+        let http2 = match Handler::for_listener(listener) {
+            Handler::Stream(listener) => {
+                let peer = source.or(addrs.map(|(_, peer)| peer));
+                let local = addrs.map(|(local, _)| local);
+                self.tcp
+                    .serve(io, preface, &listener, peer, local, shutdown)
+                    .await;
+                return None;
+            }
+            Handler::Http(http2) => http2,
+        };
+
+        if let Some(source) = source {
+            set_client_addr(&mut io, source);
+        }
+        let tls = addrs.and_then(|(local, _)| self.tls.get(&local.port()));
+        if let Some(tls) = tls {
+            io = tls.accept(io, preface).await?;
+        } else if !preface.is_empty() {
+            io = Box::new(Rewind::new(io, preface));
+        }
+
+        // 内层应用按请求返回可复用连接，这里在同一连接上循环处理，保证 PROXY 头只解析一次
+        let http = Arc::new(HttpApp::new(self.http.clone(), http2));
+        let mut reuse = http.process_new(io, shutdown).await;
+        while let Some(stream) = reuse {
+            reuse = http.process_new(stream, shutdown).await;
+        }
+        None
+    }
+}
+
+/// 以 PROXY 头中的源地址作为连接摘要里的对端地址：摘要随连接传给 HTTP 会话，
+/// 之后 `Session::client_addr()` 返回真实客户端；须在 TLS 握手包装连接之前调用
+fn set_client_addr(io: &mut Stream, source: std::net::SocketAddr) {
+    let Some(stream) = io.as_any().downcast_ref::<L4Stream>() else {
+        return;
+    };
+    let digest = SocketDigest::from_raw_fd(stream.as_raw_fd());
+    let _ = digest.peer_addr.set(Some(source.into()));
+    io.set_socket_digest(digest);
+}
+
+/// 取出服务中的应用：Pingora 0.6 的 `HttpProxy` 只能经由 `http_proxy_service` 构建，
+/// 而这里由网关应用分发连接，不能让服务自己监听端口；启动时只调用一次
+fn into_app<A>(service: Service<A>) -> A {
+    let mut service = ManuallyDrop::new(service);
+    let app = service
+        .app_logic_mut()
+        .expect("app logic is only taken when the service starts");
+    // SAFETY: 应用按值读出后服务既不会再被使用，也不会被 drop（ManuallyDrop），
+    // 应用只有读出的这一个所有者；服务本身的名称与空监听列表随之泄漏，整个进程仅一次
+    unsafe { std::ptr::read(app) }
+}
+
+/// 把 PROXY protocol 探测时已读出的非 PROXY 前缀字节放回连接：读取时先返回这些字节，
+/// 其余操作原样委托给内层连接；也作为 TLS 握手时的内层连接
+#[derive(Debug)]
+struct Rewind {
+    inner: Stream,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl Rewind {
+    fn new(inner: Stream, buf: Vec<u8>) -> Self {
+        Self { inner, buf, pos: 0 }
+    }
+}
+
+impl AsyncRead for Rewind {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        out: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        if this.pos < this.buf.len() {
+            let n = (this.buf.len() - this.pos).min(out.remaining());
+            out.put_slice(&this.buf[this.pos..this.pos + n]);
+            this.pos += n;
+            if this.pos == this.buf.len() {
+                this.buf = Vec::new();
+                this.pos = 0;
+            }
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut this.inner).poll_read(cx, out)
+    }
+}
+
+impl AsyncWrite for Rewind {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        data: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, data)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[async_trait]
+impl Shutdown for Rewind {
+    async fn shutdown(&mut self) {
+        self.inner.shutdown().await
+    }
+}
+
+impl UniqueID for Rewind {
+    fn id(&self) -> UniqueIDType {
+        self.inner.id()
+    }
+}
+
+impl Ssl for Rewind {
+    fn get_ssl(&self) -> Option<&TlsRef> {
+        self.inner.get_ssl()
+    }
+
+    fn get_ssl_digest(&self) -> Option<Arc<SslDigest>> {
+        self.inner.get_ssl_digest()
+    }
+
+    fn selected_alpn_proto(&self) -> Option<ALPN> {
+        self.inner.selected_alpn_proto()
+    }
+}
+
+impl GetTimingDigest for Rewind {
+    fn get_timing_digest(&self) -> Vec<Option<TimingDigest>> {
+        self.inner.get_timing_digest()
+    }
+
+    fn get_read_pending_time(&self) -> Duration {
+        self.inner.get_read_pending_time()
+    }
+
+    fn get_write_pending_time(&self) -> Duration {
+        self.inner.get_write_pending_time()
+    }
+}
+
+impl GetProxyDigest for Rewind {
+    fn get_proxy_digest(&self) -> Option<Arc<ProxyDigest>> {
+        self.inner.get_proxy_digest()
+    }
+
+    fn set_proxy_digest(&mut self, digest: ProxyDigest) {
+        self.inner.set_proxy_digest(digest)
+    }
+}
+
+impl GetSocketDigest for Rewind {
+    fn get_socket_digest(&self) -> Option<Arc<SocketDigest>> {
+        self.inner.get_socket_digest()
+    }
+
+    fn set_socket_digest(&mut self, digest: SocketDigest) {
+        self.inner.set_socket_digest(digest)
+    }
+}
+
+#[async_trait]
+impl Peek for Rewind {
+    /// h2c 探测需要窥视完整的连接前言：缓冲不足时从内层连接补读，补读的字节同样会先于后续数据返回
+    async fn try_peek(&mut self, out: &mut [u8]) -> io::Result<bool> {
+        self.buf.drain(..self.pos);
+        self.pos = 0;
+        let buffered = self.buf.len();
+        if buffered < out.len() {
+            self.buf.resize(out.len(), 0);
+            if let Err(err) = self.inner.read_exact(&mut self.buf[buffered..]).await {
+                self.buf.truncate(buffered);
+                return Err(err);
+            }
+        }
+        out.copy_from_slice(&self.buf[..out.len()]);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::build_runtime;
+    use gateway_common::snapshot::Snapshot;
+    use serde_json::json;
+    use std::path::Path;
+
+    fn listener(port: u16, protocol: &str, options: serde_json::Value) -> serde_json::Value {
+        json!({
+            "id": uuid::Uuid::new_v4(),
+            "name": format!("{}-{}", protocol, port),
+            "port": port,
+            "protocol": protocol,
+            "tls_policy_id": null,
+            "enabled": true,
+            "options": options,
+            "created_at": "2026-01-01T00:00:00Z",
+            "updated_at": "2026-01-01T00:00:00Z",
+        })
+    }
+
+    #[test]
+    fn test_tcp_and_http_split() {
+        let snapshot: Snapshot = serde_json::from_value(json!({
+            "listeners": [
+                listener(5432, "tcp", json!(null)),
+                listener(8443, "tls_passthrough", json!(null)),
+                listener(8080, "http", json!({"http2": {"h2c": true}})),
+                listener(443, "https", json!(null)),
+            ],
+            "routes": [],
+            "upstream_pools": [],
+            "upstream_targets": [],
+            "tls_policies": [],
+            "certificates": [],
+        }))
+        .unwrap();
+        let pem = crate::tls::default_tls_pem(Path::new("/nonexistent")).unwrap();
+        let runtime = build_runtime(&snapshot, None, &pem, None, None).unwrap();
+        let handler =
+            |port: u16| Handler::for_listener(runtime.listeners_by_port.get(&port).cloned());
+
+        for port in [5432, 8443] {
+            assert!(
+                matches!(handler(port), Handler::Stream(listener) if listener.port == i32::from(port)),
+                "port {} 交给 L4 转发",
+                port
+            );
+        }
+        assert!(
+            matches!(handler(8080), Handler::Http(http2) if http2.h2c),
+            "沿用监听器的 h2c 配置"
+        );
+        assert!(matches!(handler(443), Handler::Http(http2) if http2 == Http2Config::default()));
+        assert!(
+            matches!(handler(9000), Handler::Http(http2) if http2 == Http2Config::default()),
+            "未配置监听器的端口按 HTTP 处理"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_proxied_client_addr_in_digest() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = tokio::net::TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (accepted, _) = listener.accept().await.unwrap();
+        let mut io: Stream = Box::new(L4Stream::from(accepted));
+
+        let source = "203.0.113.7:51000".parse().unwrap();
+        set_client_addr(&mut io, source);
+        let digest = io.get_socket_digest().unwrap();
+        assert_eq!(
+            digest.peer_addr().and_then(|addr| addr.as_inet()),
+            Some(&source)
+        );
+        assert_eq!(
+            digest.local_addr().and_then(|addr| addr.as_inet()).copied(),
+            Some(listener.local_addr().unwrap()),
+            "本地地址仍取自套接字"
+        );
+        drop(client);
+    }
+
+    #[tokio::test]
+    async fn test_proxy_header_before_tls_handshake() {
+        use crate::proxy_protocol::{ProxyProtocolVersion, encode_header};
+        use pingora::tls::ssl::{SslConnector, SslVerifyMode};
+        use tokio::io::AsyncWriteExt;
+
+        let snapshot: Snapshot = serde_json::from_value(json!({
+            "listeners": [listener(443, "https", json!(null))],
+            "routes": [],
+            "upstream_pools": [],
+            "upstream_targets": [],
+            "tls_policies": [],
+            "certificates": [],
+        }))
+        .unwrap();
+        let pem = crate::tls::default_tls_pem(Path::new("/nonexistent")).unwrap();
+        let runtime: SharedRuntime = Arc::new(arc_swap::ArcSwap::from_pointee(
+            build_runtime(&snapshot, None, &pem, None, None).unwrap(),
+        ));
+        let tls = TlsPort::new(443, &runtime).unwrap();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let local = listener.local_addr().unwrap();
+        let source = "203.0.113.7:51000".parse().unwrap();
+        let client = async {
+            let mut tcp = tokio::net::TcpStream::connect(local).await.unwrap();
+            tcp.write_all(&encode_header(ProxyProtocolVersion::V2, source, local))
+                .await
+                .unwrap();
+            let mut connector = SslConnector::builder(SslMethod::tls()).unwrap();
+            connector.set_verify(SslVerifyMode::NONE);
+            let config = connector.build().configure().unwrap();
+            pingora::protocols::tls::client::handshake(config, "localhost", L4Stream::from(tcp))
+                .await
+                .unwrap()
+        };
+        let server = async {
+            let (accepted, _) = listener.accept().await.unwrap();
+            let mut io: Stream = Box::new(L4Stream::from(accepted));
+            let header = read_header(&mut io, ProxyProtocolMode::Required)
+                .await
+                .unwrap();
+            assert_eq!(header.source, Some(source));
+            set_client_addr(&mut io, source);
+            tls.accept(io, header.preface).await
+        };
+        let (_client, server) = tokio::join!(client, server);
+
+        let stream = server.expect("PROXY 头之后完成 TLS 握手");
+        assert!(stream.get_ssl_digest().is_some());
+        assert_eq!(
+            stream
+                .get_socket_digest()
+                .and_then(|digest| digest.peer_addr().and_then(|addr| addr.as_inet()).copied()),
+            Some(source),
+            "TLS 连接的对端地址取自 PROXY 头"
+        );
+    }
+}
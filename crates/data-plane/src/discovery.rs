@@ -0,0 +1,401 @@
+use crate::proxy::{SharedRuntime, TargetRuntime};
+use anyhow::{Context, Result, bail};
+use arc_swap::ArcSwap;
+use gateway_common::models::DiscoveryOptions;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime};
+use tokio::time::{Duration, sleep};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// 检查各发现源是否到期的周期
+const TICK: Duration = Duration::from_secs(1);
+const DEFAULT_DNS_INTERVAL: Duration = Duration::from_secs(30);
+const DEFAULT_FILE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 池的发现源（运行时）；配置不变时跨配置版本共享已解析的目标
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoverySource {
+    kind: SourceKind,
+    interval: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SourceKind {
+    Dns {
+        name: String,
+        port: u16,
+        weight: i32,
+    },
+    Srv {
+        name: String,
+    },
+    File {
+        path: PathBuf,
+    },
+}
+
+impl DiscoverySource {
+    pub fn from_options(pool_id: Uuid, options: &DiscoveryOptions) -> Option<Self> {
+        let name = options
+            .name
+            .as_deref()
+            .map(str::trim)
+            .filter(|n| !n.is_empty());
+        let kind = match (options.r#type.to_ascii_lowercase().as_str(), name) {
+            ("dns", Some(name)) => options.port.map(|port| SourceKind::Dns {
+                name: name.to_string(),
+                port,
+                weight: options.weight.unwrap_or(1),
+            }),
+            ("srv", Some(name)) => Some(SourceKind::Srv {
+                name: name.to_string(),
+            }),
+            ("file", _) => options.path.as_ref().map(|path| SourceKind::File {
+                path: PathBuf::from(path),
+            }),
+            _ => None,
+        };
+        let Some(kind) = kind else {
+            warn!(
+                "invalid discovery {} for pool {}, static targets used",
+                options.r#type, pool_id
+            );
+            return None;
+        };
+        let default_interval = match kind {
+            SourceKind::File { .. } => DEFAULT_FILE_INTERVAL,
+            _ => DEFAULT_DNS_INTERVAL,
+        };
+        let interval = options
+            .interval_secs
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs)
+            .unwrap_or(default_interval);
+        Some(Self { kind, interval })
+    }
+}
+
+/// 由发现源维护的池内目标
+pub struct Discovery {
+    source: DiscoverySource,
+    targets: ArcSwap<Vec<Arc<TargetRuntime>>>,
+    state: Mutex<DiscoveryState>,
+}
+
+#[derive(Default)]
+struct DiscoveryState {
+    next_due: Option<Instant>,
+    running: bool,
+    /// 上次成功加载的文件修改时间与大小，未变化时跳过解析
+    file_stamp: Option<(SystemTime, u64)>,
+}
+
+/// 发现源给出的一个目标
+struct Resolved {
+    address: String,
+    weight: i32,
+}
+
+impl Discovery {
+    /// `initial` 为首次解析完成前使用的目标（通常是池内原有目标）
+    pub fn new(source: DiscoverySource, initial: Arc<Vec<Arc<TargetRuntime>>>) -> Self {
+        Self {
+            source,
+            targets: ArcSwap::new(initial),
+            state: Mutex::new(DiscoveryState::default()),
+        }
+    }
+
+    pub fn source(&self) -> &DiscoverySource {
+        &self.source
+    }
+
+    pub fn targets(&self) -> Arc<Vec<Arc<TargetRuntime>>> {
+        self.targets.load_full()
+    }
+
+    /// 到期且没有进行中的解析时占用本轮解析
+    fn begin(&self, now: Instant) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.running || state.next_due.is_some_and(|due| now < due) {
+            return false;
+        }
+        state.running = true;
+        state.next_due = Some(now + self.source.interval);
+        true
+    }
+
+    fn finish(&self) {
+        self.state.lock().unwrap().running = false;
+    }
+
+    /// 解析发现源；文件未变化时返回 None
+    async fn resolve(&self) -> Result<Option<Vec<Resolved>>> {
+        match &self.source.kind {
+            SourceKind::Dns { name, port, weight } => {
+                let addrs = tokio::net::lookup_host((name.as_str(), *port))
+                    .await
+                    .with_context(|| format!("resolve {}", name))?;
+                Ok(Some(
+                    addrs
+                        .map(|addr| Resolved {
+                            address: addr.to_string(),
+                            weight: *weight,
+                        })
+                        .collect(),
+                ))
+            }
+            SourceKind::Srv { name } => resolve_srv(name).await.map(Some),
+            SourceKind::File { path } => self.load_file(path).await,
+        }
+    }
+
+    async fn load_file(&self, path: &Path) -> Result<Option<Vec<Resolved>>> {
+        let metadata = tokio::fs::metadata(path)
+            .await
+            .with_context(|| format!("stat {}", path.display()))?;
+        let stamp = (metadata.modified()?, metadata.len());
+        if self.state.lock().unwrap().file_stamp == Some(stamp) {
+            return Ok(None);
+        }
+        let content = tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("read {}", path.display()))?;
+        let is_json = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        let file: TargetFile = if is_json {
+            serde_json::from_str(&content)?
+        } else {
+            serde_yaml::from_str(&content)?
+        };
+        let mut resolved = Vec::with_capacity(file.targets.len());
+        for target in file.targets {
+            if !is_target_address(&target.address) {
+                bail!("invalid address {}", target.address);
+            }
+            resolved.push(Resolved {
+                address: target.address,
+                weight: target.weight.unwrap_or(1),
+            });
+        }
+        self.state.lock().unwrap().file_stamp = Some(stamp);
+        Ok(Some(resolved))
+    }
+
+    /// 以解析结果替换池内目标：地址不变的目标沿用健康状态与进行中请求计数，消失的目标进入摘流
+    fn update(&self, pool_id: Uuid, resolved: Vec<Resolved>, drain_timeout: Duration) {
+        let current = self.targets.load_full();
+        let mut by_address: BTreeMap<String, i32> = BTreeMap::new();
+        for target in resolved {
+            by_address.entry(target.address).or_insert(target.weight);
+        }
+        let next: Vec<Arc<TargetRuntime>> = by_address
+            .into_iter()
+            .map(|(address, weight)| {
+                let prior = current.iter().find(|t| t.address() == address);
+                let id = prior.map(|t| t.id()).unwrap_or_else(Uuid::new_v4);
+                Arc::new(TargetRuntime::new(
+                    id,
+                    address,
+                    weight,
+                    prior.map(|t| t.as_ref()),
+                ))
+            })
+            .collect();
+
+        let removed: Vec<Arc<TargetRuntime>> = current
+            .iter()
+            .filter(|t| !next.iter().any(|n| n.address() == t.address()))
+            .cloned()
+            .collect();
+        let added = next
+            .iter()
+            .filter(|n| !current.iter().any(|t| t.address() == n.address()))
+            .count();
+        if added > 0 || !removed.is_empty() {
+            info!(
+                "discovery for pool {}: {} targets ({} added, {} removed)",
+                pool_id,
+                next.len(),
+                added,
+                removed.len()
+            );
+        }
+
+        crate::metrics::set_discovered_targets(&pool_id.to_string(), next.len());
+        self.targets.store(Arc::new(next));
+        for target in removed {
+            crate::drain::start(pool_id, target, drain_timeout);
+        }
+    }
+}
+
+/// file 发现源的文件格式（JSON 或 YAML）
+#[derive(Deserialize)]
+struct TargetFile {
+    #[serde(default)]
+    targets: Vec<TargetEntry>,
+}
+
+#[derive(Deserialize)]
+struct TargetEntry {
+    address: String,
+    weight: Option<i32>,
+}
+
+/// SRV 记录只取优先级数值最小的一组，目标主机再按 A/AAAA 解析
+async fn resolve_srv(name: &str) -> Result<Vec<Resolved>> {
+    let records = crate::dns::resolve_srv(name)
+        .await
+        .with_context(|| format!("resolve srv {}", name))?;
+    let Some(priority) = records.iter().map(|record| record.priority).min() else {
+        bail!("no srv records for {}", name);
+    };
+    let mut resolved = Vec::new();
+    for record in records.iter().filter(|record| record.priority == priority) {
+        match tokio::net::lookup_host((record.target.as_str(), record.port)).await {
+            Ok(addrs) => resolved.extend(addrs.map(|addr| Resolved {
+                address: addr.to_string(),
+                weight: i32::from(record.weight),
+            })),
+            Err(err) => warn!("resolve srv target {} failed: {}", record.target, err),
+        }
+    }
+    if resolved.is_empty() {
+        bail!("no srv target of {} resolved", name);
+    }
+    Ok(resolved)
+}
+
+fn is_target_address(address: &str) -> bool {
+    if gateway_common::net::unix_socket_path(address).is_some() {
+        return true;
+    }
+    address
+        .rsplit_once(':')
+        .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok())
+}
+
+/// 周期性解析各池的发现源；解析失败时保留上次的目标
+pub async fn run_discovery(runtime: SharedRuntime) {
+    loop {
+        let now = Instant::now();
+        for (pool_id, discovery, drain_timeout) in runtime.load().discovery_pools() {
+            if !discovery.begin(now) {
+                continue;
+            }
+            tokio::spawn(async move {
+                match discovery.resolve().await {
+                    Ok(Some(resolved)) => discovery.update(pool_id, resolved, drain_timeout),
+                    Ok(None) => {}
+                    Err(err) => warn!("discovery for pool {} failed: {:#}", pool_id, err),
+                }
+                discovery.finish();
+            });
+        }
+        sleep(TICK).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(address: &str) -> Arc<TargetRuntime> {
+        Arc::new(TargetRuntime::new(
+            Uuid::new_v4(),
+            address.to_string(),
+            1,
+            None,
+        ))
+    }
+
+    fn resolved(address: &str, weight: i32) -> Resolved {
+        Resolved {
+            address: address.to_string(),
+            weight,
+        }
+    }
+
+    #[test]
+    fn test_is_target_address() {
+        for address in [
+            "10.0.0.1:80",
+            "api.internal:8080",
+            "[::1]:443",
+            "unix:/run/app.sock",
+        ] {
+            assert!(is_target_address(address), "{} 应合法", address);
+        }
+        for address in [
+            "api.internal",
+            ":80",
+            "api.internal:http",
+            "api.internal:70000",
+            "unix:relative.sock",
+            "",
+        ] {
+            assert!(!is_target_address(address), "{} 应不合法", address);
+        }
+    }
+
+    #[test]
+    fn test_update_keeps_unchanged_targets() {
+        let pool_id = Uuid::new_v4();
+        let kept = target("10.0.1.1:80");
+        kept.set_healthy(false);
+        kept.acquire();
+        let removed = target("10.0.1.2:80");
+        removed.acquire();
+        let options = DiscoveryOptions {
+            r#type: "dns".to_string(),
+            name: Some("api.internal".to_string()),
+            port: Some(80),
+            ..Default::default()
+        };
+        let source = DiscoverySource::from_options(pool_id, &options).unwrap();
+        let discovery = Discovery::new(source, Arc::new(vec![kept.clone(), removed.clone()]));
+
+        discovery.update(
+            pool_id,
+            vec![
+                resolved("10.0.1.1:80", 3),
+                resolved("10.0.1.3:80", 1),
+                resolved("10.0.1.3:80", 7),
+            ],
+            Duration::from_secs(60),
+        );
+
+        let targets = discovery.targets();
+        assert_eq!(targets.len(), 2, "重复地址只保留一个");
+        let next = targets
+            .iter()
+            .find(|t| t.address() == "10.0.1.1:80")
+            .unwrap();
+        assert_eq!(next.id(), kept.id(), "地址不变时沿用目标 ID");
+        assert!(!next.is_healthy(), "沿用健康状态");
+        assert_eq!(next.inflight(), 1, "沿用进行中请求计数");
+        next.release();
+        assert_eq!(kept.inflight(), 0, "新旧版本共享同一计数");
+
+        let added = targets
+            .iter()
+            .find(|t| t.address() == "10.0.1.3:80")
+            .unwrap();
+        assert!(added.is_healthy());
+        assert_eq!(added.inflight(), 0);
+
+        let draining: Vec<_> = crate::drain::report()
+            .into_iter()
+            .filter(|entry| entry.pool_id == pool_id)
+            .collect();
+        assert_eq!(draining.len(), 1, "消失的目标进入摘流");
+        assert_eq!(draining[0].target_id, removed.id());
+        removed.release();
+    }
+}
@@ -0,0 +1,365 @@
+use anyhow::{Result, anyhow, bail};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::time::{Duration, timeout};
+
+const RESOLV_CONF: &str = "/etc/resolv.conf";
+const DNS_PORT: u16 = 53;
+const QUERY_TIMEOUT: Duration = Duration::from_secs(3);
+const TYPE_SRV: u16 = 33;
+const CLASS_IN: u16 = 1;
+const RCODE_NXDOMAIN: u8 = 3;
+/// 名称压缩指针的最大跳转次数，防止恶意报文造成死循环
+const MAX_POINTER_JUMPS: usize = 32;
+
+/// SRV 记录（RFC 2782）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SrvRecord {
+    pub priority: u16,
+    pub weight: u16,
+    pub port: u16,
+    pub target: String,
+}
+
+/// 查询 SRV 记录：依次尝试 resolv.conf 中的名称服务器，UDP 应答被截断时改用 TCP 重新查询
+pub async fn resolve_srv(name: &str) -> Result<Vec<SrvRecord>> {
+    let query = encode_query(name, TYPE_SRV)?;
+    let mut last_err = anyhow!("no nameserver configured");
+    for server in nameservers().await {
+        match query_server(server, &query).await {
+            Ok(response) => return decode_srv(&response, &query),
+            Err(err) => last_err = err.context(format!("nameserver {}", server)),
+        }
+    }
+    Err(last_err)
+}
+
+async fn nameservers() -> Vec<SocketAddr> {
+    let content = tokio::fs::read_to_string(RESOLV_CONF)
+        .await
+        .unwrap_or_default();
+    let servers: Vec<SocketAddr> = content
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("nameserver"))
+        .filter_map(|rest| rest.trim().parse::<IpAddr>().ok())
+        .map(|ip| SocketAddr::new(ip, DNS_PORT))
+        .collect();
+    if servers.is_empty() {
+        return vec![SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), DNS_PORT)];
+    }
+    servers
+}
+
+async fn query_server(server: SocketAddr, query: &[u8]) -> Result<Vec<u8>> {
+    let bind: SocketAddr = if server.is_ipv4() {
+        "0.0.0.0:0".parse()?
+    } else {
+        "[::]:0".parse()?
+    };
+    let socket = UdpSocket::bind(bind).await?;
+    socket.connect(server).await?;
+    socket.send(query).await?;
+    let mut buf = vec![0u8; 4096];
+    let response = loop {
+        let len = timeout(QUERY_TIMEOUT, socket.recv(&mut buf))
+            .await
+            .map_err(|_| anyhow!("query timeout"))??;
+        // 忽略 ID 不匹配的迟到应答
+        if len >= 2 && buf[..2] == query[..2] {
+            break buf[..len].to_vec();
+        }
+    };
+    if response.len() > 2 && response[2] & 0x02 != 0 {
+        return query_tcp(server, query).await;
+    }
+    Ok(response)
+}
+
+async fn query_tcp(server: SocketAddr, query: &[u8]) -> Result<Vec<u8>> {
+    let exchange = async {
+        let mut stream = TcpStream::connect(server).await?;
+        let mut framed = Vec::with_capacity(query.len() + 2);
+        framed.extend_from_slice(&(query.len() as u16).to_be_bytes());
+        framed.extend_from_slice(query);
+        stream.write_all(&framed).await?;
+        let len = stream.read_u16().await? as usize;
+        let mut response = vec![0u8; len];
+        stream.read_exact(&mut response).await?;
+        Ok::<_, std::io::Error>(response)
+    };
+    Ok(timeout(QUERY_TIMEOUT, exchange)
+        .await
+        .map_err(|_| anyhow!("tcp query timeout"))??)
+}
+
+fn encode_query(name: &str, qtype: u16) -> Result<Vec<u8>> {
+    let id = uuid::Uuid::new_v4().as_u128() as u16;
+    let mut query = Vec::with_capacity(name.len() + 18);
+    query.extend_from_slice(&id.to_be_bytes());
+    // 标准查询，期望递归
+    query.extend_from_slice(&[0x01, 0x00]);
+    query.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            bail!("invalid dns name {}", name);
+        }
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&qtype.to_be_bytes());
+    query.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(query)
+}
+
+fn decode_srv(msg: &[u8], query: &[u8]) -> Result<Vec<SrvRecord>> {
+    if msg.len() < 12 || msg[..2] != query[..2] {
+        bail!("malformed dns response");
+    }
+    if msg[2] & 0x80 == 0 {
+        bail!("dns response expected");
+    }
+    match msg[3] & 0x0f {
+        0 => {}
+        RCODE_NXDOMAIN => bail!("no such name"),
+        rcode => bail!("dns error rcode {}", rcode),
+    }
+    let qdcount = read_u16(msg, 4)? as usize;
+    let ancount = read_u16(msg, 6)? as usize;
+
+    let mut pos = 12;
+    for _ in 0..qdcount {
+        pos = skip_name(msg, pos)? + 4;
+    }
+    let mut records = Vec::new();
+    for _ in 0..ancount {
+        pos = skip_name(msg, pos)?;
+        let rtype = read_u16(msg, pos)?;
+        let rdlength = read_u16(msg, pos + 8)? as usize;
+        let rdata = pos + 10;
+        if rdata + rdlength > msg.len() {
+            bail!("truncated dns record");
+        }
+        // 应答中可能夹带 CNAME 等其他记录
+        if rtype == TYPE_SRV {
+            let target = read_name(msg, rdata + 6)?;
+            records.push(SrvRecord {
+                priority: read_u16(msg, rdata)?,
+                weight: read_u16(msg, rdata + 2)?,
+                port: read_u16(msg, rdata + 4)?,
+                target,
+            });
+        }
+        pos = rdata + rdlength;
+    }
+    Ok(records)
+}
+
+fn read_u16(msg: &[u8], pos: usize) -> Result<u16> {
+    msg.get(pos..pos + 2)
+        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+        .ok_or_else(|| anyhow!("truncated dns response"))
+}
+
+/// 跳过一个（可能被压缩的）名称，返回其后的位置
+fn skip_name(msg: &[u8], mut pos: usize) -> Result<usize> {
+    loop {
+        let len = *msg.get(pos).ok_or_else(|| anyhow!("truncated dns name"))?;
+        match len {
+            0 => return Ok(pos + 1),
+            len if len & 0xc0 == 0xc0 => return Ok(pos + 2),
+            len => pos += 1 + len as usize,
+        }
+    }
+}
+
+/// 读取名称（处理压缩指针）
+fn read_name(msg: &[u8], start: usize) -> Result<String> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut pos = start;
+    let mut jumps = 0;
+    loop {
+        let len = *msg.get(pos).ok_or_else(|| anyhow!("truncated dns name"))?;
+        if len == 0 {
+            break;
+        }
+        if len & 0xc0 == 0xc0 {
+            jumps += 1;
+            if jumps > MAX_POINTER_JUMPS {
+                bail!("dns name pointer loop");
+            }
+            pos = (read_u16(msg, pos)? & 0x3fff) as usize;
+            continue;
+        }
+        let label = msg
+            .get(pos + 1..pos + 1 + len as usize)
+            .ok_or_else(|| anyhow!("truncated dns label"))?;
+        labels.push(String::from_utf8_lossy(label).into_owned());
+        pos += 1 + len as usize;
+    }
+    Ok(labels.join("."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NAME: &str = "_http._tcp.api.internal";
+    /// 问题区中 `api.internal` 的偏移：12 字节报头之后跳过 `_http`、`_tcp` 两个标签
+    const API_INTERNAL: u16 = 12 + 6 + 5;
+
+    fn labels(name: &str) -> Vec<u8> {
+        let mut out = Vec::new();
+        for label in name.split('.') {
+            out.push(label.len() as u8);
+            out.extend_from_slice(label.as_bytes());
+        }
+        out
+    }
+
+    fn pointer(offset: u16) -> [u8; 2] {
+        (0xc000 | offset).to_be_bytes()
+    }
+
+    /// 名称指向问题区的一条应答记录
+    fn record(rtype: u16, rdata: &[u8]) -> Vec<u8> {
+        let mut out = pointer(12).to_vec();
+        out.extend_from_slice(&rtype.to_be_bytes());
+        out.extend_from_slice(&CLASS_IN.to_be_bytes());
+        out.extend_from_slice(&300u32.to_be_bytes());
+        out.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        out.extend_from_slice(rdata);
+        out
+    }
+
+    fn srv(priority: u16, weight: u16, port: u16, target: &[u8]) -> Vec<u8> {
+        let mut rdata = Vec::new();
+        rdata.extend_from_slice(&priority.to_be_bytes());
+        rdata.extend_from_slice(&weight.to_be_bytes());
+        rdata.extend_from_slice(&port.to_be_bytes());
+        rdata.extend_from_slice(target);
+        record(TYPE_SRV, &rdata)
+    }
+
+    fn response(query: &[u8], rcode: u8, answers: &[Vec<u8>]) -> Vec<u8> {
+        let mut out = query[..2].to_vec();
+        out.extend_from_slice(&[0x81, 0x80 | rcode]);
+        out.extend_from_slice(&1u16.to_be_bytes());
+        out.extend_from_slice(&(answers.len() as u16).to_be_bytes());
+        out.extend_from_slice(&[0, 0, 0, 0]);
+        out.extend_from_slice(&query[12..]);
+        for answer in answers {
+            out.extend_from_slice(answer);
+        }
+        out
+    }
+
+    #[test]
+    fn test_encode_query() {
+        let query = encode_query("api.internal.", TYPE_SRV).unwrap();
+        assert_eq!(&query[2..12], &[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+        let mut question = labels("api.internal");
+        question.push(0);
+        question.extend_from_slice(&[0, 33, 0, 1]);
+        assert_eq!(&query[12..], question.as_slice(), "末尾的点被忽略");
+
+        assert!(encode_query("api..internal", TYPE_SRV).is_err(), "空标签");
+        assert!(encode_query("", TYPE_SRV).is_err());
+        let long = format!("{}.internal", "a".repeat(64));
+        assert!(encode_query(&long, TYPE_SRV).is_err(), "标签超过 63 字节");
+        let max = format!("{}.internal", "a".repeat(63));
+        assert!(encode_query(&max, TYPE_SRV).is_ok());
+    }
+
+    #[test]
+    fn test_decode_srv_compressed_names() {
+        let query = encode_query(NAME, TYPE_SRV).unwrap();
+        let mut alias = labels("alias");
+        alias.extend_from_slice(&pointer(API_INTERNAL));
+        let mut node1 = labels("node1");
+        node1.extend_from_slice(&pointer(API_INTERNAL));
+        let mut node2 = labels("node2.example");
+        node2.push(0);
+        let msg = response(
+            &query,
+            0,
+            &[
+                record(5, &alias),
+                srv(10, 5, 8080, &node1),
+                srv(20, 1, 9090, &node2),
+            ],
+        );
+
+        let records = decode_srv(&msg, &query).unwrap();
+        assert_eq!(
+            records,
+            vec![
+                SrvRecord {
+                    priority: 10,
+                    weight: 5,
+                    port: 8080,
+                    target: "node1.api.internal".to_string(),
+                },
+                SrvRecord {
+                    priority: 20,
+                    weight: 1,
+                    port: 9090,
+                    target: "node2.example".to_string(),
+                },
+            ],
+            "跳过 CNAME，展开压缩指针"
+        );
+    }
+
+    #[test]
+    fn test_decode_srv_errors() {
+        let query = encode_query(NAME, TYPE_SRV).unwrap();
+        let nxdomain = response(&query, RCODE_NXDOMAIN, &[]);
+        let err = decode_srv(&nxdomain, &query).unwrap_err();
+        assert_eq!(err.to_string(), "no such name");
+        let servfail = response(&query, 2, &[]);
+        assert_eq!(
+            decode_srv(&servfail, &query).unwrap_err().to_string(),
+            "dns error rcode 2"
+        );
+
+        let mut target = labels("node1");
+        target.push(0);
+        let full = response(&query, 0, &[srv(10, 5, 8080, &target)]);
+        assert!(decode_srv(&full, &query).is_ok());
+        for len in [11, full.len() - 1, full.len() - target.len() - 4] {
+            assert!(
+                decode_srv(&full[..len], &query).is_err(),
+                "截断到 {} 字节",
+                len
+            );
+        }
+
+        let mut other_id = full.clone();
+        other_id[0] ^= 0xff;
+        assert!(decode_srv(&other_id, &query).is_err(), "ID 不匹配");
+        let mut not_response = full;
+        not_response[2] &= 0x7f;
+        assert!(decode_srv(&not_response, &query).is_err(), "不是应答");
+    }
+
+    #[test]
+    fn test_read_name_pointer_loop() {
+        let mut msg = vec![0u8; 12];
+        msg.extend_from_slice(&pointer(12));
+        let err = read_name(&msg, 12).unwrap_err();
+        assert_eq!(err.to_string(), "dns name pointer loop", "指向自身");
+
+        let mut msg = vec![0u8; 12];
+        msg.extend_from_slice(&labels("a"));
+        msg.extend_from_slice(&pointer(16));
+        msg.extend_from_slice(&pointer(12));
+        assert!(read_name(&msg, 12).is_err(), "两个指针互指");
+
+        let mut msg = vec![0u8; 12];
+        msg.extend_from_slice(&labels("api"));
+        msg.extend_from_slice(&pointer(200));
+        assert!(read_name(&msg, 12).is_err(), "指针越界");
+    }
+}
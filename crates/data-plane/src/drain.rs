@@ -0,0 +1,146 @@
+use crate::proxy::TargetRuntime;
+use gateway_common::models::DrainingTarget;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// 目标被移除、禁用或摘流后等待进行中请求完成的缺省时间
+pub const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 摘流中的上游目标，跨配置版本存在，直到请求全部完成或超时
+struct Draining {
+    pool_id: Uuid,
+    target: Arc<TargetRuntime>,
+    deadline: Instant,
+}
+
+fn registry() -> &'static Mutex<Vec<Draining>> {
+    static REGISTRY: OnceLock<Mutex<Vec<Draining>>> = OnceLock::new();
+    REGISTRY.get_or_init(Mutex::default)
+}
+
+/// 登记一个不再分配新请求的目标；之后仍在处理的请求须在超时前完成
+pub fn start(pool_id: Uuid, target: Arc<TargetRuntime>, timeout: Duration) {
+    let deadline = Instant::now() + timeout;
+    target.start_drain(deadline);
+    info!(
+        "draining upstream target {} ({}) of pool {}, {} inflight, timeout {:?}",
+        target.id(),
+        target.address(),
+        pool_id,
+        target.inflight(),
+        timeout
+    );
+    let mut draining = registry().lock().unwrap();
+    draining.retain(|entry| !Arc::ptr_eq(&entry.target, &target));
+    draining.push(Draining {
+        pool_id,
+        target,
+        deadline,
+    });
+}
+
+/// 当前摘流中的目标；已完成或已超时的目标从登记表中移除
+pub fn report() -> Vec<DrainingTarget> {
+    let now = Instant::now();
+    let mut draining = registry().lock().unwrap();
+    draining.retain(|entry| {
+        let inflight = entry.target.inflight();
+        if inflight == 0 {
+            info!(
+                "upstream target {} ({}) of pool {} drained",
+                entry.target.id(),
+                entry.target.address(),
+                entry.pool_id
+            );
+            return false;
+        }
+        if now >= entry.deadline {
+            warn!(
+                "upstream target {} ({}) of pool {} drain timeout, {} inflight interrupted",
+                entry.target.id(),
+                entry.target.address(),
+                entry.pool_id,
+                inflight
+            );
+            return false;
+        }
+        true
+    });
+    draining
+        .iter()
+        .map(|entry| DrainingTarget {
+            pool_id: entry.pool_id,
+            target_id: entry.target.id(),
+            address: entry.target.address().to_string(),
+            inflight: entry.target.inflight(),
+            remaining_secs: entry.deadline.saturating_duration_since(now).as_secs(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(address: &str) -> Arc<TargetRuntime> {
+        Arc::new(TargetRuntime::new(
+            Uuid::new_v4(),
+            address.to_string(),
+            1,
+            None,
+        ))
+    }
+
+    fn reported(pool_id: Uuid) -> Vec<DrainingTarget> {
+        report()
+            .into_iter()
+            .filter(|entry| entry.pool_id == pool_id)
+            .collect()
+    }
+
+    #[test]
+    fn test_drain_until_inflight_done() {
+        let pool_id = Uuid::new_v4();
+        let target = target("10.0.0.1:80");
+        target.acquire();
+        target.acquire();
+        start(pool_id, target.clone(), Duration::from_secs(60));
+
+        let entries = reported(pool_id);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].target_id, target.id());
+        assert_eq!(entries[0].address, "10.0.0.1:80");
+        assert_eq!(entries[0].inflight, 2);
+
+        target.release();
+        assert_eq!(reported(pool_id)[0].inflight, 1);
+        target.release();
+        assert!(reported(pool_id).is_empty(), "请求全部完成后移除");
+    }
+
+    #[test]
+    fn test_drain_removed_after_deadline() {
+        let pool_id = Uuid::new_v4();
+        let target = target("10.0.0.2:80");
+        target.acquire();
+        start(pool_id, target.clone(), Duration::ZERO);
+
+        assert!(reported(pool_id).is_empty(), "超时后移除");
+        assert_eq!(target.inflight(), 1, "超时不改变计数");
+    }
+
+    #[test]
+    fn test_drain_restart_replaces_entry() {
+        let pool_id = Uuid::new_v4();
+        let target = target("10.0.0.3:80");
+        target.acquire();
+        start(pool_id, target.clone(), Duration::from_secs(60));
+        start(pool_id, target.clone(), Duration::from_secs(60));
+
+        assert_eq!(reported(pool_id).len(), 1, "同一目标只登记一次");
+        target.release();
+        assert!(reported(pool_id).is_empty());
+    }
+}
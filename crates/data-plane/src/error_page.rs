@@ -0,0 +1,302 @@
+use bytes::Bytes;
+use gateway_common::models::{ErrorPage, ErrorPagesOptions};
+use serde_json::json;
+use std::collections::HashMap;
+
+const PROBLEM_JSON: &str = "application/problem+json";
+
+/// 监听器或路由的错误页配置（运行时）
+pub struct ErrorPages {
+    by_status: HashMap<u16, Vec<ErrorPage>>,
+    by_class: HashMap<u16, Vec<ErrorPage>>,
+    problem_json: bool,
+}
+
+/// 协商后的错误响应
+pub struct RenderedError {
+    pub content_type: String,
+    pub body: Bytes,
+}
+
+impl ErrorPages {
+    pub fn from_options(options: &ErrorPagesOptions) -> Self {
+        let mut by_status = HashMap::new();
+        let mut by_class = HashMap::new();
+        for (key, pages) in &options.pages {
+            if pages.is_empty() {
+                continue;
+            }
+            match key.as_str() {
+                "4xx" => {
+                    by_class.insert(4, pages.clone());
+                }
+                "5xx" => {
+                    by_class.insert(5, pages.clone());
+                }
+                _ => {
+                    if let Ok(status) = key.parse::<u16>() {
+                        by_status.insert(status, pages.clone());
+                    }
+                }
+            }
+        }
+        Self {
+            by_status,
+            by_class,
+            problem_json: options.problem_json,
+        }
+    }
+
+    /// 按状态码（优先精确状态码，其次状态类别）取候选响应，再按 Accept 头选择；
+    /// 未配置该状态且未启用问题文档时返回 None
+    pub fn render(
+        &self,
+        status: u16,
+        reason: &str,
+        request_id: &str,
+        accept: Option<&str>,
+    ) -> Option<RenderedError> {
+        let pages = self
+            .by_status
+            .get(&status)
+            .or_else(|| self.by_class.get(&(status / 100)))
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let mut candidates: Vec<&str> = pages.iter().map(|p| p.content_type.as_str()).collect();
+        if self.problem_json {
+            candidates.push(PROBLEM_JSON);
+        }
+        if candidates.is_empty() {
+            return None;
+        }
+
+        let chosen = negotiate(accept.unwrap_or(""), &candidates);
+        let Some(page) = pages.get(chosen) else {
+            let mut problem = json!({
+                "type": "about:blank",
+                "title": reason,
+                "status": status,
+            });
+            if !request_id.is_empty() {
+                problem["request_id"] = json!(request_id);
+            }
+            return Some(RenderedError {
+                content_type: PROBLEM_JSON.to_string(),
+                body: Bytes::from(problem.to_string()),
+            });
+        };
+        let body = page
+            .body
+            .replace("{{status}}", &status.to_string())
+            .replace("{{reason}}", reason)
+            .replace("{{request_id}}", request_id);
+        Some(RenderedError {
+            content_type: page.content_type.clone(),
+            body: Bytes::from(body),
+        })
+    }
+}
+
+/// 路由的错误页优先；路由未配置该状态（且未启用问题文档）时回退到监听器的错误页
+pub fn render_layered(
+    route: Option<&ErrorPages>,
+    listener: Option<&ErrorPages>,
+    status: u16,
+    reason: &str,
+    request_id: &str,
+    accept: Option<&str>,
+) -> Option<RenderedError> {
+    route
+        .into_iter()
+        .chain(listener)
+        .find_map(|pages| pages.render(status, reason, request_id, accept))
+}
+
+/// 选择 Accept 中质量值最高的候选（同质量取配置顺序靠前者）；
+/// 未携带 Accept 或没有可接受的候选时返回第一个候选
+fn negotiate(accept: &str, candidates: &[&str]) -> usize {
+    let ranges: Vec<(&str, f32)> = accept
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';');
+            let range = parts.next()?.trim();
+            if range.is_empty() {
+                return None;
+            }
+            let q = parts
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            Some((range, q))
+        })
+        .collect();
+    if ranges.is_empty() {
+        return 0;
+    }
+
+    let mut best = (0, 0.0);
+    for (i, candidate) in candidates.iter().enumerate() {
+        let q = quality(&ranges, candidate);
+        if q > best.1 {
+            best = (i, q);
+        }
+    }
+    best.0
+}
+
+/// 候选类型的质量值取最具体的匹配范围（`type/subtype` > `type/*` > `*/*`）
+fn quality(ranges: &[(&str, f32)], content_type: &str) -> f32 {
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+    let kind = essence.split('/').next().unwrap_or("");
+    let mut matched = (0, 0.0);
+    for (range, q) in ranges {
+        let range = range.to_ascii_lowercase();
+        let specificity = if range == essence {
+            3
+        } else if range.strip_suffix("/*") == Some(kind) {
+            2
+        } else if range == "*/*" {
+            1
+        } else {
+            continue;
+        };
+        if specificity > matched.0 {
+            matched = (specificity, *q);
+        }
+    }
+    matched.1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pages(entries: &[(&str, &str, &str)], problem_json: bool) -> ErrorPages {
+        let mut options = ErrorPagesOptions {
+            pages: HashMap::new(),
+            problem_json,
+        };
+        for (key, content_type, body) in entries {
+            options
+                .pages
+                .entry(key.to_string())
+                .or_default()
+                .push(ErrorPage {
+                    content_type: content_type.to_string(),
+                    body: body.to_string(),
+                });
+        }
+        ErrorPages::from_options(&options)
+    }
+
+    fn body(rendered: Option<RenderedError>) -> Option<String> {
+        rendered.map(|rendered| String::from_utf8(rendered.body.to_vec()).unwrap())
+    }
+
+    #[test]
+    fn test_status_over_class() {
+        let pages = pages(
+            &[
+                ("404", "text/plain", "missing {{status}}"),
+                ("4xx", "text/plain", "client {{status}} {{reason}}"),
+                ("5xx", "text/plain", "server {{request_id}}"),
+                ("abc", "text/plain", "ignored"),
+            ],
+            false,
+        );
+        let render = |status| body(pages.render(status, "Reason", "req-1", None));
+
+        assert_eq!(
+            render(404).as_deref(),
+            Some("missing 404"),
+            "精确状态码优先"
+        );
+        assert_eq!(render(400).as_deref(), Some("client 400 Reason"));
+        assert_eq!(render(499).as_deref(), Some("client 499 Reason"));
+        assert_eq!(render(500).as_deref(), Some("server req-1"));
+        assert_eq!(render(599).as_deref(), Some("server req-1"));
+        assert_eq!(render(302), None, "未配置的类别");
+    }
+
+    #[test]
+    fn test_route_over_listener() {
+        let route = pages(&[("503", "text/plain", "route")], false);
+        let listener = pages(
+            &[
+                ("503", "text/plain", "listener 503"),
+                ("5xx", "text/plain", "listener 5xx"),
+            ],
+            false,
+        );
+        let render = |route: Option<&ErrorPages>, status| {
+            body(render_layered(
+                route,
+                Some(&listener),
+                status,
+                "Reason",
+                "",
+                None,
+            ))
+        };
+
+        assert_eq!(render(Some(&route), 503).as_deref(), Some("route"));
+        assert_eq!(
+            render(Some(&route), 502).as_deref(),
+            Some("listener 5xx"),
+            "路由未配置的状态回退到监听器"
+        );
+        assert_eq!(render(None, 503).as_deref(), Some("listener 503"));
+        assert!(render_layered(None, None, 503, "Reason", "", None).is_none());
+    }
+
+    #[test]
+    fn test_route_problem_json_shadows_listener() {
+        let route = pages(&[], true);
+        let listener = pages(&[("5xx", "text/html", "<p>listener</p>")], false);
+        let rendered = render_layered(
+            Some(&route),
+            Some(&listener),
+            502,
+            "Bad Gateway",
+            "req-2",
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            rendered.content_type, PROBLEM_JSON,
+            "路由启用问题文档时不回退"
+        );
+        let problem: serde_json::Value = serde_json::from_slice(&rendered.body).unwrap();
+        assert_eq!(problem["status"], 502);
+        assert_eq!(problem["request_id"], "req-2");
+    }
+
+    #[test]
+    fn test_accept_negotiation() {
+        let pages = pages(
+            &[
+                ("500", "text/html", "<p>error</p>"),
+                ("500", "application/json", "{}"),
+            ],
+            false,
+        );
+        let content_type = |accept| {
+            pages
+                .render(500, "Internal Server Error", "", accept)
+                .unwrap()
+                .content_type
+        };
+        assert_eq!(content_type(None), "text/html", "缺省取第一个");
+        assert_eq!(content_type(Some("application/json")), "application/json");
+        assert_eq!(
+            content_type(Some("text/*;q=0.5, application/*;q=0.9")),
+            "application/json"
+        );
+        assert_eq!(content_type(Some("image/png")), "text/html", "无可接受候选");
+    }
+}
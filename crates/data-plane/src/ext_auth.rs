@@ -0,0 +1,485 @@
+use crate::proxy::{RuntimeConfig, UpstreamProtocol};
+use anyhow::{Context, Result, anyhow, bail};
+use bytes::{Bytes, BytesMut};
+use gateway_common::models::ExtAuthOptions;
+use gateway_common::net::unix_socket_path;
+use http::header::{self, HeaderName, HeaderValue};
+use pingora::http::RequestHeader;
+use reqwest::Client;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::warn;
+use uuid::Uuid;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_millis(1000);
+/// 拒绝响应体的最大长度，超出部分截断
+const MAX_DENIED_BODY: usize = 64 * 1024;
+/// 缓存条目上限，满时先清理过期条目，仍满则清空
+const MAX_CACHE_ENTRIES: usize = 10_000;
+
+/// 原样返回客户端前需去掉的逐跳响应头（content-length 按截断后的响应体重新计算）
+const HOP_BY_HOP: [HeaderName; 8] = [
+    header::CONNECTION,
+    HeaderName::from_static("keep-alive"),
+    header::CONTENT_LENGTH,
+    header::TRANSFER_ENCODING,
+    header::TE,
+    header::TRAILER,
+    header::UPGRADE,
+    header::PROXY_AUTHENTICATE,
+];
+
+/// 路由的外部授权策略（运行时）
+pub struct ExtAuthPolicy {
+    service: AuthService,
+    request_headers: Vec<HeaderName>,
+    response_headers: Vec<HeaderName>,
+    timeout: Duration,
+    cache_ttl: Option<Duration>,
+}
+
+enum AuthService {
+    Url(String),
+    Pool {
+        pool_id: Uuid,
+        path: String,
+    },
+    /// 配置无效：拒绝所有请求，而不是跳过授权
+    Invalid,
+}
+
+/// 授权结果
+pub enum Decision {
+    /// 放行，附带需复制到上游请求的授权响应头
+    Allow(Vec<(HeaderName, HeaderValue)>),
+    Deny(DeniedResponse),
+}
+
+pub struct DeniedResponse {
+    pub status: u16,
+    pub headers: Vec<(HeaderName, HeaderValue)>,
+    pub body: Bytes,
+}
+
+/// 客户端请求的来源信息，随子请求以 X-Forwarded-* 头发送
+pub struct AuthRequest<'a> {
+    pub header: &'a RequestHeader,
+    pub client_ip: Option<IpAddr>,
+    pub proto: &'static str,
+    pub host: Option<&'a str>,
+}
+
+impl ExtAuthPolicy {
+    /// 配置无效时路由仍然生效，但所有请求都因授权失败被拒绝
+    pub fn from_options(route_id: Uuid, options: &ExtAuthOptions) -> Self {
+        let parse_names = |names: &[String]| {
+            names
+                .iter()
+                .map(|name| HeaderName::from_bytes(name.as_bytes()).ok())
+                .collect::<Option<Vec<_>>>()
+        };
+        let request_headers = parse_names(&options.request_headers);
+        let response_headers = parse_names(&options.response_headers);
+        let service = match (&options.url, options.upstream_pool_id) {
+            _ if request_headers.is_none() || response_headers.is_none() => AuthService::Invalid,
+            (Some(url), None) => AuthService::Url(url.clone()),
+            (None, Some(pool_id)) => AuthService::Pool {
+                pool_id,
+                path: options.path.clone().unwrap_or_else(|| "/".to_string()),
+            },
+            _ => AuthService::Invalid,
+        };
+        if matches!(service, AuthService::Invalid) {
+            warn!(
+                "invalid ext_auth for route {}, all requests denied",
+                route_id
+            );
+        }
+        Self {
+            service,
+            request_headers: request_headers.unwrap_or_default(),
+            response_headers: response_headers.unwrap_or_default(),
+            timeout: options
+                .timeout_ms
+                .filter(|ms| *ms > 0)
+                .map(Duration::from_millis)
+                .unwrap_or(DEFAULT_TIMEOUT),
+            cache_ttl: options
+                .cache_ttl_secs
+                .filter(|secs| *secs > 0)
+                .map(Duration::from_secs),
+        }
+    }
+
+    /// 放行时需从客户端请求中移除的头，防止客户端伪造授权服务下发的头
+    pub fn response_headers(&self) -> &[HeaderName] {
+        &self.response_headers
+    }
+
+    /// 取得授权结果；bool 表示是否命中缓存
+    pub async fn check(
+        &self,
+        route_id: Uuid,
+        runtime: &RuntimeConfig,
+        request: &AuthRequest<'_>,
+    ) -> Result<(Arc<Decision>, bool)> {
+        let key = self
+            .cache_ttl
+            .map(|_| CacheKey::new(route_id, self, request));
+        if let Some(decision) = key.as_ref().and_then(cache_get) {
+            return Ok((decision, true));
+        }
+        let decision = Arc::new(self.send(runtime, request).await?);
+        if let (Some(key), Some(ttl)) = (key, self.cache_ttl) {
+            cache_put(key, decision.clone(), ttl);
+        }
+        Ok((decision, false))
+    }
+
+    async fn send(&self, runtime: &RuntimeConfig, request: &AuthRequest<'_>) -> Result<Decision> {
+        let headers = self.auth_headers(request);
+        let response = match &self.service {
+            AuthService::Url(url) => self.get(url, headers).await,
+            AuthService::Pool { pool_id, path } => {
+                let (target, settings) = runtime
+                    .pick_target(*pool_id)
+                    .ok_or_else(|| anyhow!("auth pool {} has no available target", pool_id))?;
+                let scheme = if settings.protocol == UpstreamProtocol::H2 {
+                    "https"
+                } else {
+                    "http"
+                };
+                let response = if unix_socket_path(target.address()).is_some() {
+                    Err(anyhow!(
+                        "unix socket target {} not supported",
+                        target.address()
+                    ))
+                } else {
+                    let url = format!("{}://{}{}", scheme, target.address(), path);
+                    self.get(&url, headers).await
+                };
+                target.release();
+                response
+            }
+            AuthService::Invalid => bail!("invalid ext_auth configuration"),
+        }?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(Decision::Allow(self.allowed_headers(response.headers())));
+        }
+        Ok(Decision::Deny(DeniedResponse {
+            status: status.as_u16(),
+            headers: denied_headers(response.headers()),
+            body: read_limited(response).await?,
+        }))
+    }
+
+    /// 子请求头：配置的客户端请求头，加上描述原始请求的 X-Forwarded-* 头
+    fn auth_headers(&self, request: &AuthRequest<'_>) -> http::HeaderMap {
+        let mut headers = http::HeaderMap::new();
+        for name in &self.request_headers {
+            for value in request.header.headers.get_all(name) {
+                headers.append(name.clone(), value.clone());
+            }
+        }
+        let uri = request
+            .header
+            .uri
+            .path_and_query()
+            .map(|pq| pq.as_str())
+            .unwrap_or("/");
+        let forwarded = [
+            ("x-forwarded-method", Some(request.header.method.as_str())),
+            ("x-forwarded-proto", Some(request.proto)),
+            ("x-forwarded-host", request.host),
+            ("x-forwarded-uri", Some(uri)),
+        ];
+        for (name, value) in forwarded {
+            if let Some(value) = value.and_then(|v| HeaderValue::from_str(v).ok()) {
+                headers.insert(name, value);
+            }
+        }
+        if let Some(ip) = request
+            .client_ip
+            .and_then(|ip| HeaderValue::from_str(&ip.to_string()).ok())
+        {
+            headers.insert("x-forwarded-for", ip);
+        }
+        headers
+    }
+
+    /// 放行时复制到上游请求的授权响应头
+    fn allowed_headers(&self, headers: &http::HeaderMap) -> Vec<(HeaderName, HeaderValue)> {
+        self.response_headers
+            .iter()
+            .flat_map(|name| {
+                headers
+                    .get_all(name)
+                    .iter()
+                    .map(|value| (name.clone(), value.clone()))
+            })
+            .collect()
+    }
+
+    async fn get(&self, url: &str, headers: http::HeaderMap) -> Result<reqwest::Response> {
+        client()
+            .get(url)
+            .headers(headers)
+            .timeout(self.timeout)
+            .send()
+            .await
+            .with_context(|| format!("auth request {}", url))
+    }
+}
+
+/// 拒绝时原样返回客户端的授权响应头
+fn denied_headers(headers: &http::HeaderMap) -> Vec<(HeaderName, HeaderValue)> {
+    headers
+        .iter()
+        .filter(|(name, _)| !HOP_BY_HOP.contains(name))
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect()
+}
+
+async fn read_limited(mut response: reqwest::Response) -> Result<Bytes> {
+    let mut body = BytesMut::new();
+    while let Some(chunk) = response.chunk().await? {
+        let remaining = MAX_DENIED_BODY - body.len();
+        if chunk.len() >= remaining {
+            body.extend_from_slice(&chunk[..remaining]);
+            break;
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body.freeze())
+}
+
+/// 授权服务的重定向（如跳转登录页）须原样返回客户端，不能由网关跟随
+fn client() -> &'static Client {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap_or_else(|err| {
+                warn!("build ext_auth client failed: {}", err);
+                Client::new()
+            })
+    })
+}
+
+/// 缓存键包含子请求携带的全部信息，结果相同的请求才会共享缓存
+#[derive(Hash, PartialEq, Eq)]
+struct CacheKey {
+    route_id: Uuid,
+    method: String,
+    host: Option<String>,
+    uri: String,
+    client_ip: Option<IpAddr>,
+    headers: Vec<Vec<u8>>,
+}
+
+impl CacheKey {
+    fn new(route_id: Uuid, policy: &ExtAuthPolicy, request: &AuthRequest<'_>) -> Self {
+        let headers = policy
+            .request_headers
+            .iter()
+            .map(|name| {
+                let mut joined = Vec::new();
+                for value in request.header.headers.get_all(name) {
+                    joined.extend_from_slice(value.as_bytes());
+                    joined.push(0);
+                }
+                joined
+            })
+            .collect();
+        Self {
+            route_id,
+            method: request.header.method.as_str().to_string(),
+            host: request.host.map(str::to_string),
+            uri: request.header.uri.to_string(),
+            client_ip: request.client_ip,
+            headers,
+        }
+    }
+}
+
+/// 缓存的鉴权结果及其过期时间
+type CacheEntries = HashMap<CacheKey, (Instant, Arc<Decision>)>;
+
+fn cache() -> &'static Mutex<CacheEntries> {
+    static CACHE: OnceLock<Mutex<CacheEntries>> = OnceLock::new();
+    CACHE.get_or_init(Mutex::default)
+}
+
+fn cache_get(key: &CacheKey) -> Option<Arc<Decision>> {
+    let cache = cache().lock().unwrap();
+    let (expires, decision) = cache.get(key)?;
+    (Instant::now() < *expires).then(|| decision.clone())
+}
+
+fn cache_put(key: CacheKey, decision: Arc<Decision>, ttl: Duration) {
+    let now = Instant::now();
+    let mut cache = cache().lock().unwrap();
+    if cache.len() >= MAX_CACHE_ENTRIES {
+        cache.retain(|_, (expires, _)| now < *expires);
+        if cache.len() >= MAX_CACHE_ENTRIES {
+            cache.clear();
+        }
+    }
+    cache.insert(key, (now + ttl, decision));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(options: serde_json::Value) -> ExtAuthPolicy {
+        let options: ExtAuthOptions = serde_json::from_value(options).unwrap();
+        ExtAuthPolicy::from_options(Uuid::new_v4(), &options)
+    }
+
+    fn request_header() -> RequestHeader {
+        let mut header = RequestHeader::build("POST", b"/api/orders?page=2", None).unwrap();
+        header.append_header("authorization", "Bearer t").unwrap();
+        header.append_header("cookie", "a=1").unwrap();
+        header.append_header("cookie", "b=2").unwrap();
+        header.append_header("x-other", "skip").unwrap();
+        header
+    }
+
+    #[test]
+    fn test_invalid_options_deny() {
+        for options in [
+            serde_json::json!({}),
+            serde_json::json!({
+                "url": "http://auth.internal/check",
+                "upstream_pool_id": Uuid::new_v4(),
+            }),
+            serde_json::json!({
+                "url": "http://auth.internal/check",
+                "request_headers": ["bad header"],
+            }),
+        ] {
+            assert!(
+                matches!(policy(options.clone()).service, AuthService::Invalid),
+                "{} 应视为无效",
+                options
+            );
+        }
+        let pool = policy(serde_json::json!({ "upstream_pool_id": Uuid::new_v4() }));
+        assert!(matches!(pool.service, AuthService::Pool { ref path, .. } if path == "/"));
+        assert_eq!(pool.timeout, DEFAULT_TIMEOUT);
+        assert_eq!(pool.cache_ttl, None);
+    }
+
+    #[test]
+    fn test_auth_request_headers() {
+        let policy = policy(serde_json::json!({
+            "url": "http://auth.internal/check",
+            "request_headers": ["authorization", "cookie"],
+        }));
+        let header = request_header();
+        let request = AuthRequest {
+            header: &header,
+            client_ip: Some("203.0.113.7".parse().unwrap()),
+            proto: "https",
+            host: Some("api.example.com"),
+        };
+        let headers = policy.auth_headers(&request);
+
+        assert_eq!(headers["authorization"], "Bearer t");
+        let cookies: Vec<_> = headers.get_all("cookie").iter().collect();
+        assert_eq!(cookies, ["a=1", "b=2"], "多值请求头全部携带");
+        assert!(!headers.contains_key("x-other"), "只携带配置的请求头");
+        assert_eq!(headers["x-forwarded-method"], "POST");
+        assert_eq!(headers["x-forwarded-proto"], "https");
+        assert_eq!(headers["x-forwarded-host"], "api.example.com");
+        assert_eq!(headers["x-forwarded-uri"], "/api/orders?page=2");
+        assert_eq!(headers["x-forwarded-for"], "203.0.113.7");
+
+        let request = AuthRequest {
+            header: &header,
+            client_ip: None,
+            proto: "http",
+            host: None,
+        };
+        let headers = policy.auth_headers(&request);
+        assert!(!headers.contains_key("x-forwarded-host"));
+        assert!(!headers.contains_key("x-forwarded-for"));
+    }
+
+    #[test]
+    fn test_forwarded_response_headers() {
+        let policy = policy(serde_json::json!({
+            "url": "http://auth.internal/check",
+            "response_headers": ["x-user-id", "x-user-role"],
+        }));
+        let mut response = http::HeaderMap::new();
+        response.insert("x-user-id", HeaderValue::from_static("42"));
+        response.append("x-user-role", HeaderValue::from_static("admin"));
+        response.append("x-user-role", HeaderValue::from_static("ops"));
+        response.insert("x-internal", HeaderValue::from_static("secret"));
+        response.insert(header::LOCATION, HeaderValue::from_static("/login"));
+        response.insert(header::CONTENT_LENGTH, HeaderValue::from_static("10"));
+        response.insert(header::CONNECTION, HeaderValue::from_static("close"));
+
+        let allowed = policy.allowed_headers(&response);
+        let names: Vec<_> = allowed.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["x-user-id", "x-user-role", "x-user-role"],
+            "放行只复制配置的头"
+        );
+
+        let denied = denied_headers(&response);
+        let names: Vec<_> = denied.iter().map(|(name, _)| name.as_str()).collect();
+        assert!(names.contains(&"location"), "拒绝时原样返回");
+        assert!(names.contains(&"x-internal"));
+        assert!(!names.contains(&"content-length"), "去掉逐跳头");
+        assert!(!names.contains(&"connection"));
+    }
+
+    #[test]
+    fn test_cache_key_and_expiry() {
+        let route_id = Uuid::new_v4();
+        let policy = policy(serde_json::json!({
+            "url": "http://auth.internal/check",
+            "request_headers": ["authorization"],
+            "cache_ttl_secs": 60,
+        }));
+        let header = request_header();
+        let request = |header| AuthRequest {
+            header,
+            client_ip: None,
+            proto: "https",
+            host: Some("api.example.com"),
+        };
+        let key = CacheKey::new(route_id, &policy, &request(&header));
+        let mut other = request_header();
+        other.insert_header("authorization", "Bearer u").unwrap();
+        assert!(
+            key != CacheKey::new(route_id, &policy, &request(&other)),
+            "携带的请求头不同则不共享缓存"
+        );
+        let mut ignored = request_header();
+        ignored.insert_header("x-other", "changed").unwrap();
+        assert!(key == CacheKey::new(route_id, &policy, &request(&ignored)));
+
+        cache_put(
+            CacheKey::new(route_id, &policy, &request(&header)),
+            Arc::new(Decision::Allow(Vec::new())),
+            Duration::from_secs(60),
+        );
+        assert!(cache_get(&key).is_some());
+        cache_put(
+            CacheKey::new(route_id, &policy, &request(&header)),
+            Arc::new(Decision::Allow(Vec::new())),
+            Duration::ZERO,
+        );
+        assert!(cache_get(&key).is_none(), "过期后不再命中");
+    }
+}
@@ -0,0 +1,224 @@
+use crate::value_match::ValueMatcher;
+use gateway_common::models::FaultOptions;
+use pingora::http::RequestHeader;
+use std::time::Duration;
+use tracing::warn;
+use uuid::Uuid;
+
+/// 路由的故障注入策略（运行时）
+pub struct FaultInjection {
+    headers: Vec<(String, ValueMatcher)>,
+    delay: Option<(f64, DelayRange)>,
+    abort: Option<(f64, FaultAbort)>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct DelayRange {
+    min: Duration,
+    max: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultAbort {
+    Status(u16),
+    Reset,
+}
+
+/// 对一次请求抽样得到的故障
+#[derive(Debug, Default)]
+pub struct Fault {
+    pub delay: Option<Duration>,
+    pub abort: Option<FaultAbort>,
+}
+
+impl FaultInjection {
+    /// 配置无效（缺少生效条件、条件无法编译或未配置任何故障）时返回 None，不注入故障
+    pub fn from_options(route_id: Uuid, options: &FaultOptions) -> Option<Self> {
+        let headers = options
+            .headers
+            .iter()
+            .map(|(key, value)| Some((key.clone(), ValueMatcher::compile(value)?)))
+            .collect::<Option<Vec<_>>>()
+            .filter(|headers| !headers.is_empty());
+        let delay = options.delay.as_ref().and_then(|delay| {
+            let (min, max) = match (delay.fixed_ms, delay.random_ms) {
+                (Some(fixed), None) => (fixed, fixed),
+                (None, Some([min, max])) if min <= max => (min, max),
+                _ => return None,
+            };
+            let range = DelayRange {
+                min: Duration::from_millis(min),
+                max: Duration::from_millis(max),
+            };
+            Some((delay.percentage, range))
+        });
+        let abort = options
+            .abort
+            .as_ref()
+            .and_then(|abort| match (abort.status, abort.reset) {
+                (None, true) => Some((abort.percentage, FaultAbort::Reset)),
+                (Some(status), false) if (200..=599).contains(&status) => {
+                    Some((abort.percentage, FaultAbort::Status(status)))
+                }
+                _ => None,
+            });
+        let (Some(headers), true) = (headers, delay.is_some() || abort.is_some()) else {
+            warn!("invalid fault injection for route {}, disabled", route_id);
+            return None;
+        };
+        Some(Self {
+            headers,
+            delay,
+            abort,
+        })
+    }
+
+    /// 请求头满足全部条件时按比例抽样，未命中任何故障时返回 None
+    pub fn sample(&self, header: &RequestHeader) -> Option<Fault> {
+        let matched = self.headers.iter().all(|(key, matcher)| {
+            let actual = header
+                .headers
+                .get(key.as_str())
+                .and_then(|v| v.to_str().ok());
+            matcher.matches(actual)
+        });
+        if !matched {
+            return None;
+        }
+        let fault = Fault {
+            delay: self
+                .delay
+                .filter(|(percentage, _)| hit(*percentage))
+                .map(|(_, range)| range.sample()),
+            abort: self
+                .abort
+                .filter(|(percentage, _)| hit(*percentage))
+                .map(|(_, abort)| abort),
+        };
+        (fault.delay.is_some() || fault.abort.is_some()).then_some(fault)
+    }
+}
+
+impl DelayRange {
+    fn sample(&self) -> Duration {
+        let span = (self.max - self.min).as_millis() as u64;
+        if span == 0 {
+            return self.min;
+        }
+        self.min + Duration::from_millis(random() % (span + 1))
+    }
+}
+
+/// 以百分比（可为小数）概率命中
+fn hit(percentage: f64) -> bool {
+    // 比例精确到 0.0001%
+    ((random() % 1_000_000) as f64) < percentage * 10_000.0
+}
+
+/// UUID v4 的低 62 位为随机位
+fn random() -> u64 {
+    Uuid::new_v4().as_u128() as u64 & ((1 << 62) - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn injection(options: serde_json::Value) -> Option<FaultInjection> {
+        let options: FaultOptions = serde_json::from_value(options).unwrap();
+        FaultInjection::from_options(Uuid::new_v4(), &options)
+    }
+
+    fn request(fault: Option<&str>) -> RequestHeader {
+        let mut header = RequestHeader::build("GET", b"/", None).unwrap();
+        if let Some(value) = fault {
+            header.insert_header("x-fault", value).unwrap();
+        }
+        header
+    }
+
+    #[test]
+    fn test_invalid_options_disabled() {
+        let abort = serde_json::json!({ "percentage": 100.0, "status": 503 });
+        for options in [
+            serde_json::json!({ "abort": abort }),
+            serde_json::json!({ "headers": { "x-fault": "on" } }),
+            serde_json::json!({
+                "headers": { "x-fault": { "regex": "(" } },
+                "abort": abort,
+            }),
+            serde_json::json!({
+                "headers": { "x-fault": "on" },
+                "delay": { "percentage": 100.0, "random_ms": [200, 100] },
+            }),
+            serde_json::json!({
+                "headers": { "x-fault": "on" },
+                "delay": { "percentage": 100.0, "fixed_ms": 10, "random_ms": [10, 20] },
+            }),
+            serde_json::json!({
+                "headers": { "x-fault": "on" },
+                "abort": { "percentage": 100.0, "status": 600 },
+            }),
+            serde_json::json!({
+                "headers": { "x-fault": "on" },
+                "abort": { "percentage": 100.0, "status": 503, "reset": true },
+            }),
+        ] {
+            assert!(injection(options.clone()).is_none(), "{} 应禁用", options);
+        }
+    }
+
+    #[test]
+    fn test_sample_requires_headers() {
+        let fault = injection(serde_json::json!({
+            "headers": { "x-fault": "on" },
+            "abort": { "percentage": 100.0, "status": 503 },
+        }))
+        .unwrap();
+        assert!(fault.sample(&request(None)).is_none(), "缺少请求头");
+        assert!(fault.sample(&request(Some("off"))).is_none());
+        let sampled = fault.sample(&request(Some("on"))).unwrap();
+        assert_eq!(sampled.abort, Some(FaultAbort::Status(503)));
+        assert_eq!(sampled.delay, None);
+    }
+
+    #[test]
+    fn test_sample_percentage() {
+        let never = injection(serde_json::json!({
+            "headers": { "x-fault": "on" },
+            "delay": { "percentage": 0.0, "fixed_ms": 100 },
+            "abort": { "percentage": 0.0, "reset": true },
+        }))
+        .unwrap();
+        let always = injection(serde_json::json!({
+            "headers": { "x-fault": "on" },
+            "delay": { "percentage": 100.0, "random_ms": [100, 200] },
+            "abort": { "percentage": 100.0, "reset": true },
+        }))
+        .unwrap();
+        let header = request(Some("on"));
+        for _ in 0..1000 {
+            assert!(never.sample(&header).is_none(), "0% 不注入");
+            let fault = always.sample(&header).expect("100% 必然注入");
+            assert_eq!(fault.abort, Some(FaultAbort::Reset));
+            let delay = fault.delay.unwrap();
+            assert!(
+                (Duration::from_millis(100)..=Duration::from_millis(200)).contains(&delay),
+                "延迟 {:?} 超出范围",
+                delay
+            );
+        }
+    }
+
+    #[test]
+    fn test_fixed_delay() {
+        let fault = injection(serde_json::json!({
+            "headers": { "x-fault": { "present": true } },
+            "delay": { "percentage": 100.0, "fixed_ms": 250 },
+        }))
+        .unwrap();
+        let sampled = fault.sample(&request(Some("any"))).unwrap();
+        assert_eq!(sampled.delay, Some(Duration::from_millis(250)));
+        assert_eq!(sampled.abort, None);
+    }
+}
@@ -0,0 +1,412 @@
+use gateway_common::models::ForwardedOptions;
+use gateway_common::net::{IpCidr, contains_ip};
+use pingora::http::RequestHeader;
+use std::net::IpAddr;
+use tracing::warn;
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+const X_FORWARDED_PROTO: &str = "x-forwarded-proto";
+const X_FORWARDED_HOST: &str = "x-forwarded-host";
+const X_FORWARDED_PORT: &str = "x-forwarded-port";
+const FORWARDED: &str = "forwarded";
+
+/// 来自可信代理的转发头处理方式
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ForwardedMode {
+    /// 保留已有转发链并追加当前一跳
+    Append,
+    /// 以推导出的真实客户端 IP 重写转发头
+    Replace,
+}
+
+impl ForwardedMode {
+    fn from_str(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "append" => Some(Self::Append),
+            "replace" => Some(Self::Replace),
+            _ => None,
+        }
+    }
+}
+
+/// 监听器的转发头配置（运行时）
+#[derive(Clone)]
+pub struct ForwardedConfig {
+    enabled: bool,
+    trusted_proxies: Vec<IpCidr>,
+    mode: ForwardedMode,
+}
+
+impl Default for ForwardedConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            trusted_proxies: Vec::new(),
+            mode: ForwardedMode::Append,
+        }
+    }
+}
+
+/// 当前一跳的连接信息，用于生成转发头
+pub struct ForwardedHop<'a> {
+    pub peer_ip: Option<IpAddr>,
+    pub client_ip: Option<IpAddr>,
+    pub proto: &'a str,
+    pub host: Option<&'a str>,
+    pub port: Option<u16>,
+}
+
+impl ForwardedConfig {
+    pub fn from_options(options: Option<&ForwardedOptions>) -> Self {
+        let Some(options) = options else {
+            return Self::default();
+        };
+        let mode = options
+            .mode
+            .as_deref()
+            .map(|mode| {
+                ForwardedMode::from_str(mode).unwrap_or_else(|| {
+                    warn!("invalid forwarded mode {}, fallback to append", mode);
+                    ForwardedMode::Append
+                })
+            })
+            .unwrap_or(ForwardedMode::Append);
+        Self {
+            enabled: options.enabled.unwrap_or(true),
+            trusted_proxies: options
+                .trusted_proxies
+                .iter()
+                .filter_map(|cidr| IpCidr::parse(cidr))
+                .collect(),
+            mode,
+        }
+    }
+
+    fn is_trusted(&self, ip: Option<IpAddr>) -> bool {
+        ip.is_some_and(|ip| contains_ip(&self.trusted_proxies, ip))
+    }
+
+    /// 推导真实客户端 IP：仅当直连方为可信代理时，从右向左跳过可信代理取第一个地址
+    pub fn client_ip(&self, header: &RequestHeader, peer_ip: Option<IpAddr>) -> Option<IpAddr> {
+        if !self.is_trusted(peer_ip) {
+            return peer_ip;
+        }
+        let mut chain = forwarded_for_chain(header);
+        if chain.is_empty() {
+            return peer_ip;
+        }
+        let leftmost = chain.first().copied();
+        while let Some(ip) = chain.pop() {
+            if !contains_ip(&self.trusted_proxies, ip) {
+                return Some(ip);
+            }
+        }
+        leftmost
+    }
+
+    /// 为发往上游的请求写入 X-Forwarded-* 与 Forwarded 头
+    pub fn apply(
+        &self,
+        upstream: &mut RequestHeader,
+        hop: &ForwardedHop<'_>,
+    ) -> pingora::Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        let trusted = self.is_trusted(hop.peer_ip);
+        let append = trusted && self.mode == ForwardedMode::Append;
+
+        let node = if trusted {
+            hop.client_ip.or(hop.peer_ip)
+        } else {
+            hop.peer_ip
+        };
+        let xff = if append {
+            let existing = joined_values(upstream, X_FORWARDED_FOR);
+            let peer = hop.peer_ip.map(|ip| ip.to_string()).unwrap_or_default();
+            match (existing.is_empty(), peer.is_empty()) {
+                (true, _) => peer,
+                (false, true) => existing,
+                (false, false) => format!("{}, {}", existing, peer),
+            }
+        } else {
+            node.map(|ip| ip.to_string()).unwrap_or_default()
+        };
+        if xff.is_empty() {
+            upstream.remove_header(X_FORWARDED_FOR);
+        } else {
+            upstream.insert_header(X_FORWARDED_FOR, xff)?;
+        }
+
+        // 可信代理传入的 proto/host/port 反映最外层入口，予以保留
+        if !(trusted && upstream.headers.contains_key(X_FORWARDED_PROTO)) {
+            upstream.insert_header(X_FORWARDED_PROTO, hop.proto)?;
+        }
+        if !(trusted && upstream.headers.contains_key(X_FORWARDED_HOST)) {
+            match hop.host {
+                Some(host) => {
+                    upstream.insert_header(X_FORWARDED_HOST, host)?;
+                }
+                None => {
+                    upstream.remove_header(X_FORWARDED_HOST);
+                }
+            }
+        }
+        if !(trusted && upstream.headers.contains_key(X_FORWARDED_PORT)) {
+            match hop.port {
+                Some(port) => {
+                    upstream.insert_header(X_FORWARDED_PORT, port.to_string())?;
+                }
+                None => {
+                    upstream.remove_header(X_FORWARDED_PORT);
+                }
+            }
+        }
+
+        let mut element = format!(
+            "for={}",
+            forwarded_node(if append { hop.peer_ip } else { node })
+        );
+        element.push_str(&format!(";proto={}", hop.proto));
+        if let Some(host) = hop.host {
+            element.push_str(&format!(";host={}", forwarded_value(host)));
+        }
+        let forwarded = if append {
+            let existing = joined_values(upstream, FORWARDED);
+            if existing.is_empty() {
+                element
+            } else {
+                format!("{}, {}", existing, element)
+            }
+        } else {
+            element
+        };
+        upstream.insert_header(FORWARDED, forwarded)?;
+        Ok(())
+    }
+}
+
+/// 收集 X-Forwarded-For（缺失时回退到 Forwarded 的 for=）中的地址链
+fn forwarded_for_chain(header: &RequestHeader) -> Vec<IpAddr> {
+    let xff: Vec<IpAddr> = header
+        .headers
+        .get_all(X_FORWARDED_FOR)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|item| parse_node(item.trim()))
+        .collect();
+    if !xff.is_empty() {
+        return xff;
+    }
+    header
+        .headers
+        .get_all(FORWARDED)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|element| {
+            element.split(';').find_map(|pair| {
+                let (key, value) = pair.trim().split_once('=')?;
+                if key.eq_ignore_ascii_case("for") {
+                    parse_node(value.trim().trim_matches('"'))
+                } else {
+                    None
+                }
+            })
+        })
+        .collect()
+}
+
+/// 解析 `1.2.3.4`、`1.2.3.4:80`、`[::1]` 与 `[::1]:80` 形式的节点地址
+fn parse_node(value: &str) -> Option<IpAddr> {
+    if let Ok(ip) = value.parse::<IpAddr>() {
+        return Some(ip);
+    }
+    if let Some(rest) = value.strip_prefix('[') {
+        let end = rest.find(']')?;
+        return rest[..end].parse().ok();
+    }
+    let (host, _port) = value.rsplit_once(':')?;
+    host.parse().ok()
+}
+
+fn joined_values(header: &RequestHeader, name: &str) -> String {
+    header
+        .headers
+        .get_all(name)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// RFC 7239 节点：IPv6 需加方括号并加引号，未知地址使用 `unknown`
+fn forwarded_node(ip: Option<IpAddr>) -> String {
+    match ip {
+        Some(IpAddr::V6(v6)) => format!("\"[{}]\"", v6),
+        Some(IpAddr::V4(v4)) => v4.to_string(),
+        None => "unknown".to_string(),
+    }
+}
+
+fn forwarded_value(value: &str) -> String {
+    let is_token = value
+        .bytes()
+        .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b));
+    if is_token {
+        value.to_string()
+    } else {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    fn config(mode: &str) -> ForwardedConfig {
+        ForwardedConfig::from_options(Some(&ForwardedOptions {
+            enabled: None,
+            trusted_proxies: vec!["10.0.0.0/8".to_string()],
+            mode: Some(mode.to_string()),
+        }))
+    }
+
+    fn request(headers: &[(&'static str, &str)]) -> RequestHeader {
+        let mut header = RequestHeader::build("GET", b"/", None).unwrap();
+        for (name, value) in headers {
+            header.append_header(*name, *value).unwrap();
+        }
+        header
+    }
+
+    fn hop(peer_ip: &str, client_ip: Option<IpAddr>) -> ForwardedHop<'static> {
+        ForwardedHop {
+            peer_ip: Some(ip(peer_ip)),
+            client_ip,
+            proto: "https",
+            host: Some("example.com"),
+            port: Some(443),
+        }
+    }
+
+    fn value<'a>(header: &'a RequestHeader, name: &str) -> Option<&'a str> {
+        header.headers.get(name).and_then(|v| v.to_str().ok())
+    }
+
+    #[test]
+    fn test_client_ip_from_trusted_proxy() {
+        let config = config("append");
+        let header = request(&[(X_FORWARDED_FOR, "203.0.113.7, 10.0.0.2")]);
+        assert_eq!(
+            config.client_ip(&header, Some(ip("10.0.0.1"))),
+            Some(ip("203.0.113.7")),
+            "从右向左跳过可信代理"
+        );
+        assert_eq!(
+            config.client_ip(&header, Some(ip("198.51.100.1"))),
+            Some(ip("198.51.100.1")),
+            "直连方不可信时忽略转发头"
+        );
+
+        let all_trusted = request(&[(X_FORWARDED_FOR, "10.0.0.3, 10.0.0.2")]);
+        assert_eq!(
+            config.client_ip(&all_trusted, Some(ip("10.0.0.1"))),
+            Some(ip("10.0.0.3")),
+            "链上全是可信代理时取最左侧地址"
+        );
+
+        let forwarded = request(&[(FORWARDED, "for=\"[2001:db8::1]:443\";proto=https")]);
+        assert_eq!(
+            config.client_ip(&forwarded, Some(ip("10.0.0.1"))),
+            Some(ip("2001:db8::1")),
+            "缺少 X-Forwarded-For 时回退到 Forwarded"
+        );
+    }
+
+    #[test]
+    fn test_append_from_trusted_proxy() {
+        let config = config("append");
+        let mut header = request(&[
+            (X_FORWARDED_FOR, "203.0.113.7"),
+            (X_FORWARDED_PROTO, "http"),
+            (FORWARDED, "for=203.0.113.7"),
+        ]);
+        config
+            .apply(&mut header, &hop("10.0.0.1", Some(ip("203.0.113.7"))))
+            .unwrap();
+        assert_eq!(
+            value(&header, X_FORWARDED_FOR),
+            Some("203.0.113.7, 10.0.0.1")
+        );
+        assert_eq!(
+            value(&header, X_FORWARDED_PROTO),
+            Some("http"),
+            "保留可信代理传入的 proto"
+        );
+        assert_eq!(
+            value(&header, FORWARDED),
+            Some("for=203.0.113.7, for=10.0.0.1;proto=https;host=example.com")
+        );
+    }
+
+    #[test]
+    fn test_replace_from_trusted_proxy() {
+        let config = config("replace");
+        let mut header = request(&[(X_FORWARDED_FOR, "203.0.113.7, 10.0.0.2")]);
+        config
+            .apply(&mut header, &hop("10.0.0.1", Some(ip("203.0.113.7"))))
+            .unwrap();
+        assert_eq!(value(&header, X_FORWARDED_FOR), Some("203.0.113.7"));
+        assert_eq!(
+            value(&header, FORWARDED),
+            Some("for=203.0.113.7;proto=https;host=example.com")
+        );
+    }
+
+    #[test]
+    fn test_untrusted_peer_overwrites_headers() {
+        for mode in ["append", "replace"] {
+            let config = config(mode);
+            let mut header = request(&[
+                (X_FORWARDED_FOR, "1.1.1.1"),
+                (X_FORWARDED_PROTO, "http"),
+                (X_FORWARDED_HOST, "spoofed.example"),
+                (FORWARDED, "for=1.1.1.1"),
+            ]);
+            config
+                .apply(&mut header, &hop("198.51.100.1", Some(ip("1.1.1.1"))))
+                .unwrap();
+            assert_eq!(
+                value(&header, X_FORWARDED_FOR),
+                Some("198.51.100.1"),
+                "不可信的直连方不能伪造客户端地址（{}）",
+                mode
+            );
+            assert_eq!(value(&header, X_FORWARDED_PROTO), Some("https"));
+            assert_eq!(value(&header, X_FORWARDED_HOST), Some("example.com"));
+            assert_eq!(value(&header, X_FORWARDED_PORT), Some("443"));
+            assert_eq!(
+                value(&header, FORWARDED),
+                Some("for=198.51.100.1;proto=https;host=example.com")
+            );
+        }
+    }
+
+    #[test]
+    fn test_forwarded_node_format() {
+        assert_eq!(forwarded_node(Some(ip("::1"))), "\"[::1]\"");
+        assert_eq!(forwarded_node(None), "unknown");
+        assert_eq!(forwarded_value("example.com:8443"), "\"example.com:8443\"");
+        assert_eq!(parse_node("[::1]:80"), Some(ip("::1")));
+        assert_eq!(parse_node("1.2.3.4:80"), Some(ip("1.2.3.4")));
+        assert_eq!(parse_node("unknown"), None);
+    }
+}
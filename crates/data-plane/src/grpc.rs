@@ -0,0 +1,127 @@
+use gateway_common::grpc::UNKNOWN;
+use http::HeaderMap;
+use pingora::http::{RequestHeader, ResponseHeader};
+
+pub const GRPC_STATUS: &str = "grpc-status";
+pub const GRPC_MESSAGE: &str = "grpc-message";
+/// 消息前缀：1 字节压缩标志 + 4 字节长度
+const MESSAGE_PREFIX_LEN: usize = 5;
+
+/// 是否为 gRPC 请求（`content-type: application/grpc[+proto|+json|...]`）
+pub fn is_grpc_request(header: &RequestHeader) -> bool {
+    header
+        .headers
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.to_ascii_lowercase().starts_with("application/grpc"))
+}
+
+/// 读取响应头或尾部中的 grpc-status
+pub fn status_from_headers(headers: &HeaderMap) -> Option<u32> {
+    headers
+        .get(GRPC_STATUS)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse().ok())
+}
+
+/// 统计请求体中的 gRPC 消息数，超过一条即为客户端流式调用，不能安全重放
+#[derive(Debug, Default)]
+pub struct MessageCounter {
+    /// 当前消息尚未读到的载荷字节数
+    remaining: usize,
+    prefix: Vec<u8>,
+    messages: u32,
+}
+
+impl MessageCounter {
+    pub fn feed(&mut self, mut chunk: &[u8]) {
+        while !chunk.is_empty() {
+            if self.remaining > 0 {
+                let n = self.remaining.min(chunk.len());
+                self.remaining -= n;
+                chunk = &chunk[n..];
+                continue;
+            }
+            let n = (MESSAGE_PREFIX_LEN - self.prefix.len()).min(chunk.len());
+            self.prefix.extend_from_slice(&chunk[..n]);
+            chunk = &chunk[n..];
+            if self.prefix.len() == MESSAGE_PREFIX_LEN {
+                let len = &self.prefix[1..];
+                self.remaining = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+                self.messages = self.messages.saturating_add(1);
+                self.prefix.clear();
+            }
+        }
+    }
+
+    pub fn messages(&self) -> u32 {
+        self.messages
+    }
+}
+
+/// 网关本地错误的 HTTP 状态码到 gRPC 状态码的映射（与 gRPC 官方 HTTP 映射表一致）
+pub fn status_from_http(status: u16) -> u32 {
+    match status {
+        400 => 13,
+        401 => 16,
+        403 => 7,
+        404 => 12,
+        429 | 502 | 503 | 504 => 14,
+        _ => UNKNOWN,
+    }
+}
+
+/// 构造 trailers-only 的 gRPC 错误响应：HTTP 200，状态放在唯一的 HEADERS 帧中
+pub fn error_response(code: u32, message: &str) -> pingora::Result<ResponseHeader> {
+    let mut header = ResponseHeader::build(200, Some(4))?;
+    header.insert_header("content-type", "application/grpc")?;
+    header.insert_header(GRPC_STATUS, code.to_string())?;
+    header.insert_header(GRPC_MESSAGE, percent_encode(message))?;
+    Ok(header)
+}
+
+/// grpc-message 使用百分号编码（仅保留可打印 ASCII，`%` 本身需编码）
+fn percent_encode(message: &str) -> String {
+    let mut out = String::with_capacity(message.len());
+    for byte in message.bytes() {
+        if (0x20..=0x7e).contains(&byte) && byte != b'%' {
+            out.push(byte as char);
+        } else {
+            out.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(payload: &[u8]) -> Vec<u8> {
+        let mut out = vec![0];
+        out.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        out.extend_from_slice(payload);
+        out
+    }
+
+    #[test]
+    fn test_count_messages() {
+        let mut counter = MessageCounter::default();
+        counter.feed(&message(b"hello"));
+        assert_eq!(counter.messages(), 1);
+
+        let mut body = message(b"first");
+        body.extend(message(b""));
+        body.extend(message(b"third"));
+        // 逐字节喂入，前缀与载荷跨数据块
+        let mut counter = MessageCounter::default();
+        for byte in &body {
+            counter.feed(std::slice::from_ref(byte));
+        }
+        assert_eq!(counter.messages(), 3);
+
+        let mut counter = MessageCounter::default();
+        counter.feed(&body[..3]);
+        assert_eq!(counter.messages(), 0, "前缀不完整时不计数");
+    }
+}
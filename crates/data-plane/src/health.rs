@@ -1,14 +1,11 @@
-use crate::proxy::RuntimeConfig;
+use crate::proxy::SharedRuntime;
 use std::collections::HashMap;
-use std::sync::Arc;
 use std::time::Instant;
-use tokio::net::TcpStream;
-use tokio::sync::RwLock;
 use tokio::time::{Duration, sleep, timeout};
 use tracing::debug;
 
 pub async fn run_health_checks(
-    runtime: Arc<RwLock<RuntimeConfig>>,
+    runtime: SharedRuntime,
     default_interval_secs: u64,
     default_timeout_ms: u64,
 ) {
@@ -17,10 +14,7 @@ pub async fn run_health_checks(
 
     loop {
         let now = Instant::now();
-        let pools = {
-            let runtime = runtime.read().await;
-            runtime.health_pools()
-        };
+        let pools = runtime.load().health_pools();
 
         for (pool_id, health, targets) in pools {
             let interval =
@@ -52,6 +46,6 @@ pub async fn run_health_checks(
 }
 
 async fn check_tcp(address: &str, timeout_duration: Duration) -> bool {
-    let result = timeout(timeout_duration, TcpStream::connect(address)).await;
+    let result = timeout(timeout_duration, crate::l4::connect_upstream(address)).await;
     matches!(result, Ok(Ok(_)))
 }
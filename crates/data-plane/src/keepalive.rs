@@ -0,0 +1,197 @@
+use gateway_common::models::KeepaliveOptions;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// 池的上游连接复用设置（运行时）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeepaliveSettings {
+    pub enabled: bool,
+    pub pool_size: Option<usize>,
+    pub idle_timeout: Duration,
+    pub max_requests: Option<u64>,
+}
+
+impl Default for KeepaliveSettings {
+    fn default() -> Self {
+        Self::from_options(None, crate::drain::DEFAULT_DRAIN_TIMEOUT)
+    }
+}
+
+impl KeepaliveSettings {
+    pub fn from_options(options: Option<&KeepaliveOptions>, drain_timeout: Duration) -> Self {
+        let options = options.cloned().unwrap_or_default();
+        // 摘流目标的空闲连接最多在连接池中保留摘流超时时间
+        let idle_timeout = options
+            .idle_timeout_secs
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs)
+            .map_or(drain_timeout, |timeout| timeout.min(drain_timeout));
+        Self {
+            enabled: options.enabled.unwrap_or(true),
+            pool_size: options.pool_size,
+            idle_timeout,
+            max_requests: options.max_requests.filter(|max| *max > 0),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Registry {
+    /// 各上游连接（按套接字描述符）已承载的请求数；描述符被新连接占用时重新计数
+    requests: HashMap<u64, u64>,
+    /// 各池归还到连接池的时间，用于近似估算池内空闲连接数
+    idle: HashMap<Uuid, VecDeque<Instant>>,
+}
+
+fn registry() -> &'static Mutex<Registry> {
+    static REGISTRY: OnceLock<Mutex<Registry>> = OnceLock::new();
+    REGISTRY.get_or_init(Mutex::default)
+}
+
+/// 请求取得上游连接，返回该连接已承载的请求数（含本次）；复用的连接不再计为空闲
+pub fn connected(pool_id: Uuid, conn: u64, reused: bool) -> u64 {
+    let mut registry = registry().lock().unwrap();
+    if reused && let Some(idle) = registry.idle.get_mut(&pool_id) {
+        idle.pop_front();
+    }
+    let requests = registry.requests.entry(conn).or_default();
+    if !reused {
+        *requests = 0;
+    }
+    *requests += 1;
+    *requests
+}
+
+/// 本次请求结束后是否关闭上游连接，而不是归还到连接池
+pub fn should_close(pool_id: Uuid, settings: &KeepaliveSettings, requests: u64) -> bool {
+    if !settings.enabled || settings.max_requests.is_some_and(|max| requests >= max) {
+        return true;
+    }
+    let Some(pool_size) = settings.pool_size else {
+        return false;
+    };
+    let mut registry = registry().lock().unwrap();
+    let idle = registry.idle.entry(pool_id).or_default();
+    expire(idle, settings.idle_timeout);
+    idle.len() >= pool_size
+}
+
+/// 请求完成后连接归还到连接池
+pub fn released(pool_id: Uuid, settings: &KeepaliveSettings) {
+    if settings.pool_size.is_none() {
+        return;
+    }
+    let mut registry = registry().lock().unwrap();
+    let idle = registry.idle.entry(pool_id).or_default();
+    expire(idle, settings.idle_timeout);
+    idle.push_back(Instant::now());
+}
+
+/// 超过空闲超时的连接已被连接池淘汰
+fn expire(idle: &mut VecDeque<Instant>, idle_timeout: Duration) {
+    while idle
+        .front()
+        .is_some_and(|released| released.elapsed() >= idle_timeout)
+    {
+        idle.pop_front();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(options: KeepaliveOptions) -> KeepaliveSettings {
+        KeepaliveSettings::from_options(Some(&options), Duration::from_secs(30))
+    }
+
+    #[test]
+    fn test_from_options() {
+        let defaults = KeepaliveSettings::default();
+        assert!(defaults.enabled);
+        assert_eq!(defaults.pool_size, None);
+        assert_eq!(defaults.max_requests, None);
+        assert_eq!(defaults.idle_timeout, crate::drain::DEFAULT_DRAIN_TIMEOUT);
+
+        let capped = settings(KeepaliveOptions {
+            idle_timeout_secs: Some(120),
+            max_requests: Some(0),
+            ..Default::default()
+        });
+        assert_eq!(
+            capped.idle_timeout,
+            Duration::from_secs(30),
+            "不超过摘流超时"
+        );
+        assert_eq!(capped.max_requests, None, "0 视为不限制");
+        let shorter = settings(KeepaliveOptions {
+            idle_timeout_secs: Some(10),
+            ..Default::default()
+        });
+        assert_eq!(shorter.idle_timeout, Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_request_count_across_reuse() {
+        let pool_id = Uuid::new_v4();
+        let conn = u64::MAX - 1;
+        assert_eq!(connected(pool_id, conn, false), 1);
+        assert_eq!(connected(pool_id, conn, true), 2);
+        assert_eq!(connected(pool_id, conn, true), 3);
+        assert_eq!(
+            connected(pool_id, conn, false),
+            1,
+            "描述符被新连接占用时重新计数"
+        );
+    }
+
+    #[test]
+    fn test_close_after_max_requests() {
+        let pool_id = Uuid::new_v4();
+        let limited = settings(KeepaliveOptions {
+            max_requests: Some(3),
+            ..Default::default()
+        });
+        assert!(!should_close(pool_id, &limited, 2));
+        assert!(should_close(pool_id, &limited, 3), "达到上限后关闭");
+
+        let disabled = settings(KeepaliveOptions {
+            enabled: Some(false),
+            ..Default::default()
+        });
+        assert!(should_close(pool_id, &disabled, 1), "不复用时每次关闭");
+        assert!(!should_close(pool_id, &KeepaliveSettings::default(), 1000));
+    }
+
+    #[test]
+    fn test_close_when_idle_pool_full() {
+        let pool_id = Uuid::new_v4();
+        let pooled = settings(KeepaliveOptions {
+            pool_size: Some(2),
+            ..Default::default()
+        });
+        assert!(!should_close(pool_id, &pooled, 1));
+        released(pool_id, &pooled);
+        released(pool_id, &pooled);
+        assert!(should_close(pool_id, &pooled, 1), "空闲连接已满");
+
+        connected(pool_id, u64::MAX - 2, true);
+        assert!(!should_close(pool_id, &pooled, 1), "复用的连接不再计为空闲");
+
+        let expired = settings(KeepaliveOptions {
+            pool_size: Some(1),
+            idle_timeout_secs: Some(1),
+            ..Default::default()
+        });
+        let other = Uuid::new_v4();
+        released(other, &expired);
+        assert!(should_close(other, &expired, 1));
+        std::thread::sleep(Duration::from_millis(1100));
+        assert!(
+            !should_close(other, &expired, 1),
+            "超过空闲超时的连接已淘汰"
+        );
+    }
+}
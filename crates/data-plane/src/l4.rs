@@ -0,0 +1,244 @@
+use crate::proxy::{ListenerRuntime, SharedRuntime, TargetRuntime};
+use crate::proxy_protocol::encode_header;
+use crate::sni::{parse_sni, read_client_hello};
+use gateway_common::net::unix_socket_path;
+use pingora::protocols::Stream;
+use pingora::server::ShutdownWatch;
+use std::net::SocketAddr;
+use std::time::Instant;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, copy_bidirectional};
+use tokio::net::{TcpStream, UnixStream};
+use tokio::time::{Duration, timeout};
+use tracing::{debug, info, warn};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const CLIENT_HELLO_TIMEOUT: Duration = Duration::from_secs(5);
+/// 目标开始摘流前，转发中的连接按此间隔检查摘流状态
+const DRAIN_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// tcp / tls_passthrough 监听器的 L4 转发：不解析应用层协议（TLS 透传仅读取 ClientHello 中的 SNI），
+/// 按池策略选取目标后双向拷贝字节流
+pub struct TcpProxy {
+    runtime: SharedRuntime,
+}
+
+impl TcpProxy {
+    pub fn new(runtime: SharedRuntime) -> Self {
+        Self { runtime }
+    }
+
+    pub async fn serve(
+        &self,
+        mut downstream: Stream,
+        mut preface: Vec<u8>,
+        listener: &ListenerRuntime,
+        peer: Option<SocketAddr>,
+        local: Option<SocketAddr>,
+        shutdown: &ShutdownWatch,
+    ) {
+        let start = Instant::now();
+        let mut sni = None;
+        if listener.protocol.eq_ignore_ascii_case("tls_passthrough") {
+            // ClientHello 原样保留在 preface 中，随后转发给上游完成端到端握手
+            match timeout(
+                CLIENT_HELLO_TIMEOUT,
+                read_client_hello(&mut downstream, &mut preface),
+            )
+            .await
+            {
+                Ok(Ok(())) => sni = parse_sni(&preface),
+                Ok(Err(err)) => {
+                    warn!("tls passthrough listener {}: {}", listener.id, err);
+                    crate::metrics::inc_tcp_connection("bad_client_hello");
+                    return;
+                }
+                Err(_) => {
+                    warn!(
+                        "tls passthrough listener {}: ClientHello timeout",
+                        listener.id
+                    );
+                    crate::metrics::inc_tcp_connection("bad_client_hello");
+                    return;
+                }
+            }
+        }
+
+        let picked = self
+            .runtime
+            .load()
+            .pick_stream_target(listener.id, sni.as_deref());
+        let Some((target, proxy_protocol)) = picked else {
+            warn!(
+                "l4 listener {} has no available route (sni {:?})",
+                listener.id, sni
+            );
+            crate::metrics::inc_tcp_connection("no_route");
+            return;
+        };
+
+        let address = target.address().to_string();
+        let result = async {
+            let mut upstream = match timeout(CONNECT_TIMEOUT, connect_upstream(&address)).await {
+                Ok(Ok(stream)) => stream,
+                Ok(Err(err)) => {
+                    crate::metrics::inc_upstream_error("tcp_connect");
+                    return Err(format!("connect {} failed: {}", address, err));
+                }
+                Err(_) => {
+                    crate::metrics::inc_upstream_error("tcp_connect_timeout");
+                    return Err(format!("connect {} timeout", address));
+                }
+            };
+            if let Some(version) = proxy_protocol {
+                let (Some(src), Some(dst)) = (peer, local) else {
+                    return Err("missing address for PROXY protocol header".to_string());
+                };
+                upstream
+                    .write_all(&encode_header(version, src, dst))
+                    .await
+                    .map_err(|err| format!("send PROXY header failed: {}", err))?;
+            }
+            // PROXY protocol 探测与 ClientHello 读取消费的字节属于客户端数据，原样转发
+            if !preface.is_empty() {
+                upstream
+                    .write_all(&preface)
+                    .await
+                    .map_err(|err| format!("forward preface failed: {}", err))?;
+            }
+
+            crate::metrics::tcp_active_inc();
+            let copied = forward(&mut downstream, &mut upstream, &target, shutdown).await;
+            crate::metrics::tcp_active_dec();
+            Ok::<_, String>(copied)
+        }
+        .await;
+
+        target.release();
+
+        let peer = peer.map(|addr| addr.to_string()).unwrap_or_default();
+        match result {
+            Ok(copied) => {
+                crate::metrics::inc_tcp_connection("ok");
+                let (sent, received) = copied.unwrap_or_default();
+                info!(
+                    listener_id = %listener.id,
+                    peer = %peer,
+                    upstream = %address,
+                    sni = sni.as_deref().unwrap_or(""),
+                    sent,
+                    received,
+                    elapsed_ms = start.elapsed().as_millis() as u64,
+                    "tcp access"
+                );
+            }
+            Err(err) => {
+                crate::metrics::inc_tcp_connection("upstream_error");
+                warn!(listener_id = %listener.id, peer = %peer, "tcp proxy error: {}", err);
+            }
+        }
+    }
+}
+
+/// 双向拷贝字节流，直到任一方向结束、进程关闭或目标摘流超时；后两种情况返回 `None`
+async fn forward(
+    downstream: &mut Stream,
+    upstream: &mut Box<dyn UpstreamStream>,
+    target: &TargetRuntime,
+    shutdown: &ShutdownWatch,
+) -> Option<(u64, u64)> {
+    let mut shutdown = shutdown.clone();
+    tokio::select! {
+        copied = copy_bidirectional(downstream, upstream) => copied.ok(),
+        _ = shutdown.changed() => {
+            debug!("tcp connection closed by shutdown");
+            None
+        }
+        _ = drain_expired(target) => {
+            debug!("tcp connection to {} closed by drain timeout", target.address());
+            None
+        }
+    }
+}
+
+/// 等到目标摘流截止：截止时间在摘流开始时才确定，之前按间隔检查
+async fn drain_expired(target: &TargetRuntime) {
+    loop {
+        if let Some(deadline) = target.drain_deadline() {
+            tokio::time::sleep_until(deadline.into()).await;
+            return;
+        }
+        tokio::time::sleep(DRAIN_CHECK_INTERVAL).await;
+    }
+}
+
+/// 上游连接：TCP 或 unix 域套接字
+pub trait UpstreamStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> UpstreamStream for T {}
+
+/// 按目标地址（`host:port` 或 `unix:/path`）建立上游连接
+pub async fn connect_upstream(address: &str) -> std::io::Result<Box<dyn UpstreamStream>> {
+    match unix_socket_path(address) {
+        Some(path) => Ok(Box::new(UnixStream::connect(path).await?)),
+        None => Ok(Box::new(TcpStream::connect(address).await?)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pingora::protocols::l4::stream::Stream as L4Stream;
+    use tokio::net::TcpListener;
+
+    async fn tcp_pair() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (accepted, _) = listener.accept().await.unwrap();
+        (client, accepted)
+    }
+
+    #[tokio::test]
+    async fn test_drained_target_closes_connection() {
+        let (_client, accepted) = tcp_pair().await;
+        let (upstream, _server) = tcp_pair().await;
+        let mut downstream: Stream = Box::new(L4Stream::from(accepted));
+        let mut upstream: Box<dyn UpstreamStream> = Box::new(upstream);
+        let (_tx, shutdown) = tokio::sync::watch::channel(false);
+
+        let target = TargetRuntime::new(uuid::Uuid::new_v4(), "127.0.0.1:1".into(), 1, None);
+        target.start_drain(Instant::now() + Duration::from_millis(50));
+        let copied = timeout(
+            Duration::from_secs(2),
+            forward(&mut downstream, &mut upstream, &target, &shutdown),
+        )
+        .await
+        .expect("摘流超时后连接应被关闭");
+        assert_eq!(copied, None, "两端仍未关闭，由摘流截止中断");
+    }
+
+    #[tokio::test]
+    async fn test_drain_started_during_connection() {
+        let (_client, accepted) = tcp_pair().await;
+        let (upstream, _server) = tcp_pair().await;
+        let mut downstream: Stream = Box::new(L4Stream::from(accepted));
+        let mut upstream: Box<dyn UpstreamStream> = Box::new(upstream);
+        let (_tx, shutdown) = tokio::sync::watch::channel(false);
+
+        let target = TargetRuntime::new(uuid::Uuid::new_v4(), "127.0.0.1:1".into(), 1, None);
+        let forwarding = forward(&mut downstream, &mut upstream, &target, &shutdown);
+        tokio::pin!(forwarding);
+        assert!(
+            timeout(Duration::from_millis(100), &mut forwarding)
+                .await
+                .is_err(),
+            "未摘流的目标继续转发"
+        );
+        target.start_drain(Instant::now());
+        let copied = timeout(DRAIN_CHECK_INTERVAL * 3, forwarding)
+            .await
+            .expect("转发开始后才摘流的连接同样被关闭");
+        assert_eq!(copied, None);
+    }
+}
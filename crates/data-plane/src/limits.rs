@@ -0,0 +1,190 @@
+use gateway_common::models::RequestLimitsOptions;
+use pingora::http::RequestHeader;
+
+/// 监听器或路由的请求大小限制（运行时）
+#[derive(Clone, Copy, Default, Debug)]
+pub struct RequestLimits {
+    pub max_body_bytes: Option<u64>,
+    max_header_bytes: Option<usize>,
+    max_header_count: Option<usize>,
+    max_uri_length: Option<usize>,
+}
+
+impl RequestLimits {
+    pub fn from_options(options: Option<&RequestLimitsOptions>) -> Self {
+        let Some(options) = options else {
+            return Self::default();
+        };
+        let positive = |value: Option<u32>| value.filter(|n| *n > 0).map(|n| n as usize);
+        Self {
+            max_body_bytes: options.max_body_bytes.filter(|n| *n > 0),
+            max_header_bytes: positive(options.max_header_bytes),
+            max_header_count: positive(options.max_header_count),
+            max_uri_length: positive(options.max_uri_length),
+        }
+    }
+
+    /// 检查请求目标、请求头与 Content-Length，超限时返回应响应的状态码
+    pub fn check_header(&self, header: &RequestHeader) -> Option<u16> {
+        if let Some(max) = self.max_uri_length
+            && header.raw_path().len() > max
+        {
+            return Some(414);
+        }
+        if let Some(max) = self.max_header_count
+            && header.headers.len() > max
+        {
+            return Some(431);
+        }
+        if let Some(max) = self.max_header_bytes {
+            let total: usize = header
+                .headers
+                .iter()
+                .map(|(name, value)| name.as_str().len() + value.len())
+                .sum();
+            if total > max {
+                return Some(431);
+            }
+        }
+        if let Some(max) = self.max_body_bytes
+            && content_length(header).is_some_and(|len| len > max)
+        {
+            return Some(413);
+        }
+        None
+    }
+}
+
+/// 取两个请求体上限中较严格者
+pub fn tighter(a: Option<u64>, b: Option<u64>) -> Option<u64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// 累计转发过程中收到的请求体字节数，超过上限时返回 true；未设置上限时不计数
+pub fn body_exceeded(received: &mut u64, chunk: usize, max: Option<u64>) -> bool {
+    let Some(max) = max else {
+        return false;
+    };
+    *received += chunk as u64;
+    *received > max
+}
+
+fn content_length(header: &RequestHeader) -> Option<u64> {
+    header
+        .headers
+        .get("content-length")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(options: RequestLimitsOptions) -> RequestLimits {
+        RequestLimits::from_options(Some(&options))
+    }
+
+    fn request(path: &str, headers: &[(&str, &str)]) -> RequestHeader {
+        let mut header = RequestHeader::build("POST", path.as_bytes(), None).unwrap();
+        for (name, value) in headers {
+            header.append_header(name.to_string(), *value).unwrap();
+        }
+        header
+    }
+
+    #[test]
+    fn test_uri_length_boundary() {
+        let limits = limits(RequestLimitsOptions {
+            max_uri_length: Some(10),
+            ..Default::default()
+        });
+        assert_eq!(limits.check_header(&request("/123456789", &[])), None);
+        assert_eq!(limits.check_header(&request("/123456789?", &[])), Some(414));
+    }
+
+    #[test]
+    fn test_header_boundary() {
+        let count = limits(RequestLimitsOptions {
+            max_header_count: Some(2),
+            ..Default::default()
+        });
+        assert_eq!(
+            count.check_header(&request("/", &[("a", "1"), ("b", "2")])),
+            None
+        );
+        assert_eq!(
+            count.check_header(&request("/", &[("a", "1"), ("b", "2"), ("c", "3")])),
+            Some(431)
+        );
+
+        // 按名称与取值的字节数合计
+        let bytes = limits(RequestLimitsOptions {
+            max_header_bytes: Some(10),
+            ..Default::default()
+        });
+        assert_eq!(
+            bytes.check_header(&request("/", &[("x-a", "1234567")])),
+            None
+        );
+        assert_eq!(
+            bytes.check_header(&request("/", &[("x-a", "12345678")])),
+            Some(431)
+        );
+    }
+
+    #[test]
+    fn test_body_boundary() {
+        let limits = limits(RequestLimitsOptions {
+            max_body_bytes: Some(100),
+            ..Default::default()
+        });
+        assert_eq!(
+            limits.check_header(&request("/", &[("content-length", "100")])),
+            None
+        );
+        assert_eq!(
+            limits.check_header(&request("/", &[("content-length", "101")])),
+            Some(413)
+        );
+        assert_eq!(
+            limits.check_header(&request("/", &[("content-length", "abc")])),
+            None,
+            "无法解析的长度留给转发时累计"
+        );
+
+        let mut received = 0;
+        assert!(!body_exceeded(&mut received, 60, limits.max_body_bytes));
+        assert!(!body_exceeded(&mut received, 40, limits.max_body_bytes));
+        assert!(body_exceeded(&mut received, 1, limits.max_body_bytes));
+
+        let mut received = 0;
+        assert!(!body_exceeded(&mut received, 1 << 20, None));
+        assert_eq!(received, 0, "未设置上限时不计数");
+    }
+
+    #[test]
+    fn test_zero_means_unlimited() {
+        let limits = limits(RequestLimitsOptions {
+            max_body_bytes: Some(0),
+            max_uri_length: Some(0),
+            ..Default::default()
+        });
+        assert_eq!(limits.max_body_bytes, None);
+        assert_eq!(
+            limits.check_header(&request("/long/path", &[("content-length", "5")])),
+            None
+        );
+    }
+
+    #[test]
+    fn test_tighter() {
+        assert_eq!(tighter(Some(10), Some(5)), Some(5));
+        assert_eq!(tighter(None, Some(5)), Some(5));
+        assert_eq!(tighter(Some(10), None), Some(10));
+        assert_eq!(tighter(None, None), None);
+    }
+}
@@ -1,16 +1,33 @@
+mod app;
+mod discovery;
+mod dns;
+mod drain;
+mod error_page;
+mod ext_auth;
+mod fault;
+mod forwarded;
+mod grpc;
 mod health;
+mod keepalive;
+mod l4;
+mod limits;
 mod metrics;
 mod node;
 mod proxy;
+mod proxy_protocol;
+mod route_index;
+mod security_headers;
+mod sni;
 mod tls;
+mod value_match;
+mod ws;
 
 use anyhow::Result;
+use app::GatewayApp;
+use arc_swap::ArcSwap;
 use gateway_common::config::AppConfig;
 use gateway_common::snapshot::Snapshot;
 use gateway_common::state::SnapshotStore;
-use pingora::listeners::TlsAcceptCallbacks;
-use pingora::listeners::tls::TlsSettings;
-use pingora::proxy::http_proxy_service;
 use pingora::server::Server;
 use pingora::services::listening::Service as ListeningService;
 use proxy::AcmeChallengeClient;
@@ -34,8 +51,9 @@ async fn main() -> Result<()> {
 
     let default_tls_pem = Arc::new(tls::default_tls_pem(&config.certs_dir)?);
 
-    let runtime = Arc::new(RwLock::new(proxy::build_runtime(
+    let runtime: proxy::SharedRuntime = Arc::new(ArcSwap::from_pointee(proxy::build_runtime(
         &snapshot,
+        None,
         &default_tls_pem,
         http_port_range,
         https_port_range,
@@ -53,9 +71,7 @@ async fn main() -> Result<()> {
                 &default_tls_pem_for_updates,
                 http_port_range_for_updates,
                 https_port_range_for_updates,
-            )
-            .await
-            {
+            ) {
                 warn!("failed to apply snapshot: {}", err);
             }
         }
@@ -79,19 +95,26 @@ async fn main() -> Result<()> {
         health::run_health_checks(runtime_for_health, health_interval, health_timeout).await;
     }));
 
+    let runtime_for_discovery = runtime.clone();
+    tasks.push(tokio::spawn(async move {
+        discovery::run_discovery(runtime_for_discovery).await;
+    }));
+
     let mut server = Server::new(None)?;
     server.bootstrap();
 
     let acme_client = AcmeChallengeClient::new(config.control_plane_url.clone());
 
     let router = proxy::ProxyRouter::new(runtime.clone(), Some(acme_client.clone()));
-    let mut service = http_proxy_service(&server.configuration, router);
+    // 连接入口：解析 PROXY protocol 头（https 端口随后握手）后分发到 L4 转发或 HTTP 代理。
+    // 所有端口都以明文 TCP 监听，TLS 由入口在 PROXY 头之后终止
+    let mut app = GatewayApp::new(server.configuration.clone(), router, runtime.clone());
+    let mut addrs = Vec::new();
 
     // 绑定端口范围预绑定
     if let Some(range) = http_port_range {
         for port in range.iter() {
-            let addr = format!("0.0.0.0:{}", port);
-            service.add_tcp(&addr);
+            addrs.push(format!("0.0.0.0:{}", port));
         }
         info!(
             "data plane pre-bound HTTP ports {}-{}",
@@ -101,11 +124,8 @@ async fn main() -> Result<()> {
 
     if let Some(range) = https_port_range {
         for port in range.iter() {
-            let addr = format!("0.0.0.0:{}", port);
-            let callbacks: TlsAcceptCallbacks =
-                Box::new(proxy::PortTlsSelector::new(port, runtime.clone()));
-            let settings = TlsSettings::with_callbacks(callbacks)?;
-            service.add_tls_with_settings(&addr, None, settings);
+            app.add_tls_port(port)?;
+            addrs.push(format!("0.0.0.0:{}", port));
         }
         info!(
             "data plane pre-bound HTTPS ports {}-{}",
@@ -115,25 +135,25 @@ async fn main() -> Result<()> {
 
     // 如果未使用端口范围预绑定，则从快照中绑定监听器
     if http_port_range.is_none() && https_port_range.is_none() {
-        let listeners = runtime.read().await.listeners.clone();
+        let listeners = runtime.load().listeners.clone();
         if listeners.is_empty() {
             warn!("no listeners configured at startup; restart required after publish");
         }
         for listener in listeners {
             let addr = format!("0.0.0.0:{}", listener.port);
             if listener.protocol.eq_ignore_ascii_case("https") {
-                let port = listener.port as u16;
-                let callbacks: TlsAcceptCallbacks =
-                    Box::new(proxy::PortTlsSelector::new(port, runtime.clone()));
-                let settings = TlsSettings::with_callbacks(callbacks)?;
-                service.add_tls_with_settings(&addr, None, settings);
-            } else {
-                service.add_tcp(&addr);
+                app.add_tls_port(listener.port as u16)?;
             }
             info!("data plane listening on {} ({})", addr, listener.protocol);
+            addrs.push(addr);
         }
     }
 
+    let mut service = ListeningService::new("gateway proxy".to_string(), app);
+    for addr in &addrs {
+        service.add_tcp(addr);
+    }
+
     server.add_service(service);
 
     let mut metrics_service = ListeningService::prometheus_http_service();
@@ -38,7 +38,7 @@ fn inflight_requests() -> &'static MetricRegistration<IntGauge> {
     METRIC.get_or_init(|| {
         register_int_gauge!(
             format!("{METRIC_PREFIX}_inflight_requests"),
-            "数据平面当前进行中的请求数（包含 WS 存量连接）"
+            "数据平面当前进行中的请求数（不含已升级的 WS 连接）"
         )
     })
 }
@@ -69,6 +69,107 @@ fn upstream_target_healthy() -> &'static MetricRegistration<IntGaugeVec> {
     })
 }
 
+fn grpc_responses_total() -> &'static MetricRegistration<IntCounterVec> {
+    static METRIC: OnceLock<MetricRegistration<IntCounterVec>> = OnceLock::new();
+    METRIC.get_or_init(|| {
+        register_int_counter_vec!(
+            Opts::new(
+                format!("{METRIC_PREFIX}_grpc_responses_total"),
+                "数据平面 gRPC 响应总数（按 grpc-status）"
+            ),
+            &["code"]
+        )
+    })
+}
+
+fn tcp_connections_total() -> &'static MetricRegistration<IntCounterVec> {
+    static METRIC: OnceLock<MetricRegistration<IntCounterVec>> = OnceLock::new();
+    METRIC.get_or_init(|| {
+        register_int_counter_vec!(
+            Opts::new(
+                format!("{METRIC_PREFIX}_tcp_connections_total"),
+                "数据平面 L4（tcp）连接总数"
+            ),
+            &["result"]
+        )
+    })
+}
+
+fn tcp_active_connections() -> &'static MetricRegistration<IntGauge> {
+    static METRIC: OnceLock<MetricRegistration<IntGauge>> = OnceLock::new();
+    METRIC.get_or_init(|| {
+        register_int_gauge!(
+            format!("{METRIC_PREFIX}_tcp_active_connections"),
+            "数据平面当前 L4（tcp）转发中的连接数"
+        )
+    })
+}
+
+fn ws_active_connections() -> &'static MetricRegistration<IntGaugeVec> {
+    static METRIC: OnceLock<MetricRegistration<IntGaugeVec>> = OnceLock::new();
+    METRIC.get_or_init(|| {
+        register_int_gauge_vec!(
+            Opts::new(
+                format!("{METRIC_PREFIX}_ws_active_connections"),
+                "数据平面当前 WS 连接数（按路由，含升级握手中的连接）"
+            ),
+            &["route_id"]
+        )
+    })
+}
+
+fn upstream_discovered_targets() -> &'static MetricRegistration<IntGaugeVec> {
+    static METRIC: OnceLock<MetricRegistration<IntGaugeVec>> = OnceLock::new();
+    METRIC.get_or_init(|| {
+        register_int_gauge_vec!(
+            Opts::new(
+                format!("{METRIC_PREFIX}_upstream_discovered_targets"),
+                "服务发现解析得到的上游目标数（按池）"
+            ),
+            &["pool_id"]
+        )
+    })
+}
+
+fn upstream_connections_total() -> &'static MetricRegistration<IntCounterVec> {
+    static METRIC: OnceLock<MetricRegistration<IntCounterVec>> = OnceLock::new();
+    METRIC.get_or_init(|| {
+        register_int_counter_vec!(
+            Opts::new(
+                format!("{METRIC_PREFIX}_upstream_connections_total"),
+                "HTTP 请求使用的上游连接总数（reused=true 为复用空闲连接，false 为新建连接）"
+            ),
+            &["pool_id", "address", "reused"]
+        )
+    })
+}
+
+fn fault_injected_total() -> &'static MetricRegistration<IntCounterVec> {
+    static METRIC: OnceLock<MetricRegistration<IntCounterVec>> = OnceLock::new();
+    METRIC.get_or_init(|| {
+        register_int_counter_vec!(
+            Opts::new(
+                format!("{METRIC_PREFIX}_fault_injected_total"),
+                "路由故障注入次数（kind=delay|abort|reset）"
+            ),
+            &["route_id", "kind"]
+        )
+    })
+}
+
+fn ext_auth_total() -> &'static MetricRegistration<IntCounterVec> {
+    static METRIC: OnceLock<MetricRegistration<IntCounterVec>> = OnceLock::new();
+    METRIC.get_or_init(|| {
+        register_int_counter_vec!(
+            Opts::new(
+                format!("{METRIC_PREFIX}_ext_auth_total"),
+                "外部授权结果（result=allow|deny|error，cached=是否命中缓存）"
+            ),
+            &["route_id", "result", "cached"]
+        )
+    })
+}
+
 pub fn observe_request(method: &str, status: u16, seconds: f64) {
     let status = status.to_string();
     if let Ok(counter) = requests_total() {
@@ -105,3 +206,65 @@ pub fn set_target_health(pool_id: &str, address: &str, healthy: bool) {
         gauge.with_label_values(&[pool_id, address]).set(value);
     }
 }
+
+pub fn inc_grpc_response(code: &str) {
+    if let Ok(counter) = grpc_responses_total() {
+        counter.with_label_values(&[code]).inc();
+    }
+}
+
+pub fn inc_tcp_connection(result: &str) {
+    if let Ok(counter) = tcp_connections_total() {
+        counter.with_label_values(&[result]).inc();
+    }
+}
+
+pub fn tcp_active_inc() {
+    if let Ok(gauge) = tcp_active_connections() {
+        gauge.inc();
+    }
+}
+
+pub fn tcp_active_dec() {
+    if let Ok(gauge) = tcp_active_connections() {
+        gauge.dec();
+    }
+}
+
+pub fn ws_active_inc(route_id: &str) {
+    if let Ok(gauge) = ws_active_connections() {
+        gauge.with_label_values(&[route_id]).inc();
+    }
+}
+
+pub fn ws_active_dec(route_id: &str) {
+    if let Ok(gauge) = ws_active_connections() {
+        gauge.with_label_values(&[route_id]).dec();
+    }
+}
+
+pub fn set_discovered_targets(pool_id: &str, count: usize) {
+    if let Ok(gauge) = upstream_discovered_targets() {
+        gauge.with_label_values(&[pool_id]).set(count as i64);
+    }
+}
+
+pub fn inc_upstream_connection(pool_id: &str, address: &str, reused: bool) {
+    if let Ok(counter) = upstream_connections_total() {
+        let reused = if reused { "true" } else { "false" };
+        counter.with_label_values(&[pool_id, address, reused]).inc();
+    }
+}
+
+pub fn inc_fault_injected(route_id: &str, kind: &str) {
+    if let Ok(counter) = fault_injected_total() {
+        counter.with_label_values(&[route_id, kind]).inc();
+    }
+}
+
+pub fn inc_ext_auth(route_id: &str, result: &str, cached: bool) {
+    if let Ok(counter) = ext_auth_total() {
+        let cached = if cached { "true" } else { "false" };
+        counter.with_label_values(&[route_id, result, cached]).inc();
+    }
+}
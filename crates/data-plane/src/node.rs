@@ -1,4 +1,5 @@
 use gateway_common::config::AppConfig;
+use gateway_common::models::NodeMetadata;
 use gateway_common::snapshot::PublishedSnapshotResponse;
 use gateway_common::state::SnapshotStore;
 use reqwest::Client;
@@ -64,10 +65,13 @@ pub async fn start_node_tasks(config: AppConfig, snapshots: SnapshotStore, runti
             let url = format!("{}/api/v1/nodes/heartbeat", base);
             loop {
                 let version_id = *runtime.current_version.read().await;
+                let metadata = NodeMetadata {
+                    draining_targets: crate::drain::report(),
+                };
                 let payload = serde_json::json!({
                     "node_id": node_id,
                     "version_id": version_id,
-                    "metadata": null
+                    "metadata": metadata
                 });
                 if let Err(err) = client.post(&url).json(&payload).send().await {
                     warn!("heartbeat error: {}", err);
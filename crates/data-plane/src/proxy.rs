@@ -1,50 +1,213 @@
+use crate::discovery::{Discovery, DiscoverySource};
+use crate::error_page::ErrorPages;
+use crate::ext_auth::{AuthRequest, Decision, DeniedResponse, ExtAuthPolicy};
+use crate::fault::{FaultAbort, FaultInjection};
+use crate::forwarded::{ForwardedConfig, ForwardedHop};
+use crate::keepalive::KeepaliveSettings;
+use crate::limits::{RequestLimits, tighter};
+use crate::proxy_protocol::{
+    ProxyHeaderConnect, ProxyProtocolConfig, ProxyProtocolMode, ProxyProtocolVersion, encode_header,
+};
+use crate::route_index::RouteIndex;
+use crate::security_headers::SecurityHeaders;
 use crate::tls::TlsKeyPairPem;
+use crate::value_match::ValueMatcher;
+use crate::ws::{WsGuard, WsSettings};
 use anyhow::Result;
+use arc_swap::ArcSwap;
 use async_trait::async_trait;
 use bytes::Bytes;
 use gateway_common::config::PortRange;
-use gateway_common::entities::upstream_targets::Model as UpstreamTarget;
-use gateway_common::models::RouteMatch;
+use gateway_common::entities::listeners::Model as Listener;
+use gateway_common::entities::routes::Model as Route;
+use gateway_common::entities::upstream_pools::Model as UpstreamPool;
+use gateway_common::grpc::code_name;
+use gateway_common::models::{
+    DEFAULT_REQUEST_ID_HEADER, Http2Options, ListenerOptions, MaintenanceOptions, PoolOptions,
+    RequestIdOptions, RouteMatch, RouteOptions, ValueMatch,
+};
+use gateway_common::net::{IpCidr, contains_ip, host_matches, normalize_host, unix_socket_path};
 use gateway_common::snapshot::Snapshot;
+use pingora::ErrorSource;
 use pingora::http::RequestHeader;
 use pingora::http::ResponseHeader;
+use pingora::http::StatusCode;
 use pingora::listeners::TlsAccept;
 use pingora::prelude::*;
+use pingora::protocols::ALPN;
 use pingora::protocols::tls::TlsRef;
-use pingora::proxy::ProxyHttp;
+use pingora::proxy::{FailToProxy, ProxyHttp};
+use pingora::tls::ssl::{AlpnError, SslVerifyMode, select_next_proto};
+use pingora::tls::x509::X509;
+use pingora::tls::x509::store::X509StoreBuilder;
 use regex::Regex;
 use reqwest::Client;
 use serde::Deserialize;
 use serde_json::Value as JsonValue;
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::net::{IpAddr, SocketAddr};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::time::Instant;
-use tokio::sync::RwLock;
-use tracing::{debug, warn};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
 use uuid::Uuid;
 
+/// 运行时配置的共享句柄：发布时整体替换，读取方无锁获取当前配置的快照
+pub type SharedRuntime = Arc<ArcSwap<RuntimeConfig>>;
+
 #[derive(Clone)]
 pub struct ProxyRouter {
-    runtime: Arc<RwLock<RuntimeConfig>>,
+    runtime: SharedRuntime,
     acme_client: Option<AcmeChallengeClient>,
 }
 
 pub struct RequestCtx {
     start: Instant,
+    /// 请求开始时的运行时配置，整个请求期间不受后续发布影响
+    runtime: Arc<RuntimeConfig>,
     target: Option<Arc<TargetRuntime>>,
+    request_id: String,
+    request_id_header: String,
+    listener: Option<Arc<ListenerRuntime>>,
+    client_ip: Option<IpAddr>,
+    client_cert_subject: Option<String>,
+    peer_addr: Option<SocketAddr>,
+    pool: Option<Arc<PoolSettings>>,
+    route_error_pages: Option<Arc<ErrorPages>>,
+    retry_after: Option<u32>,
+    max_body_bytes: Option<u64>,
+    body_bytes: u64,
+    ws: Option<WsGuard>,
+    /// 已完成 WebSocket 升级，不再计入进行中的请求
+    upgraded: bool,
+    grpc: bool,
+    grpc_status: Option<u32>,
+    /// 已转发的请求体中的 gRPC 消息数
+    grpc_messages: crate::grpc::MessageCounter,
+    retries: u32,
+    /// 当前上游连接已承载的请求数（含本次），0 表示尚未取得连接
+    upstream_requests: u64,
+    /// 本次请求结束后上游连接将被关闭，不归还到连接池
+    upstream_close: bool,
+    /// 已对本次请求抽样过故障注入，重试时不再注入
+    fault_sampled: bool,
+    /// 生效的安全响应头：先取监听器配置，匹配路由后改为路由的配置
+    security_headers: Option<Arc<SecurityHeaders>>,
 }
 
 impl ProxyRouter {
-    pub fn new(
-        runtime: Arc<RwLock<RuntimeConfig>>,
-        acme_client: Option<AcmeChallengeClient>,
-    ) -> Self {
+    pub fn new(runtime: SharedRuntime, acme_client: Option<AcmeChallengeClient>) -> Self {
         Self {
             runtime,
             acme_client,
         }
     }
+
+    /// 直连对端地址：连接携带 PROXY protocol 头时，连接入口已将其中的源地址写入连接摘要
+    fn peer_addr(&self, session: &Session) -> Option<SocketAddr> {
+        session.client_addr()?.as_inet().copied()
+    }
+
+    /// 请求命中的维护配置：先看监听器，再看最先匹配的路由；客户端在放行网段内时返回 None
+    fn maintenance(
+        &self,
+        header: &RequestHeader,
+        ctx: &mut RequestCtx,
+    ) -> Option<Arc<MaintenanceConfig>> {
+        let listener = ctx.listener.clone()?;
+        if let Some(maintenance) = &listener.maintenance
+            && maintenance.applies(ctx.client_ip)
+        {
+            return Some(maintenance.clone());
+        }
+        let runtime = ctx.runtime.clone();
+        let routes = runtime
+            .routes_by_listener
+            .get(&listener.id)
+            .filter(|routes| routes.has_maintenance())?;
+        let route = routes
+            .matching(header, MatchInput::new(header, ctx))
+            .next()?;
+        let maintenance = route.maintenance.clone()?;
+        if !maintenance.applies(ctx.client_ip) {
+            return None;
+        }
+        ctx.route_error_pages = route.error_pages.clone();
+        ctx.security_headers = route.security_headers.clone();
+        Some(maintenance)
+    }
+
+    /// 按优先级最先匹配的路由配置的外部授权
+    fn ext_auth(
+        &self,
+        header: &RequestHeader,
+        ctx: &mut RequestCtx,
+    ) -> Option<(Uuid, Arc<ExtAuthPolicy>)> {
+        let listener = ctx.listener.clone()?;
+        let runtime = ctx.runtime.clone();
+        let routes = runtime
+            .routes_by_listener
+            .get(&listener.id)
+            .filter(|routes| routes.has_ext_auth())?;
+        let route = routes
+            .matching(header, MatchInput::new(header, ctx))
+            .next()?;
+        let ext_auth = route.ext_auth.clone()?;
+        ctx.route_error_pages = route.error_pages.clone();
+        ctx.security_headers = route.security_headers.clone();
+        Some((route.id, ext_auth))
+    }
+
+    /// 执行外部授权：放行时把授权响应头写入请求并返回 false，否则写出响应并返回 true
+    async fn authorize(
+        &self,
+        session: &mut Session,
+        ctx: &mut RequestCtx,
+    ) -> Result<bool, Box<pingora::Error>> {
+        let Some((route_id, policy)) = self.ext_auth(session.req_header(), ctx) else {
+            return Ok(false);
+        };
+        let is_https = ctx
+            .listener
+            .as_ref()
+            .is_some_and(|listener| listener.protocol.eq_ignore_ascii_case("https"));
+        let header = session.req_header();
+        let request = AuthRequest {
+            header,
+            client_ip: ctx.client_ip,
+            proto: if is_https { "https" } else { "http" },
+            host: request_host(header),
+        };
+        let route_label = route_id.to_string();
+        let (decision, cached) = match policy.check(route_id, &ctx.runtime, &request).await {
+            Ok(result) => result,
+            Err(err) => {
+                warn!(request_id = %ctx.request_id, "ext_auth for route {} failed: {:#}", route_id, err);
+                crate::metrics::inc_ext_auth(&route_label, "error", false);
+                write_error_response(session, ctx, 503).await?;
+                return Ok(true);
+            }
+        };
+        match decision.as_ref() {
+            Decision::Allow(headers) => {
+                crate::metrics::inc_ext_auth(&route_label, "allow", cached);
+                let req = session.req_header_mut();
+                for name in policy.response_headers() {
+                    req.remove_header(name);
+                }
+                for (name, value) in headers {
+                    req.append_header(name.clone(), value.clone())?;
+                }
+                Ok(false)
+            }
+            Decision::Deny(denied) => {
+                crate::metrics::inc_ext_auth(&route_label, "deny", cached);
+                write_denied_response(session, ctx, denied).await?;
+                Ok(true)
+            }
+        }
+    }
 }
 
 #[async_trait]
@@ -54,7 +217,29 @@ impl ProxyHttp for ProxyRouter {
     fn new_ctx(&self) -> Self::CTX {
         RequestCtx {
             start: Instant::now(),
+            runtime: self.runtime.load_full(),
             target: None,
+            request_id: String::new(),
+            request_id_header: DEFAULT_REQUEST_ID_HEADER.to_string(),
+            listener: None,
+            client_ip: None,
+            client_cert_subject: None,
+            peer_addr: None,
+            pool: None,
+            route_error_pages: None,
+            retry_after: None,
+            max_body_bytes: None,
+            body_bytes: 0,
+            ws: None,
+            upgraded: false,
+            grpc: false,
+            grpc_status: None,
+            grpc_messages: Default::default(),
+            retries: 0,
+            upstream_requests: 0,
+            upstream_close: false,
+            fault_sampled: false,
+            security_headers: None,
         }
     }
 
@@ -66,35 +251,97 @@ impl ProxyHttp for ProxyRouter {
         ctx.start = Instant::now();
         crate::metrics::inflight_inc();
 
+        ctx.listener = downstream_port(session)
+            .and_then(|port| ctx.runtime.listeners_by_port.get(&port).cloned());
+        ctx.security_headers = ctx
+            .listener
+            .as_ref()
+            .and_then(|listener| listener.security_headers.clone());
+        ctx.peer_addr = self.peer_addr(session);
+        let peer_ip = ctx.peer_addr.map(|addr| addr.ip());
+        let request_id = ctx
+            .listener
+            .as_ref()
+            .map(|listener| listener.request_id.clone())
+            .unwrap_or_default();
+        ctx.request_id = request_id.resolve(session.req_header(), peer_ip);
+        ctx.request_id_header = request_id.header;
+        ctx.client_ip = match &ctx.listener {
+            Some(listener) => listener.forwarded.client_ip(session.req_header(), peer_ip),
+            None => peer_ip,
+        };
+        ctx.client_cert_subject = session
+            .digest()
+            .and_then(|digest| digest.ssl_digest.as_ref())
+            .and_then(|ssl| crate::tls::client_cert_subject(&ssl.cert_digest));
+        ctx.grpc = crate::grpc::is_grpc_request(session.req_header());
+        let limits = ctx
+            .listener
+            .as_ref()
+            .map(|listener| listener.limits)
+            .unwrap_or_default();
+        ctx.max_body_bytes = limits.max_body_bytes;
+        if let Some(status) = limits.check_header(session.req_header()) {
+            write_error_response(session, ctx, status).await?;
+            return Ok(true);
+        }
+        // 写回请求头，随请求一并转发给上游
+        session
+            .req_header_mut()
+            .insert_header(ctx.request_id_header.clone(), ctx.request_id.as_str())?;
+
         let path = session.req_header().uri.path();
         if let Some(token) = acme_token_from_path(path)
             && let Some(client) = &self.acme_client
         {
             if let Some(key_auth) = client.fetch(&token).await {
-                let mut header = ResponseHeader::build(200, Some(4)).map_err(|_| {
-                    Error::explain(ErrorType::HTTPStatus(500), "failed to build response")
-                })?;
                 let body = Bytes::from(key_auth);
-                header
-                    .insert_header("content-type", "text/plain")
-                    .map_err(|_| {
-                        Error::explain(ErrorType::HTTPStatus(500), "failed to set content-type")
-                    })?;
-                header
-                    .insert_header("content-length", body.len().to_string())
-                    .map_err(|_| {
-                        Error::explain(ErrorType::HTTPStatus(500), "failed to set content-length")
-                    })?;
-                session
-                    .write_response_header(Box::new(header), false)
-                    .await?;
-                session.write_response_body(Some(body), true).await?;
+                write_local_response(session, ctx, 200, "text/plain", body).await?;
                 return Ok(true);
             }
-            session.respond_error(404).await?;
+            write_error_response(session, ctx, 404).await?;
+            return Ok(true);
+        }
+
+        if let Some(maintenance) = self.maintenance(session.req_header(), ctx) {
+            write_maintenance_response(session, ctx, &maintenance).await?;
             return Ok(true);
         }
-        Ok(false)
+        self.authorize(session, ctx).await
+    }
+
+    async fn request_body_filter(
+        &self,
+        _session: &mut Session,
+        body: &mut Option<Bytes>,
+        _end_of_stream: bool,
+        ctx: &mut Self::CTX,
+    ) -> Result<(), Box<pingora::Error>>
+    where
+        Self::CTX: Send + Sync,
+    {
+        if ctx.upgraded {
+            // 升级后的数据为 WebSocket 帧，不受请求体大小限制
+            if let Some(ws) = &ctx.ws {
+                ws.on_request_body(body);
+            }
+            return Ok(());
+        }
+        if ctx.grpc
+            && let Some(chunk) = body.as_ref()
+        {
+            ctx.grpc_messages.feed(chunk);
+        }
+        // 分块传输或 Content-Length 不可信时，在转发过程中累计请求体大小
+        if let Some(chunk) = body.as_ref()
+            && crate::limits::body_exceeded(&mut ctx.body_bytes, chunk.len(), ctx.max_body_bytes)
+        {
+            return Err(Error::explain(
+                ErrorType::HTTPStatus(413),
+                "request body too large",
+            ));
+        }
+        check_drain(ctx)
     }
 
     async fn upstream_peer(
@@ -103,14 +350,10 @@ impl ProxyHttp for ProxyRouter {
         ctx: &mut Self::CTX,
     ) -> Result<Box<HttpPeer>, Box<pingora::Error>> {
         let header = session.req_header();
-        let port = session
-            .as_downstream()
-            .server_addr()
-            .and_then(|addr| addr.as_inet().map(|inet| inet.port()))
-            .ok_or_else(|| {
-                Error::explain(ErrorType::InternalError, "missing downstream server_addr")
-            })?;
-        let runtime = self.runtime.read().await;
+        let port = downstream_port(session).ok_or_else(|| {
+            Error::explain(ErrorType::InternalError, "missing downstream server_addr")
+        })?;
+        let runtime = ctx.runtime.clone();
         let listener = match runtime.listeners_by_port.get(&port) {
             Some(listener) => listener,
             None => return Err(Error::explain(ErrorType::HTTPStatus(404), "no listener")),
@@ -120,19 +363,59 @@ impl ProxyHttp for ProxyRouter {
             None => return Err(Error::explain(ErrorType::HTTPStatus(404), "no routes")),
         };
 
-        for route in routes {
-            if !route.matches(header) {
-                continue;
+        let mut first_matched = None;
+        for route in routes.matching(header, MatchInput::new(header, ctx)) {
+            first_matched.get_or_insert(route);
+            // 故障注入先于选取目标：延迟期间不占用目标的进行中计数
+            if let Some(fault) = &route.fault
+                && !ctx.fault_sampled
+            {
+                ctx.fault_sampled = true;
+                inject_fault(route.id, fault, header, ctx).await?;
             }
-            if let Some((peer, target)) = runtime.pick_peer(route.upstream_pool_id) {
+            if let Some((mut peer, target, settings)) = runtime.pick_peer(route.upstream_pool_id) {
                 if let Some(prev) = ctx.target.take() {
-                    prev.inflight.fetch_sub(1, Ordering::Relaxed);
+                    prev.release();
                 }
                 ctx.target = target;
-                debug!("route matched: {}", route.id);
+                if let Some(version) = settings.proxy_protocol {
+                    // PROXY 头只在新建连接时由连接器发送，连接池需按客户端 IP 隔离
+                    let local = session
+                        .as_downstream()
+                        .server_addr()
+                        .and_then(|addr| addr.as_inet().copied());
+                    let header = upstream_proxy_header(version, ctx.peer_addr, local)?;
+                    peer.options.custom_l4 = Some(Arc::new(ProxyHeaderConnect::new(header)));
+                    peer.group_key = proxy_group_key(ctx.peer_addr);
+                }
+                ctx.pool = Some(settings);
+                ctx.route_error_pages = route.error_pages.clone();
+                ctx.security_headers = route.security_headers.clone();
+                if let Some(status) = route.limits.check_header(header) {
+                    return Err(Error::explain(
+                        ErrorType::HTTPStatus(status),
+                        "request exceeds route limits",
+                    ));
+                }
+                ctx.max_body_bytes = tighter(ctx.max_body_bytes, route.limits.max_body_bytes);
+                if route.kind == RouteKind::Ws && ctx.ws.is_none() {
+                    ctx.ws = crate::ws::acquire(route.id, route.websocket);
+                    if ctx.ws.is_none() {
+                        return Err(Error::explain(
+                            ErrorType::HTTPStatus(503),
+                            "websocket connection limit reached",
+                        ));
+                    }
+                }
+                debug!(request_id = %ctx.request_id, "route matched: {}", route.id);
                 return Ok(peer);
             }
         }
+        // 路由已匹配但没有可用上游时，错误页与安全响应头取最先匹配的路由配置
+        ctx.route_error_pages = first_matched.and_then(|route| route.error_pages.clone());
+        if let Some(route) = first_matched {
+            ctx.security_headers = route.security_headers.clone();
+        }
 
         Err(Error::explain(
             ErrorType::HTTPStatus(502),
@@ -140,11 +423,213 @@ impl ProxyHttp for ProxyRouter {
         ))
     }
 
+    fn fail_to_connect(
+        &self,
+        _session: &mut Session,
+        _peer: &HttpPeer,
+        ctx: &mut Self::CTX,
+        mut e: Box<Error>,
+    ) -> Box<Error> {
+        // 连接阶段请求尚未发出，按池配置重试总是安全的
+        let retry = ctx
+            .pool
+            .as_ref()
+            .is_some_and(|pool| ctx.retries < pool.max_retries);
+        if retry {
+            ctx.retries += 1;
+            e.set_retry(true);
+        }
+        e
+    }
+
+    async fn connected_to_upstream(
+        &self,
+        _session: &mut Session,
+        reused: bool,
+        _peer: &HttpPeer,
+        #[cfg(unix)] fd: std::os::unix::io::RawFd,
+        #[cfg(windows)] sock: std::os::windows::io::RawSocket,
+        _digest: Option<&pingora::protocols::Digest>,
+        ctx: &mut Self::CTX,
+    ) -> Result<(), Box<pingora::Error>> {
+        if let (Some(pool), Some(target)) = (&ctx.pool, &ctx.target) {
+            #[cfg(unix)]
+            let conn = fd as u64;
+            #[cfg(windows)]
+            let conn = sock as u64;
+            ctx.upstream_requests = crate::keepalive::connected(pool.pool_id, conn, reused);
+            ctx.upstream_close = false;
+            crate::metrics::inc_upstream_connection(
+                &pool.pool_id.to_string(),
+                target.address(),
+                reused,
+            );
+        }
+        #[cfg(unix)]
+        if let (Some(ws), Some(target)) = (&ctx.ws, &ctx.target) {
+            ws.set_upstream(fd, target.address());
+        }
+        Ok(())
+    }
+
+    async fn upstream_request_filter(
+        &self,
+        session: &mut Session,
+        upstream_request: &mut RequestHeader,
+        ctx: &mut Self::CTX,
+    ) -> Result<(), Box<pingora::Error>>
+    where
+        Self::CTX: Send + Sync,
+    {
+        let forwarded = ctx
+            .listener
+            .as_ref()
+            .map(|listener| listener.forwarded.clone())
+            .unwrap_or_default();
+        let is_https = ctx
+            .listener
+            .as_ref()
+            .is_some_and(|listener| listener.protocol.eq_ignore_ascii_case("https"));
+        let host = request_host(session.req_header()).map(str::to_string);
+        let hop = ForwardedHop {
+            peer_ip: ctx.peer_addr.map(|addr| addr.ip()),
+            client_ip: ctx.client_ip,
+            proto: if is_https { "https" } else { "http" },
+            host: host.as_deref(),
+            port: downstream_port(session),
+        };
+        forwarded.apply(upstream_request, &hop)?;
+
+        // h2 连接由多个请求复用，keepalive 设置只作用于 http1 上游；WebSocket 升级须保留 Connection 头
+        if let Some(pool) = &ctx.pool
+            && pool.protocol == UpstreamProtocol::Http1
+            && ctx.ws.is_none()
+            && ctx.upstream_requests > 0
+            && crate::keepalive::should_close(pool.pool_id, &pool.keepalive, ctx.upstream_requests)
+        {
+            upstream_request.insert_header(http::header::CONNECTION, "close")?;
+            ctx.upstream_close = true;
+        }
+        Ok(())
+    }
+
+    async fn response_filter(
+        &self,
+        session: &mut Session,
+        upstream_response: &mut ResponseHeader,
+        ctx: &mut Self::CTX,
+    ) -> Result<(), Box<pingora::Error>>
+    where
+        Self::CTX: Send + Sync,
+    {
+        check_drain(ctx)?;
+        if connection_close(&upstream_response.headers) {
+            ctx.upstream_close = true;
+        }
+        if ctx.grpc
+            && let Some(code) = crate::grpc::status_from_headers(&upstream_response.headers)
+        {
+            // trailers-only 响应：状态在响应头中，尚未向客户端写出任何内容，可按状态重试
+            ctx.grpc_status = Some(code);
+            if let Some(pool) = &ctx.pool
+                && pool.grpc_retry_on.contains(&code)
+                && ctx.retries < pool.max_retries
+                && grpc_replayable(session, ctx)
+            {
+                ctx.retries += 1;
+                let mut e = Error::explain(
+                    ErrorType::HTTPStatus(503),
+                    format!("retry on grpc status {}", code_name(code)),
+                );
+                e.set_retry(true);
+                return Err(e);
+            }
+        }
+        if upstream_response.status == StatusCode::SWITCHING_PROTOCOLS
+            && let Some(ws) = &ctx.ws
+        {
+            ws.establish();
+            ctx.upgraded = true;
+            crate::metrics::inflight_dec();
+        }
+        if !ctx.request_id.is_empty() {
+            upstream_response
+                .insert_header(ctx.request_id_header.clone(), ctx.request_id.as_str())?;
+        }
+        if let Some(security_headers) = &ctx.security_headers {
+            security_headers.apply(upstream_response)?;
+        }
+        Ok(())
+    }
+
+    fn response_body_filter(
+        &self,
+        _session: &mut Session,
+        body: &mut Option<Bytes>,
+        _end_of_stream: bool,
+        ctx: &mut Self::CTX,
+    ) -> Result<Option<std::time::Duration>, Box<pingora::Error>>
+    where
+        Self::CTX: Send + Sync,
+    {
+        if ctx.upgraded
+            && let Some(ws) = &ctx.ws
+        {
+            ws.on_response_body(body);
+        }
+        check_drain(ctx)?;
+        Ok(None)
+    }
+
+    fn upstream_response_trailer_filter(
+        &self,
+        _session: &mut Session,
+        upstream_trailers: &mut http::HeaderMap,
+        ctx: &mut Self::CTX,
+    ) -> Result<(), Box<pingora::Error>> {
+        if ctx.grpc
+            && let Some(code) = crate::grpc::status_from_headers(upstream_trailers)
+        {
+            ctx.grpc_status = Some(code);
+        }
+        Ok(())
+    }
+
+    async fn fail_to_proxy(
+        &self,
+        session: &mut Session,
+        e: &Error,
+        ctx: &mut Self::CTX,
+    ) -> FailToProxy
+    where
+        Self::CTX: Send + Sync,
+    {
+        let code = error_status_code(e);
+        if ctx.grpc && code > 0 {
+            ctx.grpc_status = Some(crate::grpc::status_from_http(code));
+        }
+        if code > 0
+            && session.as_downstream().response_written().is_none()
+            && let Err(err) = write_error_response(session, ctx, code).await
+        {
+            warn!(
+                request_id = %ctx.request_id,
+                "failed to write error response: {}", err
+            );
+        }
+        FailToProxy {
+            error_code: code,
+            can_reuse_downstream: false,
+        }
+    }
+
     async fn logging(&self, session: &mut Session, e: Option<&Error>, ctx: &mut Self::CTX)
     where
         Self::CTX: Send + Sync,
     {
-        crate::metrics::inflight_dec();
+        if !ctx.upgraded {
+            crate::metrics::inflight_dec();
+        }
 
         let status = session
             .as_downstream()
@@ -155,21 +640,64 @@ impl ProxyHttp for ProxyRouter {
         let seconds = ctx.start.elapsed().as_secs_f64();
         crate::metrics::observe_request(method, status, seconds);
 
-        if e.is_some() {
+        let grpc_status = ctx
+            .grpc
+            .then(|| {
+                ctx.grpc_status
+                    .unwrap_or_else(|| crate::grpc::status_from_http(status))
+            })
+            .map(code_name);
+        if let Some(code) = grpc_status {
+            crate::metrics::inc_grpc_response(code);
+        }
+
+        if let Some(e) = e {
             crate::metrics::inc_upstream_error("proxy_error");
+            warn!(request_id = %ctx.request_id, "proxy error: {}", e);
+        }
+
+        if let Some(pool) = &ctx.pool
+            && pool.protocol == UpstreamProtocol::Http1
+            && ctx.upstream_requests > 0
+            && !ctx.upstream_close
+            && !ctx.upgraded
+            && e.is_none()
+        {
+            crate::keepalive::released(pool.pool_id, &pool.keepalive);
         }
 
+        let peer = ctx
+            .peer_addr
+            .map(|addr| addr.to_string())
+            .unwrap_or_default();
+        let client = ctx.client_ip.map(|ip| ip.to_string()).unwrap_or_default();
+        info!(
+            request_id = %ctx.request_id,
+            client = %client,
+            peer = %peer,
+            method,
+            path = session.req_header().uri.path(),
+            status,
+            grpc_status = grpc_status.unwrap_or(""),
+            elapsed_ms = ctx.start.elapsed().as_millis() as u64,
+            "access"
+        );
+
         if let Some(target) = ctx.target.take() {
-            target.inflight.fetch_sub(1, Ordering::Relaxed);
+            target.release();
         }
     }
 }
 
 pub struct RuntimeConfig {
     pub listeners: Vec<ListenerRuntime>,
-    pub listeners_by_port: HashMap<u16, ListenerRuntime>,
+    pub listeners_by_port: HashMap<u16, Arc<ListenerRuntime>>,
     pub tls_by_port: HashMap<u16, Arc<TlsKeyPair>>,
-    pub routes_by_listener: HashMap<Uuid, Vec<RouteRule>>,
+    /// https 端口的服务端 ALPN 协议列表（wire format）
+    pub alpn_by_port: HashMap<u16, Vec<u8>>,
+    /// https 端口要求校验的客户端证书 CA
+    pub client_ca_by_port: HashMap<u16, Arc<Vec<X509>>>,
+    pub routes_by_listener: HashMap<Uuid, ListenerRoutes>,
     pools: HashMap<Uuid, PoolRuntime>,
 }
 
@@ -233,6 +761,136 @@ pub struct ListenerRuntime {
     pub id: Uuid,
     pub port: i32,
     pub protocol: String,
+    pub request_id: RequestIdConfig,
+    pub forwarded: ForwardedConfig,
+    pub proxy_protocol: ProxyProtocolConfig,
+    pub http2: Http2Config,
+    pub error_pages: Option<Arc<ErrorPages>>,
+    pub maintenance: Option<Arc<MaintenanceConfig>>,
+    pub limits: RequestLimits,
+    pub security_headers: Option<Arc<SecurityHeaders>>,
+}
+
+/// 监听器的下游 HTTP/2 配置（运行时）
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Hash)]
+pub struct Http2Config {
+    pub h2c: bool,
+    pub limits: Http2Limits,
+}
+
+/// 下游 HTTP/2 连接级限制
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Hash)]
+pub struct Http2Limits {
+    pub max_concurrent_streams: Option<u32>,
+    pub max_header_list_size: Option<u32>,
+}
+
+impl Http2Config {
+    fn from_options(listener: &Listener, options: Option<&Http2Options>) -> Self {
+        let Some(options) = options else {
+            return Self::default();
+        };
+        Self {
+            h2c: listener.protocol.eq_ignore_ascii_case("http") && options.h2c.unwrap_or(false),
+            limits: Http2Limits {
+                max_concurrent_streams: options.max_concurrent_streams.filter(|n| *n > 0),
+                max_header_list_size: options.max_header_list_size.filter(|n| *n > 0),
+            },
+        }
+    }
+}
+
+impl ListenerRuntime {
+    /// 是否为不解析 HTTP 的 L4 监听器
+    pub fn is_stream(&self) -> bool {
+        self.protocol.eq_ignore_ascii_case("tcp")
+            || self.protocol.eq_ignore_ascii_case("tls_passthrough")
+    }
+}
+
+/// 监听器的请求 ID 配置（运行时）
+#[derive(Clone)]
+pub struct RequestIdConfig {
+    header: String,
+    trusted: Vec<IpCidr>,
+}
+
+impl Default for RequestIdConfig {
+    fn default() -> Self {
+        Self {
+            header: DEFAULT_REQUEST_ID_HEADER.to_string(),
+            trusted: Vec::new(),
+        }
+    }
+}
+
+impl RequestIdConfig {
+    fn from_options(options: Option<&RequestIdOptions>) -> Self {
+        let Some(options) = options else {
+            return Self::default();
+        };
+        Self {
+            header: options
+                .header
+                .as_deref()
+                .map(str::trim)
+                .filter(|h| !h.is_empty())
+                .map(str::to_ascii_lowercase)
+                .unwrap_or_else(|| DEFAULT_REQUEST_ID_HEADER.to_string()),
+            trusted: options
+                .trusted_cidrs
+                .iter()
+                .filter_map(|cidr| IpCidr::parse(cidr))
+                .collect(),
+        }
+    }
+
+    /// 可信客户端携带合法请求 ID 时沿用，否则生成新的 UUIDv7
+    fn resolve(&self, header: &RequestHeader, client_ip: Option<IpAddr>) -> String {
+        let trusted = client_ip.is_some_and(|ip| contains_ip(&self.trusted, ip));
+        if trusted
+            && let Some(incoming) = header
+                .headers
+                .get(self.header.as_str())
+                .and_then(|v| v.to_str().ok())
+                .map(str::trim)
+                .filter(|v| is_valid_request_id(v))
+        {
+            return incoming.to_string();
+        }
+        Uuid::now_v7().to_string()
+    }
+}
+
+fn is_valid_request_id(value: &str) -> bool {
+    !value.is_empty() && value.len() <= 128 && value.bytes().all(|b| b.is_ascii_graphic())
+}
+
+/// 监听器或路由的维护配置（运行时），仅在开启时存在
+pub struct MaintenanceConfig {
+    message: Option<String>,
+    retry_after: Option<u32>,
+    bypass: Vec<IpCidr>,
+}
+
+impl MaintenanceConfig {
+    fn from_options(options: Option<&MaintenanceOptions>) -> Option<Arc<Self>> {
+        let options = options.filter(|options| options.enabled)?;
+        Some(Arc::new(Self {
+            message: options.message.clone(),
+            retry_after: options.retry_after,
+            bypass: options
+                .bypass_cidrs
+                .iter()
+                .filter_map(|cidr| IpCidr::parse(cidr))
+                .collect(),
+        }))
+    }
+
+    /// 客户端不在放行网段内时生效
+    fn applies(&self, client_ip: Option<IpAddr>) -> bool {
+        !client_ip.is_some_and(|ip| contains_ip(&self.bypass, ip))
+    }
 }
 
 #[derive(Clone)]
@@ -244,11 +902,11 @@ pub struct TlsKeyPair {
 
 pub struct PortTlsSelector {
     port: u16,
-    runtime: Arc<RwLock<RuntimeConfig>>,
+    runtime: SharedRuntime,
 }
 
 impl PortTlsSelector {
-    pub fn new(port: u16, runtime: Arc<RwLock<RuntimeConfig>>) -> Self {
+    pub fn new(port: u16, runtime: SharedRuntime) -> Self {
         Self { port, runtime }
     }
 }
@@ -256,9 +914,12 @@ impl PortTlsSelector {
 #[async_trait]
 impl TlsAccept for PortTlsSelector {
     async fn certificate_callback(&self, ssl: &mut TlsRef) -> () {
-        let pair = {
-            let runtime = self.runtime.read().await;
-            runtime.tls_by_port.get(&self.port).cloned()
+        let (pair, client_ca) = {
+            let runtime = self.runtime.load();
+            (
+                runtime.tls_by_port.get(&self.port).cloned(),
+                runtime.client_ca_by_port.get(&self.port).cloned(),
+            )
         };
         let Some(pair) = pair else { return };
 
@@ -267,7 +928,63 @@ impl TlsAccept for PortTlsSelector {
             let _ = pingora::tls::ext::ssl_add_chain_cert(ssl, cert);
         }
         let _ = pingora::tls::ext::ssl_use_private_key(ssl, &pair.key);
+
+        if let Some(cas) = client_ca {
+            request_client_cert(ssl, &cas);
+        }
+    }
+}
+
+/// 请求并校验客户端证书；客户端可以不提供证书，提供的证书必须由配置的 CA 签发
+fn request_client_cert(ssl: &mut TlsRef, cas: &[X509]) {
+    let store = X509StoreBuilder::new().and_then(|mut builder| {
+        for ca in cas {
+            builder.add_cert(ca.clone())?;
+        }
+        Ok(builder.build())
+    });
+    match store.and_then(|store| ssl.set_verify_cert_store(store)) {
+        Ok(()) => ssl.set_verify_callback(SslVerifyMode::PEER, |verified, ctx| {
+            // 仅记录链校验通过的叶子证书
+            if verified
+                && ctx.error_depth() == 0
+                && let Some(cert) = ctx.current_cert()
+            {
+                crate::tls::remember_client_cert(cert);
+            }
+            verified
+        }),
+        Err(err) => warn!("failed to set client CA store: {}", err),
+    }
+}
+
+/// 按端口所属 TLS 策略的 ALPN 列表与客户端协商协议；端口未配置时按 http/1.1 协商
+pub fn select_alpn<'a>(
+    runtime: &ArcSwap<RuntimeConfig>,
+    port: u16,
+    client: &'a [u8],
+) -> Result<&'a [u8], AlpnError> {
+    let runtime = runtime.load();
+    let protos = runtime
+        .alpn_by_port
+        .get(&port)
+        .map(|protos| protos.as_slice())
+        .unwrap_or(crate::tls::ALPN_HTTP1);
+    let selected = select_next_proto(protos, client).ok_or(AlpnError::NOACK)?;
+    client_protocol(client, selected).ok_or(AlpnError::NOACK)
+}
+
+/// 在客户端 ALPN 列表（长度前缀编码）中找到与 `proto` 相同的条目，返回借用自客户端列表的切片
+fn client_protocol<'a>(client: &'a [u8], proto: &[u8]) -> Option<&'a [u8]> {
+    let mut rest = client;
+    while let Some((&len, tail)) = rest.split_first() {
+        let (name, tail) = tail.split_at_checked(len as usize)?;
+        if name == proto {
+            return Some(name);
+        }
+        rest = tail;
     }
+    None
 }
 
 #[derive(Clone)]
@@ -277,24 +994,58 @@ pub struct RouteRule {
     pub priority: i32,
     pub matcher: RouteMatcher,
     pub kind: RouteKind,
+    pub error_pages: Option<Arc<ErrorPages>>,
+    pub maintenance: Option<Arc<MaintenanceConfig>>,
+    pub limits: RequestLimits,
+    pub websocket: WsSettings,
+    pub fault: Option<Arc<FaultInjection>>,
+    pub ext_auth: Option<Arc<ExtAuthPolicy>>,
+    /// 已叠加监听器配置的安全响应头
+    pub security_headers: Option<Arc<SecurityHeaders>>,
 }
 
 #[derive(Clone, Default)]
 pub struct RouteMatcher {
     host: Option<String>,
+    host_regex: Option<Regex>,
     path_prefix: Option<String>,
     path_regex: Option<Regex>,
     methods: Option<Vec<String>>,
-    headers: Option<HashMap<String, String>>,
-    query: Option<HashMap<String, String>>,
+    headers: Option<Vec<(String, ValueMatcher)>>,
+    query: Option<Vec<(String, ValueMatcher)>>,
+    cookies: Option<Vec<(String, ValueMatcher)>>,
+    source_cidrs: Option<Vec<IpCidr>>,
+    client_cert_subject: Option<ValueMatcher>,
     ws: Option<bool>,
 }
 
+/// 路由匹配所需的请求头以外的信息
+pub struct MatchInput {
+    /// 规范化后的请求主机（见 `normalize_host`），缺失时为空串
+    pub host: String,
+    /// 经可信代理解析后的客户端 IP
+    pub client_ip: Option<IpAddr>,
+    /// 经校验的客户端证书主题
+    pub client_cert_subject: Option<String>,
+}
+
+impl MatchInput {
+    fn new(header: &RequestHeader, ctx: &RequestCtx) -> Self {
+        Self {
+            host: request_host(header).map(normalize_host).unwrap_or_default(),
+            client_ip: ctx.client_ip,
+            client_cert_subject: ctx.client_cert_subject.clone(),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum RouteKind {
     Port,
     Path,
     Ws,
+    Tcp,
+    Sni,
 }
 
 impl RouteKind {
@@ -303,6 +1054,8 @@ impl RouteKind {
             "port" => Some(Self::Port),
             "path" => Some(Self::Path),
             "ws" => Some(Self::Ws),
+            "tcp" => Some(Self::Tcp),
+            "sni" => Some(Self::Sni),
             _ => None,
         }
     }
@@ -312,14 +1065,30 @@ impl RouteMatcher {
     pub fn from_json(value: &JsonValue) -> Option<Self> {
         let parsed: RouteMatch = serde_json::from_value(value.clone()).ok()?;
         let path_regex = parsed.path_regex.and_then(|expr| Regex::new(&expr).ok());
+        let host_regex = parsed.host_regex.and_then(|expr| Regex::new(&expr).ok());
 
         Some(Self {
-            host: parsed.host,
+            host: parsed.host.map(|host| normalize_host(&host)),
+            host_regex,
             path_prefix: parsed.path_prefix,
             path_regex,
             methods: parsed.method,
-            headers: parsed.headers,
-            query: parsed.query,
+            headers: compile_value_matchers(parsed.headers)?,
+            query: compile_value_matchers(parsed.query)?,
+            cookies: compile_value_matchers(parsed.cookies)?,
+            source_cidrs: match parsed.source_cidrs {
+                Some(cidrs) => Some(
+                    cidrs
+                        .iter()
+                        .map(|cidr| IpCidr::parse(cidr))
+                        .collect::<Option<Vec<_>>>()?,
+                ),
+                None => None,
+            },
+            client_cert_subject: match &parsed.client_cert_subject {
+                Some(value) => Some(ValueMatcher::compile(value)?),
+                None => None,
+            },
             ws: parsed.ws,
         })
     }
@@ -328,16 +1097,44 @@ impl RouteMatcher {
         self.ws = Some(true);
     }
 
-    pub fn matches(&self, header: &RequestHeader) -> bool {
-        if let Some(expected_host) = &self.host {
-            let host = header
-                .headers
-                .get("host")
-                .and_then(|v| v.to_str().ok())
-                .unwrap_or("");
-            if !host.eq_ignore_ascii_case(expected_host) {
-                return false;
-            }
+    pub fn host(&self) -> Option<&str> {
+        self.host.as_deref()
+    }
+
+    pub fn host_regex(&self) -> Option<&str> {
+        self.host_regex.as_ref().map(Regex::as_str)
+    }
+
+    pub fn path_prefix(&self) -> Option<&str> {
+        self.path_prefix.as_deref()
+    }
+
+    pub fn path_regex(&self) -> Option<&str> {
+        self.path_regex.as_ref().map(Regex::as_str)
+    }
+
+    pub fn matches(&self, header: &RequestHeader, input: &MatchInput) -> bool {
+        if let Some(pattern) = &self.host
+            && !host_matches(pattern, &input.host)
+        {
+            return false;
+        }
+        if let Some(regex) = &self.host_regex
+            && !regex.is_match(&input.host)
+        {
+            return false;
+        }
+
+        if let Some(cidrs) = &self.source_cidrs
+            && !input.client_ip.is_some_and(|ip| contains_ip(cidrs, ip))
+        {
+            return false;
+        }
+
+        if let Some(matcher) = &self.client_cert_subject
+            && !matcher.matches(input.client_cert_subject.as_deref())
+        {
+            return false;
         }
 
         let path = header.uri.path();
@@ -361,13 +1158,12 @@ impl RouteMatcher {
         }
 
         if let Some(headers) = &self.headers {
-            for (key, expected) in headers {
+            for (key, matcher) in headers {
                 let actual = header
                     .headers
                     .get(key.as_str())
-                    .and_then(|v| v.to_str().ok())
-                    .unwrap_or("");
-                if actual != expected {
+                    .and_then(|v| v.to_str().ok());
+                if !matcher.matches(actual) {
                     return false;
                 }
             }
@@ -375,9 +1171,16 @@ impl RouteMatcher {
 
         if let Some(query) = &self.query {
             let actual = parse_query(header.uri.query().unwrap_or(""));
-            for (key, expected) in query {
-                let got = actual.get(key).map(String::as_str).unwrap_or("");
-                if got != expected {
+            for (key, matcher) in query {
+                if !matcher.matches(actual.get(key).map(String::as_str)) {
+                    return false;
+                }
+            }
+        }
+
+        if let Some(cookies) = &self.cookies {
+            for (name, matcher) in cookies {
+                if !matcher.matches(crate::value_match::cookie(header, name)) {
                     return false;
                 }
             }
@@ -394,69 +1197,276 @@ impl RouteMatcher {
 }
 
 impl RouteRule {
-    pub fn matches(&self, header: &RequestHeader) -> bool {
+    pub fn matches(&self, header: &RequestHeader, input: &MatchInput) -> bool {
         match self.kind {
             RouteKind::Port => true,
-            RouteKind::Path => self.matcher.matches(header),
-            RouteKind::Ws => is_ws_request(header) && self.matcher.matches(header),
+            RouteKind::Path => self.matcher.matches(header, input),
+            RouteKind::Ws => is_ws_request(header) && self.matcher.matches(header, input),
+            // tcp/sni 路由只由 L4 转发使用
+            RouteKind::Tcp | RouteKind::Sni => false,
+        }
+    }
+
+    /// L4 路由匹配：sni 路由未配置 host 时作为兜底，匹配任意（含缺失的）SNI
+    pub fn matches_stream(&self, sni: Option<&str>) -> bool {
+        match self.kind {
+            RouteKind::Tcp => true,
+            RouteKind::Sni => match (&self.matcher.host, sni) {
+                (None, _) => true,
+                (Some(pattern), Some(sni)) => host_matches(pattern, sni),
+                (Some(_), None) => false,
+            },
+            _ => false,
+        }
+    }
+}
+
+/// 监听器的路由表：按优先级排序的规则与预编译索引
+pub struct ListenerRoutes {
+    rules: Vec<RouteRule>,
+    index: RouteIndex,
+    maintenance: bool,
+    ext_auth: bool,
+}
+
+impl ListenerRoutes {
+    fn new(rules: Vec<RouteRule>) -> Self {
+        let index = RouteIndex::build(&rules);
+        let maintenance = rules.iter().any(|route| route.maintenance.is_some());
+        let ext_auth = rules.iter().any(|route| route.ext_auth.is_some());
+        Self {
+            rules,
+            index,
+            maintenance,
+            ext_auth,
         }
     }
+
+    pub fn rules(&self) -> &[RouteRule] {
+        &self.rules
+    }
+
+    /// 是否有路由处于维护模式；没有时请求过滤阶段无需提前匹配路由
+    pub fn has_maintenance(&self) -> bool {
+        self.maintenance
+    }
+
+    /// 是否有路由配置了外部授权
+    pub fn has_ext_auth(&self) -> bool {
+        self.ext_auth
+    }
+
+    /// 按优先级返回与请求完整匹配的 HTTP 路由
+    pub fn matching<'a>(
+        &'a self,
+        header: &'a RequestHeader,
+        input: MatchInput,
+    ) -> impl Iterator<Item = &'a RouteRule> + 'a {
+        self.index
+            .candidates(&input.host, header.uri.path())
+            .positions()
+            .map(move |pos| &self.rules[pos])
+            .filter(move |route| route.matches(header, &input))
+    }
 }
 
 pub struct PoolRuntime {
-    targets: Vec<Arc<TargetRuntime>>,
+    targets: PoolTargets,
     cursor: AtomicUsize,
     policy: PoolPolicy,
     health: PoolHealthCheck,
+    settings: Arc<PoolSettings>,
+    /// 层级健康容量低于该百分比时同时使用下一层级的目标
+    failover_threshold: u8,
+}
+
+/// 缺省的故障转移阈值（百分比）
+const DEFAULT_FAILOVER_THRESHOLD: u8 = 50;
+
+/// 池内可分配请求的目标：配置中的静态目标，或由服务发现持续更新的目标
+enum PoolTargets {
+    Static(Arc<Vec<Arc<TargetRuntime>>>),
+    Discovered(Arc<Discovery>),
+}
+
+/// 上游连接协议
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum UpstreamProtocol {
+    #[default]
+    Http1,
+    /// TLS + ALPN h2
+    H2,
+    /// 明文 HTTP/2（prior knowledge）
+    H2c,
+}
+
+impl UpstreamProtocol {
+    fn from_str(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "http1" => Some(Self::Http1),
+            "h2" => Some(Self::H2),
+            "h2c" => Some(Self::H2c),
+            _ => None,
+        }
+    }
+}
+
+/// 上游池的连接与重试配置（运行时）
+#[derive(Clone, Debug, Default)]
+pub struct PoolSettings {
+    pub pool_id: Uuid,
+    pub proxy_protocol: Option<ProxyProtocolVersion>,
+    pub protocol: UpstreamProtocol,
+    pub sni: Option<String>,
+    pub max_retries: u32,
+    pub grpc_retry_on: Vec<u32>,
+    pub drain_timeout: Duration,
+    pub keepalive: KeepaliveSettings,
+}
+
+impl PoolSettings {
+    fn from_pool(pool: &UpstreamPool, options: &PoolOptions) -> Self {
+        let proxy_protocol = options.proxy_protocol.as_deref().and_then(|version| {
+            let parsed = ProxyProtocolVersion::from_str(version);
+            if parsed.is_none() {
+                warn!(
+                    "invalid proxy_protocol {} for pool {}, disabled",
+                    version, pool.id
+                );
+            }
+            parsed
+        });
+        let protocol = options
+            .protocol
+            .as_deref()
+            .map(|protocol| {
+                UpstreamProtocol::from_str(protocol).unwrap_or_else(|| {
+                    warn!(
+                        "invalid protocol {} for pool {}, fallback to http1",
+                        protocol, pool.id
+                    );
+                    UpstreamProtocol::Http1
+                })
+            })
+            .unwrap_or_default();
+        // 与 h2/h2c 同用时 PROXY 头会写进 TLS / HTTP/2 数据流，控制平面已拒绝该组合
+        let proxy_protocol = proxy_protocol.filter(|_| {
+            let supported = protocol == UpstreamProtocol::Http1;
+            if !supported {
+                warn!(
+                    "proxy_protocol not supported with http2 pool {}, disabled",
+                    pool.id
+                );
+            }
+            supported
+        });
+        let drain_timeout = options
+            .drain_timeout_secs
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs)
+            .unwrap_or(crate::drain::DEFAULT_DRAIN_TIMEOUT);
+        Self {
+            pool_id: pool.id,
+            proxy_protocol,
+            protocol,
+            sni: options.sni.clone(),
+            max_retries: options.max_retries.unwrap_or(0),
+            grpc_retry_on: options
+                .grpc_retry_on
+                .iter()
+                .filter_map(|status| gateway_common::grpc::parse_code(status))
+                .collect(),
+            drain_timeout,
+            keepalive: KeepaliveSettings::from_options(options.keepalive.as_ref(), drain_timeout),
+        }
+    }
 }
 
 impl PoolRuntime {
+    fn targets(&self) -> Arc<Vec<Arc<TargetRuntime>>> {
+        match &self.targets {
+            PoolTargets::Static(targets) => targets.clone(),
+            PoolTargets::Discovered(discovery) => discovery.targets(),
+        }
+    }
+
     fn pick(&self) -> Option<Arc<TargetRuntime>> {
+        let targets = self.targets();
+        let candidates = self.failover_candidates(&targets);
+        let targets = candidates.as_deref().unwrap_or(&targets);
         match self.policy {
-            PoolPolicy::RoundRobin => self.pick_round_robin(),
-            PoolPolicy::Weighted => self.pick_weighted(),
-            PoolPolicy::LeastConn => self.pick_least_conn(),
+            PoolPolicy::RoundRobin => self.pick_round_robin(targets),
+            PoolPolicy::Weighted => self.pick_weighted(targets),
+            PoolPolicy::LeastConn => self.pick_least_conn(targets),
+        }
+    }
+
+    /// 按优先级层级（目标已按 priority 升序排列）选取候选目标：依次加入各层级的健康目标，
+    /// 直到某一层级的健康容量达到阈值。只有一个层级时返回 None，沿用全部目标；
+    /// 所有层级都没有健康目标时只使用最优先的层级，不会把请求分给备用层级中的不健康目标
+    fn failover_candidates(
+        &self,
+        targets: &[Arc<TargetRuntime>],
+    ) -> Option<Vec<Arc<TargetRuntime>>> {
+        if targets.first()?.priority == targets.last()?.priority {
+            return None;
+        }
+        let mut candidates = Vec::new();
+        for tier in targets.chunk_by(|a, b| a.priority == b.priority) {
+            let total: usize = tier.iter().map(|t| t.weight()).sum();
+            let mut healthy = 0;
+            for target in tier.iter().filter(|t| t.is_healthy()) {
+                healthy += target.weight();
+                candidates.push(target.clone());
+            }
+            if healthy * 100 >= total * usize::from(self.failover_threshold) {
+                break;
+            }
         }
+        if candidates.is_empty() {
+            let primary = targets.chunk_by(|a, b| a.priority == b.priority).next()?;
+            return Some(primary.to_vec());
+        }
+        Some(candidates)
     }
 
-    fn pick_weighted(&self) -> Option<Arc<TargetRuntime>> {
-        if self.targets.is_empty() {
+    fn pick_weighted(&self, targets: &[Arc<TargetRuntime>]) -> Option<Arc<TargetRuntime>> {
+        if targets.is_empty() {
             return None;
         }
-        let total_all: usize = self.targets.iter().map(|t| t.weight()).sum();
+        let total_all: usize = targets.iter().map(|t| t.weight()).sum();
         if total_all == 0 {
             return None;
         }
-        let total_healthy: usize = self
-            .targets
+        let total_healthy: usize = targets
             .iter()
-            .filter(|t| t.healthy.load(Ordering::Relaxed))
+            .filter(|t| t.is_healthy())
             .map(|t| t.weight())
             .sum();
         let use_all = total_healthy == 0;
         let total_weight = if use_all { total_all } else { total_healthy };
 
         let mut cursor = self.cursor.fetch_add(1, Ordering::Relaxed) % total_weight;
-        for target in &self.targets {
+        for target in targets {
             let weight = target.weight();
-            let healthy = target.healthy.load(Ordering::Relaxed);
+            let healthy = target.is_healthy();
             if healthy || use_all {
                 if cursor < weight {
-                    target.inflight.fetch_add(1, Ordering::Relaxed);
+                    target.acquire();
                     return Some(target.clone());
                 }
                 cursor = cursor.saturating_sub(weight);
             }
         }
-        let fallback = self.targets.first().cloned();
+        let fallback = targets.first().cloned();
         if let Some(target) = &fallback {
-            target.inflight.fetch_add(1, Ordering::Relaxed);
+            target.acquire();
         }
         fallback
     }
 
-    fn pick_round_robin(&self) -> Option<Arc<TargetRuntime>> {
-        let n = self.targets.len();
+    fn pick_round_robin(&self, targets: &[Arc<TargetRuntime>]) -> Option<Arc<TargetRuntime>> {
+        let n = targets.len();
         if n == 0 {
             return None;
         }
@@ -464,33 +1474,32 @@ impl PoolRuntime {
 
         for offset in 0..n {
             let idx = (start + offset) % n;
-            let target = &self.targets[idx];
-            if target.healthy.load(Ordering::Relaxed) {
-                target.inflight.fetch_add(1, Ordering::Relaxed);
+            let target = &targets[idx];
+            if target.is_healthy() {
+                target.acquire();
                 return Some(target.clone());
             }
         }
 
-        let target = self.targets[start].clone();
-        target.inflight.fetch_add(1, Ordering::Relaxed);
+        let target = targets[start].clone();
+        target.acquire();
         Some(target)
     }
 
-    fn pick_least_conn(&self) -> Option<Arc<TargetRuntime>> {
-        let n = self.targets.len();
+    fn pick_least_conn(&self, targets: &[Arc<TargetRuntime>]) -> Option<Arc<TargetRuntime>> {
+        let n = targets.len();
         if n == 0 {
             return None;
         }
 
-        let min_healthy = self
-            .targets
+        let min_healthy = targets
             .iter()
-            .filter(|t| t.healthy.load(Ordering::Relaxed))
+            .filter(|t| t.is_healthy())
             .map(|t| t.inflight.load(Ordering::Relaxed))
             .min();
         let use_all = min_healthy.is_none();
         let min_inflight = if use_all {
-            self.targets
+            targets
                 .iter()
                 .map(|t| t.inflight.load(Ordering::Relaxed))
                 .min()
@@ -501,35 +1510,78 @@ impl PoolRuntime {
         let start = self.cursor.fetch_add(1, Ordering::Relaxed) % n;
         for offset in 0..n {
             let idx = (start + offset) % n;
-            let target = &self.targets[idx];
-            let ok = use_all || target.healthy.load(Ordering::Relaxed);
+            let target = &targets[idx];
+            let ok = use_all || target.is_healthy();
             if ok && target.inflight.load(Ordering::Relaxed) == min_inflight {
-                target.inflight.fetch_add(1, Ordering::Relaxed);
+                target.acquire();
                 return Some(target.clone());
             }
         }
 
-        let target = self.targets[start].clone();
-        target.inflight.fetch_add(1, Ordering::Relaxed);
+        let target = targets[start].clone();
+        target.acquire();
         Some(target)
     }
 }
 
+/// `previous` 为当前生效的运行时配置：池内仍存在的目标（按目标 ID 与地址识别）沿用其健康状态
+/// 与进行中请求计数，避免新版本生效后把流量重新打到已知故障的目标上
 pub fn build_runtime(
     snapshot: &Snapshot,
+    previous: Option<&RuntimeConfig>,
     default_tls_pem: &TlsKeyPairPem,
     http_port_range: Option<PortRange>,
     https_port_range: Option<PortRange>,
 ) -> Result<RuntimeConfig> {
     let mut pools = HashMap::new();
     for pool in &snapshot.upstream_pools {
-        let targets: Vec<Arc<TargetRuntime>> = snapshot
-            .upstream_targets
-            .iter()
-            .filter(|t| t.pool_id == pool.id && t.enabled)
-            .cloned()
-            .map(|target| Arc::new(TargetRuntime::new(target, true)))
-            .collect();
+        let existing = previous.and_then(|runtime| runtime.pools.get(&pool.id));
+        let options = pool_options(pool);
+        let source = options
+            .discovery
+            .as_ref()
+            .and_then(|discovery| DiscoverySource::from_options(pool.id, discovery));
+        let targets = match source {
+            // 发现源不变时沿用已解析的目标；新配置的发现源在首次解析完成前沿用池内原有目标
+            Some(source) => PoolTargets::Discovered(
+                existing
+                    .and_then(|pool| match &pool.targets {
+                        PoolTargets::Discovered(discovery) if discovery.source() == &source => {
+                            Some(discovery.clone())
+                        }
+                        _ => None,
+                    })
+                    .unwrap_or_else(|| {
+                        let initial = existing.map(|pool| pool.targets()).unwrap_or_default();
+                        Arc::new(Discovery::new(source, initial))
+                    }),
+            ),
+            None => {
+                let prior_targets = existing.map(|pool| pool.targets()).unwrap_or_default();
+                let mut targets: Vec<Arc<TargetRuntime>> = snapshot
+                    .upstream_targets
+                    .iter()
+                    .filter(|t| t.pool_id == pool.id && t.enabled && t.drained_at.is_none())
+                    .map(|target| {
+                        let prior = prior_targets
+                            .iter()
+                            .find(|t| t.id == target.id && t.address == target.address);
+                        Arc::new(
+                            TargetRuntime::new(
+                                target.id,
+                                target.address.clone(),
+                                target.weight,
+                                prior.map(|t| t.as_ref()),
+                            )
+                            .with_priority(target.priority),
+                        )
+                    })
+                    .collect();
+                // 同一层级的目标相邻，选取时按层级依次尝试
+                targets.sort_by_key(|target| target.priority);
+                PoolTargets::Static(Arc::new(targets))
+            }
+        };
         let policy = PoolPolicy::from_str(&pool.policy).unwrap_or_else(|| {
             warn!("invalid pool policy {} for pool {}", pool.policy, pool.id);
             PoolPolicy::Weighted
@@ -549,11 +1601,28 @@ pub fn build_runtime(
                 cursor: AtomicUsize::new(0),
                 policy,
                 health,
+                settings: Arc::new(PoolSettings::from_pool(pool, &options)),
+                failover_threshold: options
+                    .failover_threshold
+                    .filter(|threshold| (1..=100).contains(threshold))
+                    .unwrap_or(DEFAULT_FAILOVER_THRESHOLD),
             },
         );
     }
 
-    let mut routes_by_listener: HashMap<Uuid, Vec<RouteRule>> = HashMap::new();
+    let listener_options_by_id: HashMap<Uuid, ListenerOptions> = snapshot
+        .listeners
+        .iter()
+        .map(|l| (l.id, listener_options(l)))
+        .collect();
+    let https_listeners: HashSet<Uuid> = snapshot
+        .listeners
+        .iter()
+        .filter(|l| l.protocol.eq_ignore_ascii_case("https"))
+        .map(|l| l.id)
+        .collect();
+
+    let mut rules_by_listener: HashMap<Uuid, Vec<RouteRule>> = HashMap::new();
     for route in &snapshot.routes {
         if !route.enabled {
             continue;
@@ -566,30 +1635,63 @@ pub fn build_runtime(
             }
         };
         let mut matcher = match kind {
-            RouteKind::Port => RouteMatcher::default(),
-            RouteKind::Path | RouteKind::Ws => match RouteMatcher::from_json(&route.match_expr) {
-                Some(matcher) => matcher,
-                None => {
-                    warn!("invalid match_expr for route {}", route.id);
-                    continue;
+            RouteKind::Port | RouteKind::Tcp => RouteMatcher::default(),
+            RouteKind::Path | RouteKind::Ws | RouteKind::Sni => {
+                match RouteMatcher::from_json(&route.match_expr) {
+                    Some(matcher) => matcher,
+                    None => {
+                        warn!("invalid match_expr for route {}", route.id);
+                        continue;
+                    }
                 }
-            },
+            }
         };
         if kind == RouteKind::Ws {
             matcher.enforce_ws();
         }
-        let entry = routes_by_listener.entry(route.listener_id).or_default();
+        let entry = rules_by_listener.entry(route.listener_id).or_default();
+        let options = route_options(route);
         entry.push(RouteRule {
             id: route.id,
             upstream_pool_id: route.upstream_pool_id,
             priority: route.priority,
             matcher,
             kind,
+            error_pages: options
+                .error_pages
+                .as_ref()
+                .map(|pages| Arc::new(ErrorPages::from_options(pages))),
+            maintenance: MaintenanceConfig::from_options(options.maintenance.as_ref()),
+            limits: RequestLimits::from_options(options.limits.as_ref()),
+            websocket: WsSettings::from_options(options.websocket.as_ref()),
+            fault: options
+                .fault
+                .as_ref()
+                .and_then(|fault| FaultInjection::from_options(route.id, fault))
+                .map(Arc::new),
+            ext_auth: options
+                .ext_auth
+                .as_ref()
+                .map(|ext_auth| Arc::new(ExtAuthPolicy::from_options(route.id, ext_auth))),
+            security_headers: SecurityHeaders::resolve(
+                &format!("route {}", route.id),
+                https_listeners.contains(&route.listener_id),
+                &[
+                    listener_options_by_id
+                        .get(&route.listener_id)
+                        .and_then(|options| options.security_headers.as_ref()),
+                    options.security_headers.as_ref(),
+                ],
+            ),
         });
     }
-    for routes in routes_by_listener.values_mut() {
-        routes.sort_by(|a, b| b.priority.cmp(&a.priority));
-    }
+    let routes_by_listener: HashMap<Uuid, ListenerRoutes> = rules_by_listener
+        .into_iter()
+        .map(|(listener_id, mut rules)| {
+            rules.sort_by_key(|rule| std::cmp::Reverse(rule.priority));
+            (listener_id, ListenerRoutes::new(rules))
+        })
+        .collect();
 
     let listeners: Vec<ListenerRuntime> = snapshot
         .listeners
@@ -642,36 +1744,69 @@ pub fn build_runtime(
             }
             true
         })
-        .map(|l| ListenerRuntime {
-            id: l.id,
-            port: l.port,
-            protocol: l.protocol.clone(),
+        .map(|l| {
+            let default_options = ListenerOptions::default();
+            let options = listener_options_by_id
+                .get(&l.id)
+                .unwrap_or(&default_options);
+            ListenerRuntime {
+                id: l.id,
+                port: l.port,
+                protocol: l.protocol.clone(),
+                request_id: RequestIdConfig::from_options(options.request_id.as_ref()),
+                forwarded: ForwardedConfig::from_options(options.forwarded.as_ref()),
+                proxy_protocol: listener_proxy_protocol(l, options),
+                http2: Http2Config::from_options(l, options.http2.as_ref()),
+                error_pages: options
+                    .error_pages
+                    .as_ref()
+                    .map(|pages| Arc::new(ErrorPages::from_options(pages))),
+                maintenance: MaintenanceConfig::from_options(options.maintenance.as_ref()),
+                limits: RequestLimits::from_options(options.limits.as_ref()),
+                security_headers: SecurityHeaders::resolve(
+                    &format!("listener {}", l.id),
+                    l.protocol.eq_ignore_ascii_case("https"),
+                    &[options.security_headers.as_ref()],
+                ),
+            }
         })
         .collect();
 
-    let mut listeners_by_port: HashMap<u16, ListenerRuntime> = HashMap::new();
+    let mut listeners_by_port: HashMap<u16, Arc<ListenerRuntime>> = HashMap::new();
     for listener in &listeners {
         if (1..=65535).contains(&listener.port) {
-            listeners_by_port.insert(listener.port as u16, listener.clone());
+            listeners_by_port.insert(listener.port as u16, Arc::new(listener.clone()));
         }
     }
 
     let default_tls = Arc::new(parse_tls_keypair(default_tls_pem)?);
     let mut tls_by_port: HashMap<u16, Arc<TlsKeyPair>> = HashMap::new();
+    let mut alpn_by_port: HashMap<u16, Vec<u8>> = HashMap::new();
+    let mut client_ca_by_port: HashMap<u16, Arc<Vec<X509>>> = HashMap::new();
     if let Some(range) = https_port_range {
         for port in range.iter() {
-            let pair = match listeners_by_port.get(&port) {
+            let policy_id = match listeners_by_port.get(&port) {
                 Some(listener) if listener.protocol.eq_ignore_ascii_case("https") => snapshot
                     .listeners
                     .iter()
                     .find(|l| l.id == listener.id)
-                    .and_then(|l| l.tls_policy_id)
-                    .and_then(|id| crate::tls::tls_pem_for_policy(snapshot, id))
-                    .and_then(|pem| parse_tls_keypair(&pem).ok().map(Arc::new))
-                    .unwrap_or_else(|| default_tls.clone()),
-                _ => default_tls.clone(),
+                    .and_then(|l| l.tls_policy_id),
+                _ => None,
             };
+            let pair = policy_id
+                .and_then(|id| crate::tls::tls_pem_for_policy(snapshot, id))
+                .and_then(|pem| parse_tls_keypair(&pem).ok().map(Arc::new))
+                .unwrap_or_else(|| default_tls.clone());
             tls_by_port.insert(port, pair);
+            if let Some(policy_id) = policy_id {
+                insert_policy_options(
+                    snapshot,
+                    port,
+                    policy_id,
+                    &mut alpn_by_port,
+                    &mut client_ca_by_port,
+                );
+            }
         }
     } else {
         for listener in &snapshot.listeners {
@@ -688,6 +1823,15 @@ pub fn build_runtime(
                 .and_then(|pem| parse_tls_keypair(&pem).ok().map(Arc::new))
                 .unwrap_or_else(|| default_tls.clone());
             tls_by_port.insert(port, pair);
+            if let Some(policy_id) = listener.tls_policy_id {
+                insert_policy_options(
+                    snapshot,
+                    port,
+                    policy_id,
+                    &mut alpn_by_port,
+                    &mut client_ca_by_port,
+                );
+            }
         }
     }
 
@@ -695,31 +1839,468 @@ pub fn build_runtime(
         listeners,
         listeners_by_port,
         tls_by_port,
+        alpn_by_port,
+        client_ca_by_port,
         routes_by_listener,
         pools,
     })
 }
 
+/// 选中的上游对端、对应的目标与连接池设置
+pub type PickedPeer = (Box<HttpPeer>, Option<Arc<TargetRuntime>>, Arc<PoolSettings>);
+
 impl RuntimeConfig {
-    pub fn pick_peer(&self, pool_id: Uuid) -> Option<(Box<HttpPeer>, Option<Arc<TargetRuntime>>)> {
+    pub fn pick_peer(&self, pool_id: Uuid) -> Option<PickedPeer> {
         let pool = self.pools.get(&pool_id)?;
         let target = pool.pick()?;
-        let peer = Box::new(HttpPeer::new(
-            target.target.address.clone(),
-            false,
-            String::new(),
-        ));
-        Some((peer, Some(target)))
+        let settings = pool.settings.clone();
+        let address = target.address.clone();
+        let tls = settings.protocol == UpstreamProtocol::H2;
+        let socket_path = unix_socket_path(&address);
+        let sni = match (tls, socket_path) {
+            (false, _) => String::new(),
+            // unix 套接字没有主机名可作缺省 SNI
+            (true, Some(_)) => settings.sni.clone().unwrap_or_default(),
+            (true, None) => settings
+                .sni
+                .clone()
+                .unwrap_or_else(|| address_host(&address).to_string()),
+        };
+        let peer = match socket_path {
+            Some(path) => HttpPeer::new_uds(path, tls, sni),
+            // `unix:` 前缀但路径不合法（相对路径或超出 sun_path 长度），不能按 host:port 解析
+            None if address.starts_with("unix:") => {
+                Err(Error::explain(ErrorType::SocketError, "invalid path"))
+            }
+            None => Ok(HttpPeer::new(address, tls, sni)),
+        };
+        let peer = match peer {
+            Ok(peer) => peer,
+            Err(err) => {
+                warn!("invalid unix socket target {}: {}", target.address, err);
+                target.release();
+                return None;
+            }
+        };
+        let mut peer = Box::new(peer);
+        if settings.protocol != UpstreamProtocol::Http1 {
+            // 仅协商 h2；非 TLS 时即为 h2c prior knowledge
+            peer.options.alpn = ALPN::H2;
+        }
+        peer.options.idle_timeout = Some(settings.keepalive.idle_timeout);
+        Some((peer, Some(target), settings))
+    }
+
+    /// 在上游池内按池策略选取目标；调用方用完后须调用 `TargetRuntime::release`
+    pub fn pick_target(&self, pool_id: Uuid) -> Option<(Arc<TargetRuntime>, Arc<PoolSettings>)> {
+        let pool = self.pools.get(&pool_id)?;
+        Some((pool.pick()?, pool.settings.clone()))
+    }
+
+    /// L4 转发选取目标：按优先级取监听器上第一条匹配（tcp 或按 SNI 匹配的 sni）且可用的路由
+    pub fn pick_stream_target(
+        &self,
+        listener_id: Uuid,
+        sni: Option<&str>,
+    ) -> Option<(Arc<TargetRuntime>, Option<ProxyProtocolVersion>)> {
+        self.routes_by_listener
+            .get(&listener_id)?
+            .rules()
+            .iter()
+            .filter(|route| route.matches_stream(sni))
+            .find_map(|route| {
+                let pool = self.pools.get(&route.upstream_pool_id)?;
+                Some((pool.pick()?, pool.settings.proxy_protocol))
+            })
+    }
+
+    /// 路由仍存在且其上游池仍包含该目标
+    pub fn serves(&self, route_id: Uuid, address: &str) -> bool {
+        self.routes_by_listener
+            .values()
+            .flat_map(|routes| routes.rules())
+            .find(|route| route.id == route_id)
+            .and_then(|route| self.pools.get(&route.upstream_pool_id))
+            .is_some_and(|pool| pool.targets().iter().any(|t| t.address() == address))
+    }
+
+    /// 本配置中可分配请求、但在新配置中已被移除、禁用或摘流的目标，及其所属池的摘流超时
+    fn removed_targets(&self, next: &RuntimeConfig) -> Vec<(Uuid, Arc<TargetRuntime>, Duration)> {
+        let mut removed = Vec::new();
+        for (pool_id, pool) in &self.pools {
+            let kept = next.pools.get(pool_id).map(|pool| pool.targets());
+            for target in pool.targets().iter() {
+                let still_served = kept.as_ref().is_some_and(|kept| {
+                    kept.iter()
+                        .any(|t| t.id == target.id && t.address == target.address)
+                });
+                if !still_served {
+                    removed.push((*pool_id, target.clone(), pool.settings.drain_timeout));
+                }
+            }
+        }
+        removed
     }
 
     pub fn health_pools(&self) -> Vec<(Uuid, PoolHealthCheck, Vec<Arc<TargetRuntime>>)> {
         self.pools
             .iter()
-            .map(|(id, pool)| (*id, pool.health.clone(), pool.targets.clone()))
+            .map(|(id, pool)| (*id, pool.health.clone(), pool.targets().to_vec()))
+            .collect()
+    }
+
+    /// 使用服务发现的池及其摘流超时
+    pub fn discovery_pools(&self) -> Vec<(Uuid, Arc<Discovery>, Duration)> {
+        self.pools
+            .iter()
+            .filter_map(|(id, pool)| match &pool.targets {
+                PoolTargets::Discovered(discovery) => {
+                    Some((*id, discovery.clone(), pool.settings.drain_timeout))
+                }
+                PoolTargets::Static(_) => None,
+            })
             .collect()
     }
 }
 
+/// https 端口按 TLS 策略配置的 ALPN 与客户端证书 CA
+fn insert_policy_options(
+    snapshot: &Snapshot,
+    port: u16,
+    policy_id: Uuid,
+    alpn_by_port: &mut HashMap<u16, Vec<u8>>,
+    client_ca_by_port: &mut HashMap<u16, Arc<Vec<X509>>>,
+) {
+    if let Some(alpn) = crate::tls::alpn_for_policy(snapshot, policy_id) {
+        alpn_by_port.insert(port, alpn);
+    }
+    if let Some(cas) = crate::tls::client_ca_for_policy(snapshot, policy_id) {
+        client_ca_by_port.insert(port, Arc::new(cas));
+    }
+}
+
+fn route_options(route: &Route) -> RouteOptions {
+    let Some(value) = &route.options else {
+        return RouteOptions::default();
+    };
+    serde_json::from_value(value.clone()).unwrap_or_else(|err| {
+        warn!("invalid options for route {}: {}", route.id, err);
+        RouteOptions::default()
+    })
+}
+
+fn listener_options(listener: &Listener) -> ListenerOptions {
+    let Some(value) = &listener.options else {
+        return ListenerOptions::default();
+    };
+    serde_json::from_value(value.clone()).unwrap_or_else(|err| {
+        warn!("invalid options for listener {}: {}", listener.id, err);
+        ListenerOptions::default()
+    })
+}
+
+fn listener_proxy_protocol(listener: &Listener, options: &ListenerOptions) -> ProxyProtocolConfig {
+    let Some(mode) = options.proxy_protocol.as_deref() else {
+        return ProxyProtocolConfig::default();
+    };
+    let mode = ProxyProtocolMode::from_str(mode).unwrap_or_else(|| {
+        warn!(
+            "invalid proxy_protocol {} for listener {}, disabled",
+            mode, listener.id
+        );
+        ProxyProtocolMode::Off
+    });
+    ProxyProtocolConfig {
+        mode,
+        trusted_cidrs: options
+            .proxy_protocol_trusted_cidrs
+            .iter()
+            .filter_map(|cidr| IpCidr::parse(cidr))
+            .collect(),
+    }
+}
+
+fn pool_options(pool: &UpstreamPool) -> PoolOptions {
+    let Some(value) = &pool.options else {
+        return PoolOptions::default();
+    };
+    serde_json::from_value(value.clone()).unwrap_or_else(|err| {
+        warn!("invalid options for upstream pool {}: {}", pool.id, err);
+        PoolOptions::default()
+    })
+}
+
+/// 响应携带 `Connection: close`，连接不会被复用
+fn connection_close(headers: &http::HeaderMap) -> bool {
+    headers
+        .get_all(http::header::CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|token| token.trim().eq_ignore_ascii_case("close"))
+}
+
+/// 目标地址中的主机部分（`host:port` 或 `[v6]:port`）
+fn address_host(address: &str) -> &str {
+    if let Some(rest) = address.strip_prefix('[') {
+        return rest.split(']').next().unwrap_or(rest);
+    }
+    address
+        .rsplit_once(':')
+        .map(|(host, _)| host)
+        .unwrap_or(address)
+}
+
+/// 发往上游的 PROXY 头：源为客户端地址，目的为网关监听地址
+fn upstream_proxy_header(
+    version: ProxyProtocolVersion,
+    src: Option<SocketAddr>,
+    dst: Option<SocketAddr>,
+) -> Result<Vec<u8>, Box<pingora::Error>> {
+    let (Some(src), Some(dst)) = (src, dst) else {
+        return Err(Error::explain(
+            ErrorType::InternalError,
+            "missing address for PROXY protocol header",
+        ));
+    };
+    Ok(encode_header(version, src, dst))
+}
+
+/// 发送 PROXY 头的上游连接按客户端 IP 分组复用：同一 IP 的不同连接（端口）共享空闲连接，
+/// 复用连接上的 PROXY 头保留建立连接时的客户端端口
+fn proxy_group_key(peer: Option<SocketAddr>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    peer.map(|addr| addr.ip()).hash(&mut hasher);
+    hasher.finish()
+}
+
+fn downstream_port(session: &Session) -> Option<u16> {
+    session
+        .as_downstream()
+        .server_addr()
+        .and_then(|addr| addr.as_inet().map(|inet| inet.port()))
+}
+
+/// 请求的 Host 头，缺失时回退到 URI authority（HTTP/2 的 `:authority`）
+fn request_host(header: &RequestHeader) -> Option<&str> {
+    header
+        .headers
+        .get("host")
+        .and_then(|v| v.to_str().ok())
+        .or_else(|| header.uri.authority().map(|a| a.as_str()))
+        .filter(|host| !host.is_empty())
+}
+
+/// 按路由的故障注入策略延迟请求，或以错误中止请求（状态码由 fail_to_proxy 写出，重置时不响应）
+async fn inject_fault(
+    route_id: Uuid,
+    fault: &FaultInjection,
+    header: &RequestHeader,
+    ctx: &RequestCtx,
+) -> Result<(), Box<pingora::Error>> {
+    let Some(fault) = fault.sample(header) else {
+        return Ok(());
+    };
+    let route_label = route_id.to_string();
+    if let Some(delay) = fault.delay {
+        debug!(request_id = %ctx.request_id, "fault injection delay {:?}", delay);
+        crate::metrics::inc_fault_injected(&route_label, "delay");
+        tokio::time::sleep(delay).await;
+    }
+    match fault.abort {
+        Some(FaultAbort::Status(status)) => {
+            debug!(request_id = %ctx.request_id, "fault injection abort {}", status);
+            crate::metrics::inc_fault_injected(&route_label, "abort");
+            Err(Error::explain(
+                ErrorType::HTTPStatus(status),
+                "fault injection abort",
+            ))
+        }
+        Some(FaultAbort::Reset) => {
+            debug!(request_id = %ctx.request_id, "fault injection reset");
+            crate::metrics::inc_fault_injected(&route_label, "reset");
+            Err(Error::create(
+                ErrorType::ConnectionClosed,
+                ErrorSource::Downstream,
+                Some("fault injection reset".into()),
+                None,
+            ))
+        }
+        None => Ok(()),
+    }
+}
+
+/// 与 Pingora 默认行为一致：由错误类型与来源推导返回给客户端的状态码，0 表示不响应
+fn error_status_code(e: &Error) -> u16 {
+    match e.etype() {
+        ErrorType::HTTPStatus(code) => *code,
+        _ => match e.esource() {
+            ErrorSource::Upstream => 502,
+            ErrorSource::Downstream => match e.etype() {
+                ErrorType::WriteError | ErrorType::ReadError | ErrorType::ConnectionClosed => 0,
+                _ => 400,
+            },
+            ErrorSource::Internal | ErrorSource::Unset => 500,
+        },
+    }
+}
+
+/// 写出网关自身生成的响应，并附带请求 ID 头
+async fn write_local_response(
+    session: &mut Session,
+    ctx: &RequestCtx,
+    status: u16,
+    content_type: &str,
+    body: Bytes,
+) -> Result<(), Box<pingora::Error>> {
+    let mut header = ResponseHeader::build(status, Some(4))?;
+    header.insert_header("content-type", content_type)?;
+    header.insert_header("content-length", body.len().to_string())?;
+    if !ctx.request_id.is_empty() {
+        header.insert_header(ctx.request_id_header.clone(), ctx.request_id.as_str())?;
+    }
+    if let Some(retry_after) = ctx.retry_after {
+        header.insert_header("retry-after", retry_after.to_string())?;
+    }
+    if let Some(security_headers) = &ctx.security_headers {
+        security_headers.apply(&mut header)?;
+    }
+    session
+        .write_response_header(Box::new(header), false)
+        .await?;
+    session.write_response_body(Some(body), true).await?;
+    Ok(())
+}
+
+/// 按 gRPC 状态重试须把请求体完整重放给下一个目标：请求体须已读完、未超出重试缓冲（64 KiB），
+/// 且不是客户端流式调用
+fn grpc_replayable(session: &mut Session, ctx: &RequestCtx) -> bool {
+    let downstream = session.as_mut();
+    downstream.is_body_done()
+        && !downstream.retry_buffer_truncated()
+        && ctx.grpc_messages.messages() <= 1
+}
+
+/// 原样写出授权服务的拒绝响应，并附带请求 ID 头
+async fn write_denied_response(
+    session: &mut Session,
+    ctx: &RequestCtx,
+    denied: &DeniedResponse,
+) -> Result<(), Box<pingora::Error>> {
+    let mut header = ResponseHeader::build(denied.status, Some(denied.headers.len() + 2))?;
+    for (name, value) in &denied.headers {
+        header.append_header(name.clone(), value.clone())?;
+    }
+    header.insert_header("content-length", denied.body.len().to_string())?;
+    if !ctx.request_id.is_empty() {
+        header.insert_header(ctx.request_id_header.clone(), ctx.request_id.as_str())?;
+    }
+    if let Some(security_headers) = &ctx.security_headers {
+        security_headers.apply(&mut header)?;
+    }
+    session
+        .write_response_header(Box::new(header), false)
+        .await?;
+    session
+        .write_response_body(Some(denied.body.clone()), true)
+        .await?;
+    Ok(())
+}
+
+async fn write_error_response(
+    session: &mut Session,
+    ctx: &RequestCtx,
+    status: u16,
+) -> Result<(), Box<pingora::Error>> {
+    let reason = StatusCode::from_u16(status)
+        .ok()
+        .and_then(|code| code.canonical_reason())
+        .unwrap_or("Error");
+    if ctx.grpc {
+        // gRPC 客户端无法解析 HTML/文本错误，改为在 trailers-only 响应中返回 grpc-status
+        let mut header =
+            crate::grpc::error_response(crate::grpc::status_from_http(status), reason)?;
+        if !ctx.request_id.is_empty() {
+            header.insert_header(ctx.request_id_header.clone(), ctx.request_id.as_str())?;
+        }
+        if let Some(retry_after) = ctx.retry_after {
+            header.insert_header("retry-after", retry_after.to_string())?;
+        }
+        if let Some(security_headers) = &ctx.security_headers {
+            security_headers.apply(&mut header)?;
+        }
+        session
+            .write_response_header(Box::new(header), true)
+            .await?;
+        return Ok(());
+    }
+    let accept = session
+        .req_header()
+        .headers
+        .get("accept")
+        .and_then(|v| v.to_str().ok());
+    let listener_pages = ctx
+        .listener
+        .as_ref()
+        .and_then(|listener| listener.error_pages.as_deref());
+    let rendered = crate::error_page::render_layered(
+        ctx.route_error_pages.as_deref(),
+        listener_pages,
+        status,
+        reason,
+        &ctx.request_id,
+        accept,
+    );
+    if let Some(rendered) = rendered {
+        return write_local_response(session, ctx, status, &rendered.content_type, rendered.body)
+            .await;
+    }
+
+    let body = if ctx.request_id.is_empty() {
+        format!("{} {}\n", status, reason)
+    } else {
+        format!("{} {}\nrequest-id: {}\n", status, reason, ctx.request_id)
+    };
+    write_local_response(
+        session,
+        ctx,
+        status,
+        "text/plain; charset=utf-8",
+        Bytes::from(body),
+    )
+    .await
+}
+
+/// 维护模式的 503 响应：配置了提示文本时直接返回（gRPC 请求除外），否则按错误页渲染
+async fn write_maintenance_response(
+    session: &mut Session,
+    ctx: &mut RequestCtx,
+    maintenance: &MaintenanceConfig,
+) -> Result<(), Box<pingora::Error>> {
+    ctx.retry_after = maintenance.retry_after;
+    match &maintenance.message {
+        Some(message) if !ctx.grpc => {
+            let body = Bytes::from(format!("{}\n", message));
+            write_local_response(session, ctx, 503, "text/plain; charset=utf-8", body).await
+        }
+        _ => write_error_response(session, ctx, 503).await,
+    }
+}
+
+/// 编译头 / 查询参数 / Cookie 条件，任一条件无效时返回 None（整条路由视为无效）
+fn compile_value_matchers(
+    values: Option<HashMap<String, ValueMatch>>,
+) -> Option<Option<Vec<(String, ValueMatcher)>>> {
+    let Some(values) = values else {
+        return Some(None);
+    };
+    values
+        .into_iter()
+        .map(|(key, value)| Some((key, ValueMatcher::compile(&value)?)))
+        .collect::<Option<Vec<_>>>()
+        .map(Some)
+}
+
 fn parse_query(query: &str) -> HashMap<String, String> {
     let mut out = HashMap::new();
     for pair in query.split('&') {
@@ -752,15 +2333,28 @@ fn is_ws_request(header: &RequestHeader) -> bool {
     connection.to_ascii_lowercase().contains("upgrade")
 }
 
-pub async fn apply_snapshot(
-    runtime: &Arc<RwLock<RuntimeConfig>>,
+/// 在旧配置之外构建新配置后原子替换；构建期间与替换之后进行中的请求都不会被阻塞，
+/// 已开始的请求继续使用其开始时的配置
+pub fn apply_snapshot(
+    runtime: &ArcSwap<RuntimeConfig>,
     snapshot: &Snapshot,
     default_tls_pem: &TlsKeyPairPem,
     http_port_range: Option<PortRange>,
     https_port_range: Option<PortRange>,
 ) -> Result<()> {
-    let new_runtime = build_runtime(snapshot, default_tls_pem, http_port_range, https_port_range)?;
-    *runtime.write().await = new_runtime;
+    let previous = runtime.load_full();
+    let new_runtime = build_runtime(
+        snapshot,
+        Some(previous.as_ref()),
+        default_tls_pem,
+        http_port_range,
+        https_port_range,
+    )?;
+    crate::ws::drain(|route_id, target| new_runtime.serves(route_id, target));
+    for (pool_id, target, timeout) in previous.removed_targets(&new_runtime) {
+        crate::drain::start(pool_id, target, timeout);
+    }
+    runtime.store(Arc::new(new_runtime));
     Ok(())
 }
 
@@ -776,31 +2370,102 @@ fn parse_tls_keypair(pem: &TlsKeyPairPem) -> Result<TlsKeyPair> {
 }
 
 pub struct TargetRuntime {
-    target: UpstreamTarget,
-    healthy: AtomicBool,
-    inflight: AtomicUsize,
+    id: Uuid,
+    address: String,
+    weight: usize,
+    /// 故障转移层级，数值越小越优先；服务发现得到的目标均为 0
+    priority: i32,
+    /// 健康状态与进行中请求计数在配置版本之间共享：旧版本上仍在处理的请求结束时，
+    /// 递减的是新版本看到的同一个计数
+    healthy: Arc<AtomicBool>,
+    inflight: Arc<AtomicUsize>,
+    /// 目标不再分配新请求后，进行中请求须在此之前完成
+    drain_deadline: OnceLock<Instant>,
 }
 
 impl TargetRuntime {
-    fn new(target: UpstreamTarget, healthy: bool) -> Self {
+    pub fn new(id: Uuid, address: String, weight: i32, previous: Option<&TargetRuntime>) -> Self {
+        let (healthy, inflight) = match previous {
+            Some(previous) => (previous.healthy.clone(), previous.inflight.clone()),
+            None => (
+                Arc::new(AtomicBool::new(true)),
+                Arc::new(AtomicUsize::new(0)),
+            ),
+        };
         Self {
-            target,
-            healthy: AtomicBool::new(healthy),
-            inflight: AtomicUsize::new(0),
+            id,
+            address,
+            weight: weight.max(1) as usize,
+            priority: 0,
+            healthy,
+            inflight,
+            drain_deadline: OnceLock::new(),
         }
     }
 
+    fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
     fn weight(&self) -> usize {
-        self.target.weight.max(1) as usize
+        self.weight
     }
 
     pub fn address(&self) -> &str {
-        &self.target.address
+        &self.address
     }
 
     pub fn set_healthy(&self, healthy: bool) {
         self.healthy.store(healthy, Ordering::Relaxed);
     }
+
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    pub fn inflight(&self) -> usize {
+        self.inflight.load(Ordering::Relaxed)
+    }
+
+    /// 选中目标，计入一个进行中请求
+    pub fn acquire(&self) {
+        self.inflight.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 请求结束，递减选取目标时计入的进行中请求
+    pub fn release(&self) {
+        self.inflight.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn start_drain(&self, deadline: Instant) {
+        let _ = self.drain_deadline.set(deadline);
+    }
+
+    pub fn drain_deadline(&self) -> Option<Instant> {
+        self.drain_deadline.get().copied()
+    }
+
+    fn drain_expired(&self) -> bool {
+        self.drain_deadline
+            .get()
+            .is_some_and(|deadline| Instant::now() >= *deadline)
+    }
+}
+
+/// 目标摘流超时后中断仍在转发的响应；已升级的 WS 连接由 ws 模块单独排空
+fn check_drain(ctx: &RequestCtx) -> Result<(), Box<pingora::Error>> {
+    if !ctx.upgraded && ctx.target.as_ref().is_some_and(|t| t.drain_expired()) {
+        return Err(Error::explain(
+            ErrorType::ConnectionClosed,
+            "upstream target drain timeout",
+        ));
+    }
+    Ok(())
 }
 
 fn acme_token_from_path(path: &str) -> Option<String> {
@@ -837,3 +2502,347 @@ impl AcmeChallengeClient {
         Some(body.key_auth)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(value: &str) -> SocketAddr {
+        value.parse().unwrap()
+    }
+
+    fn pool_json(id: Uuid, options: serde_json::Value) -> serde_json::Value {
+        serde_json::json!({
+            "id": id,
+            "name": "pool",
+            "policy": "round_robin",
+            "health_check": null,
+            "options": options,
+            "created_at": "2026-01-01T00:00:00Z",
+            "updated_at": "2026-01-01T00:00:00Z",
+        })
+    }
+
+    fn target_json(id: Uuid, pool_id: Uuid, address: &str, priority: i32) -> serde_json::Value {
+        serde_json::json!({
+            "id": id,
+            "pool_id": pool_id,
+            "address": address,
+            "weight": 1,
+            "enabled": true,
+            "priority": priority,
+            "drained_at": null,
+            "created_at": "2026-01-01T00:00:00Z",
+            "updated_at": "2026-01-01T00:00:00Z",
+        })
+    }
+
+    fn runtime(
+        pools: Vec<serde_json::Value>,
+        targets: Vec<serde_json::Value>,
+        previous: Option<&RuntimeConfig>,
+    ) -> RuntimeConfig {
+        let snapshot: Snapshot = serde_json::from_value(serde_json::json!({
+            "listeners": [],
+            "routes": [],
+            "upstream_pools": pools,
+            "upstream_targets": targets,
+            "tls_policies": [],
+            "certificates": [],
+        }))
+        .unwrap();
+        let pem = crate::tls::default_tls_pem(std::path::Path::new("/nonexistent")).unwrap();
+        build_runtime(&snapshot, previous, &pem, None, None).unwrap()
+    }
+
+    fn pool_target(runtime: &RuntimeConfig, pool_id: Uuid, id: Uuid) -> Arc<TargetRuntime> {
+        runtime.pools[&pool_id]
+            .targets()
+            .iter()
+            .find(|target| target.id == id)
+            .cloned()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_upstream_proxy_header() {
+        let src = Some(addr("203.0.113.7:51000"));
+        let dst = Some(addr("10.0.0.1:8080"));
+
+        let header = upstream_proxy_header(ProxyProtocolVersion::V1, src, dst).unwrap();
+        assert_eq!(header, b"PROXY TCP4 203.0.113.7 10.0.0.1 51000 8080\r\n");
+        assert!(upstream_proxy_header(ProxyProtocolVersion::V1, None, dst).is_err());
+        assert!(upstream_proxy_header(ProxyProtocolVersion::V2, src, None).is_err());
+    }
+
+    #[test]
+    fn test_proxy_group_key_by_client_ip() {
+        let key = proxy_group_key(Some(addr("203.0.113.7:51000")));
+        assert_eq!(
+            key,
+            proxy_group_key(Some(addr("203.0.113.7:51001"))),
+            "同一客户端 IP 共享上游连接"
+        );
+        assert_ne!(key, proxy_group_key(Some(addr("203.0.113.8:51000"))));
+        assert_ne!(key, proxy_group_key(None));
+    }
+
+    #[test]
+    fn test_request_id_resolve() {
+        let config = RequestIdConfig::from_options(Some(&RequestIdOptions {
+            header: Some("X-Trace-Id".to_string()),
+            trusted_cidrs: vec!["10.0.0.0/8".to_string()],
+        }));
+        let trusted = Some("10.1.2.3".parse().unwrap());
+        let request = |value: &str| {
+            let mut header = RequestHeader::build("GET", b"/", None).unwrap();
+            header.insert_header("x-trace-id", value).unwrap();
+            header
+        };
+
+        assert_eq!(
+            config.resolve(&request("abc-123"), trusted),
+            "abc-123",
+            "可信客户端的合法请求 ID 原样沿用"
+        );
+        for invalid in ["has space", &"a".repeat(129), ""] {
+            let id = config.resolve(&request(invalid), trusted);
+            assert!(
+                Uuid::parse_str(&id).is_ok(),
+                "非法请求 ID {:?} 被替换",
+                invalid
+            );
+        }
+        assert_eq!(
+            config.resolve(&request(&"a".repeat(128)), trusted).len(),
+            128
+        );
+        let id = config.resolve(&request("abc-123"), Some("192.168.0.1".parse().unwrap()));
+        assert_ne!(id, "abc-123", "不可信客户端的请求 ID 被替换");
+        assert_ne!(config.resolve(&request("abc-123"), None), "abc-123");
+    }
+
+    #[test]
+    fn test_request_id_generate_only() {
+        let config = RequestIdConfig::from_options(Some(&RequestIdOptions {
+            header: None,
+            trusted_cidrs: Vec::new(),
+        }));
+        assert_eq!(config.header, DEFAULT_REQUEST_ID_HEADER);
+        let mut header = RequestHeader::build("GET", b"/", None).unwrap();
+        header.insert_header("x-request-id", "abc-123").unwrap();
+        let first = config.resolve(&header, Some("127.0.0.1".parse().unwrap()));
+        let second = config.resolve(&header, Some("127.0.0.1".parse().unwrap()));
+        assert!(Uuid::parse_str(&first).is_ok(), "未配置可信网段时总是生成");
+        assert_ne!(first, second, "每个请求生成新的 ID");
+    }
+
+    #[test]
+    fn test_pick_peer_unix_socket() {
+        let pool_id = Uuid::new_v4();
+        let valid = Uuid::new_v4();
+        let rt = runtime(
+            vec![pool_json(pool_id, serde_json::json!(null))],
+            vec![target_json(valid, pool_id, "unix:/run/app.sock", 0)],
+            None,
+        );
+        let (peer, target, _) = rt.pick_peer(pool_id).unwrap();
+        let path = peer._address.as_unix().and_then(|addr| addr.as_pathname());
+        assert_eq!(path, Some(std::path::Path::new("/run/app.sock")));
+        let target = target.unwrap();
+        assert_eq!(target.inflight.load(Ordering::Relaxed), 1);
+        target.release();
+        assert_eq!(target.inflight.load(Ordering::Relaxed), 0);
+
+        // 超出 sun_path 长度或含 NUL 的路径无法构建对端
+        for address in [
+            format!("unix:/{}", "a".repeat(200)),
+            "unix:/run/a\0b".to_string(),
+        ] {
+            let pool_id = Uuid::new_v4();
+            let invalid = Uuid::new_v4();
+            let rt = runtime(
+                vec![pool_json(pool_id, serde_json::json!(null))],
+                vec![target_json(invalid, pool_id, &address, 0)],
+                None,
+            );
+            assert!(rt.pick_peer(pool_id).is_none(), "{:?}", address);
+            assert_eq!(
+                pool_target(&rt, pool_id, invalid)
+                    .inflight
+                    .load(Ordering::Relaxed),
+                0,
+                "选取失败时归还进行中计数"
+            );
+        }
+    }
+
+    #[test]
+    fn test_route_matches_source_and_client_cert() {
+        let matcher = RouteMatcher::from_json(&serde_json::json!({
+            "source_cidrs": ["10.0.0.0/8"],
+            "client_cert_subject": { "prefix": "CN=partner," },
+        }))
+        .unwrap();
+        let header = RequestHeader::build("GET", b"/", None).unwrap();
+        let input = |ip: &str, subject: Option<&str>| MatchInput {
+            host: String::new(),
+            client_ip: Some(ip.parse().unwrap()),
+            client_cert_subject: subject.map(str::to_string),
+        };
+
+        assert!(matcher.matches(&header, &input("10.1.2.3", Some("CN=partner,O=Example"))));
+        assert!(
+            !matcher.matches(&header, &input("192.0.2.1", Some("CN=partner,O=Example"))),
+            "来源不在网段内"
+        );
+        assert!(
+            !matcher.matches(&header, &input("10.1.2.3", Some("CN=other,O=Example"))),
+            "证书主题不匹配"
+        );
+        assert!(
+            !matcher.matches(&header, &input("10.1.2.3", None)),
+            "未提供客户端证书"
+        );
+    }
+
+    #[test]
+    fn test_target_counters_across_versions() {
+        let pool_id = Uuid::new_v4();
+        let (kept, moved) = (Uuid::new_v4(), Uuid::new_v4());
+        let pools = vec![pool_json(pool_id, serde_json::json!(null))];
+        let first = runtime(
+            pools.clone(),
+            vec![
+                target_json(kept, pool_id, "10.0.0.1:80", 0),
+                target_json(moved, pool_id, "10.0.0.2:80", 0),
+            ],
+            None,
+        );
+        let old_kept = pool_target(&first, pool_id, kept);
+        let old_moved = pool_target(&first, pool_id, moved);
+        old_kept.acquire();
+        old_kept.acquire();
+        old_kept.set_healthy(false);
+        old_moved.acquire();
+        old_moved.set_healthy(false);
+
+        let second = runtime(
+            pools,
+            vec![
+                target_json(kept, pool_id, "10.0.0.1:80", 0),
+                target_json(moved, pool_id, "10.0.0.3:80", 0),
+            ],
+            Some(&first),
+        );
+        let new_kept = pool_target(&second, pool_id, kept);
+        assert_eq!(new_kept.inflight(), 2, "同一目标沿用进行中请求计数");
+        assert!(!new_kept.is_healthy(), "沿用健康状态");
+        old_kept.release();
+        assert_eq!(new_kept.inflight(), 1, "旧版本上结束的请求递减同一计数");
+
+        let new_moved = pool_target(&second, pool_id, moved);
+        assert_eq!(new_moved.address(), "10.0.0.3:80");
+        assert_eq!(new_moved.inflight(), 0, "地址变化后重新计数");
+        assert!(new_moved.is_healthy(), "地址变化后视为健康");
+
+        let removed = first.removed_targets(&second);
+        assert_eq!(removed.len(), 1, "仅地址变化的目标需要摘流");
+        assert!(Arc::ptr_eq(&removed[0].1, &old_moved));
+        assert_eq!(removed[0].0, pool_id);
+    }
+
+    fn candidate_ids(pool: &PoolRuntime) -> Option<Vec<Uuid>> {
+        pool.failover_candidates(&pool.targets())
+            .map(|targets| targets.iter().map(|target| target.id).collect())
+    }
+
+    #[test]
+    fn test_failover_candidates() {
+        let pool_id = Uuid::new_v4();
+        let ids: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+        let targets = vec![
+            target_json(ids[0], pool_id, "10.0.2.1:80", 0),
+            target_json(ids[1], pool_id, "10.0.2.2:80", 0),
+            target_json(ids[2], pool_id, "10.0.2.3:80", 1),
+            target_json(ids[3], pool_id, "10.0.2.4:80", 1),
+            target_json(ids[4], pool_id, "10.0.2.5:80", 2),
+        ];
+        let config = runtime(
+            vec![pool_json(
+                pool_id,
+                serde_json::json!({ "failover_threshold": 80 }),
+            )],
+            targets,
+            None,
+        );
+        let pool = &config.pools[&pool_id];
+        let target = |i: usize| pool_target(&config, pool_id, ids[i]);
+
+        assert_eq!(
+            candidate_ids(pool),
+            Some(vec![ids[0], ids[1]]),
+            "主层级健康时只用主层级"
+        );
+
+        target(1).set_healthy(false);
+        assert_eq!(
+            candidate_ids(pool),
+            Some(vec![ids[0], ids[2], ids[3]]),
+            "低于阈值时加入下一层级"
+        );
+
+        target(0).set_healthy(false);
+        target(2).set_healthy(false);
+        assert_eq!(
+            candidate_ids(pool),
+            Some(vec![ids[3], ids[4]]),
+            "逐层加入直到达到阈值"
+        );
+
+        target(3).set_healthy(false);
+        target(4).set_healthy(false);
+        assert_eq!(
+            candidate_ids(pool),
+            Some(vec![ids[0], ids[1]]),
+            "全部不健康时只用主层级"
+        );
+    }
+
+    #[test]
+    fn test_failover_threshold_boundary() {
+        let pool_id = Uuid::new_v4();
+        let ids: Vec<Uuid> = (0..3).map(|_| Uuid::new_v4()).collect();
+        let config = runtime(
+            vec![pool_json(pool_id, serde_json::json!(null))],
+            vec![
+                target_json(ids[0], pool_id, "10.0.3.1:80", 0),
+                target_json(ids[1], pool_id, "10.0.3.2:80", 0),
+                target_json(ids[2], pool_id, "10.0.3.3:80", 1),
+            ],
+            None,
+        );
+        let pool = &config.pools[&pool_id];
+        pool_target(&config, pool_id, ids[1]).set_healthy(false);
+        assert_eq!(
+            candidate_ids(pool),
+            Some(vec![ids[0]]),
+            "缺省阈值 50，恰好达到时不加入下一层级"
+        );
+
+        let single = Uuid::new_v4();
+        let config = runtime(
+            vec![pool_json(single, serde_json::json!(null))],
+            vec![
+                target_json(Uuid::new_v4(), single, "10.0.3.4:80", 1),
+                target_json(Uuid::new_v4(), single, "10.0.3.5:80", 1),
+            ],
+            None,
+        );
+        assert_eq!(
+            candidate_ids(&config.pools[&single]),
+            None,
+            "只有一个层级时沿用全部目标"
+        );
+    }
+}
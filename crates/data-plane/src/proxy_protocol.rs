@@ -0,0 +1,516 @@
+use async_trait::async_trait;
+use gateway_common::net::{IpCidr, contains_ip};
+use pingora::connectors::l4::Connect;
+use pingora::protocols::l4::socket::SocketAddr as PeerAddr;
+use pingora::protocols::l4::stream::Stream as L4Stream;
+use pingora::{Error, ErrorType};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::time::Duration;
+
+const V1_PREFIX: &[u8] = b"PROXY ";
+const V1_MAX_LEN: usize = 107;
+const V2_SIGNATURE: &[u8] = b"\r\n\r\n\0\r\nQUIT\n";
+pub const HEADER_READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 监听器接收 PROXY protocol 头的方式
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ProxyProtocolMode {
+    #[default]
+    Off,
+    /// 有则解析，无则按普通连接处理
+    Optional,
+    /// 必须携带，否则关闭连接
+    Required,
+}
+
+impl ProxyProtocolMode {
+    pub fn from_str(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "off" => Some(Self::Off),
+            "optional" => Some(Self::Optional),
+            "required" => Some(Self::Required),
+            _ => None,
+        }
+    }
+}
+
+/// 监听器的 PROXY protocol 配置（运行时）
+#[derive(Clone, Debug, Default)]
+pub struct ProxyProtocolConfig {
+    pub mode: ProxyProtocolMode,
+    /// 只接受来自这些网段的 PROXY 头，防止任意客户端伪造源地址
+    pub trusted_cidrs: Vec<IpCidr>,
+}
+
+impl ProxyProtocolConfig {
+    /// 按直连对端决定读取方式：不可信对端在 optional 下按普通连接处理，在 required 下返回 None（拒绝连接）
+    pub fn mode_for(&self, peer: Option<IpAddr>) -> Option<ProxyProtocolMode> {
+        let trusted = peer.is_some_and(|ip| contains_ip(&self.trusted_cidrs, ip));
+        match self.mode {
+            ProxyProtocolMode::Off => Some(ProxyProtocolMode::Off),
+            mode if trusted => Some(mode),
+            ProxyProtocolMode::Optional => Some(ProxyProtocolMode::Off),
+            ProxyProtocolMode::Required => None,
+        }
+    }
+}
+
+/// 向上游发送的 PROXY protocol 版本
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProxyProtocolVersion {
+    V1,
+    V2,
+}
+
+impl ProxyProtocolVersion {
+    pub fn from_str(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "v1" => Some(Self::V1),
+            "v2" => Some(Self::V2),
+            _ => None,
+        }
+    }
+}
+
+/// 解析结果：PROXY 头携带的源地址（LOCAL/UNKNOWN 时为空），以及非 PROXY 连接已读出的前缀字节
+pub struct ProxyHeader {
+    pub source: Option<SocketAddr>,
+    pub preface: Vec<u8>,
+}
+
+/// 从连接起始处读取 PROXY protocol v1/v2 头，只消费头部字节
+pub async fn read_header<S>(stream: &mut S, mode: ProxyProtocolMode) -> io::Result<ProxyHeader>
+where
+    S: AsyncRead + Unpin,
+{
+    if mode == ProxyProtocolMode::Off {
+        return Ok(ProxyHeader {
+            source: None,
+            preface: Vec::new(),
+        });
+    }
+
+    let mut first = [0u8; 1];
+    stream.read_exact(&mut first).await?;
+    let signature = match first[0] {
+        b'P' => V1_PREFIX,
+        b'\r' => V2_SIGNATURE,
+        _ => return not_proxy(mode, first.to_vec()),
+    };
+
+    let mut prefix = vec![0u8; signature.len()];
+    prefix[0] = first[0];
+    stream.read_exact(&mut prefix[1..]).await?;
+    if prefix != signature {
+        return not_proxy(mode, prefix);
+    }
+
+    let source = if signature == V1_PREFIX {
+        read_v1(stream).await?
+    } else {
+        read_v2(stream).await?
+    };
+    Ok(ProxyHeader {
+        source,
+        preface: Vec::new(),
+    })
+}
+
+fn not_proxy(mode: ProxyProtocolMode, preface: Vec<u8>) -> io::Result<ProxyHeader> {
+    if mode == ProxyProtocolMode::Required {
+        return Err(invalid("missing PROXY protocol header"));
+    }
+    Ok(ProxyHeader {
+        source: None,
+        preface,
+    })
+}
+
+async fn read_v1<S>(stream: &mut S) -> io::Result<Option<SocketAddr>>
+where
+    S: AsyncRead + Unpin,
+{
+    // "PROXY " 之后逐字节读到 CRLF，避免读入后续的应用层数据
+    let mut line = Vec::with_capacity(V1_MAX_LEN);
+    loop {
+        let mut byte = [0u8; 1];
+        stream.read_exact(&mut byte).await?;
+        line.push(byte[0]);
+        if line.ends_with(b"\r\n") {
+            break;
+        }
+        if line.len() + V1_PREFIX.len() > V1_MAX_LEN {
+            return Err(invalid("PROXY v1 header too long"));
+        }
+    }
+    let line = std::str::from_utf8(&line[..line.len() - 2])
+        .map_err(|_| invalid("PROXY v1 header not utf-8"))?;
+    let parts: Vec<&str> = line.split(' ').collect();
+    match parts.as_slice() {
+        ["UNKNOWN", ..] => Ok(None),
+        [proto, src, _dst, src_port, _dst_port] if *proto == "TCP4" || *proto == "TCP6" => {
+            let ip: IpAddr = src
+                .parse()
+                .map_err(|_| invalid("invalid PROXY v1 source"))?;
+            let port: u16 = src_port
+                .parse()
+                .map_err(|_| invalid("invalid PROXY v1 source port"))?;
+            Ok(Some(SocketAddr::new(ip, port)))
+        }
+        _ => Err(invalid("malformed PROXY v1 header")),
+    }
+}
+
+async fn read_v2<S>(stream: &mut S) -> io::Result<Option<SocketAddr>>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut head = [0u8; 4];
+    stream.read_exact(&mut head).await?;
+    let version_command = head[0];
+    let family = head[1];
+    let len = u16::from_be_bytes([head[2], head[3]]) as usize;
+    if version_command >> 4 != 2 {
+        return Err(invalid("unsupported PROXY v2 version"));
+    }
+    let mut payload = vec![0u8; len];
+    stream.read_exact(&mut payload).await?;
+
+    // LOCAL 命令（健康检查等）不携带客户端地址
+    if version_command & 0x0f == 0 {
+        return Ok(None);
+    }
+    match family {
+        0x11 | 0x12 if payload.len() >= 12 => {
+            let ip = Ipv4Addr::new(payload[0], payload[1], payload[2], payload[3]);
+            let port = u16::from_be_bytes([payload[8], payload[9]]);
+            Ok(Some(SocketAddr::new(IpAddr::V4(ip), port)))
+        }
+        0x21 | 0x22 if payload.len() >= 36 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&payload[..16]);
+            let port = u16::from_be_bytes([payload[32], payload[33]]);
+            Ok(Some(SocketAddr::new(
+                IpAddr::V6(Ipv6Addr::from(octets)),
+                port,
+            )))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// 生成发往上游的 PROXY protocol 头
+pub fn encode_header(version: ProxyProtocolVersion, src: SocketAddr, dst: SocketAddr) -> Vec<u8> {
+    // 源/目的地址族不一致时统一映射为 IPv6
+    let (src_ip, dst_ip) = match (src.ip(), dst.ip()) {
+        (IpAddr::V4(s), IpAddr::V6(d)) => (IpAddr::V6(s.to_ipv6_mapped()), IpAddr::V6(d)),
+        (IpAddr::V6(s), IpAddr::V4(d)) => (IpAddr::V6(s), IpAddr::V6(d.to_ipv6_mapped())),
+        pair => pair,
+    };
+    match version {
+        ProxyProtocolVersion::V1 => {
+            let proto = if src_ip.is_ipv4() { "TCP4" } else { "TCP6" };
+            format!(
+                "PROXY {} {} {} {} {}\r\n",
+                proto,
+                src_ip,
+                dst_ip,
+                src.port(),
+                dst.port()
+            )
+            .into_bytes()
+        }
+        ProxyProtocolVersion::V2 => {
+            let mut out = Vec::with_capacity(52);
+            out.extend_from_slice(V2_SIGNATURE);
+            out.push(0x21);
+            match (src_ip, dst_ip) {
+                (IpAddr::V4(s), IpAddr::V4(d)) => {
+                    out.push(0x11);
+                    out.extend_from_slice(&12u16.to_be_bytes());
+                    out.extend_from_slice(&s.octets());
+                    out.extend_from_slice(&d.octets());
+                }
+                (IpAddr::V6(s), IpAddr::V6(d)) => {
+                    out.push(0x21);
+                    out.extend_from_slice(&36u16.to_be_bytes());
+                    out.extend_from_slice(&s.octets());
+                    out.extend_from_slice(&d.octets());
+                }
+                _ => unreachable!("address families normalized above"),
+            }
+            out.extend_from_slice(&src.port().to_be_bytes());
+            out.extend_from_slice(&dst.port().to_be_bytes());
+            out
+        }
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// 新建上游连接后先发送 PROXY 头的连接器。Pingora 只在需要新连接时调用，
+/// 复用的连接不会重复发送；头部在连接交给 Pingora 之前写完，不会与请求数据交错
+#[derive(Debug)]
+pub struct ProxyHeaderConnect {
+    header: Vec<u8>,
+}
+
+impl ProxyHeaderConnect {
+    pub fn new(header: Vec<u8>) -> Self {
+        Self { header }
+    }
+}
+
+#[async_trait]
+impl Connect for ProxyHeaderConnect {
+    async fn connect(&self, addr: &PeerAddr) -> pingora::Result<L4Stream> {
+        connect_and_send(addr, &self.header).await.map_err(|err| {
+            Error::because(
+                ErrorType::ConnectError,
+                format!("failed to connect to {} with PROXY protocol header", addr),
+                err,
+            )
+        })
+    }
+}
+
+async fn connect_and_send(addr: &PeerAddr, header: &[u8]) -> io::Result<L4Stream> {
+    match addr {
+        PeerAddr::Inet(addr) => {
+            let mut stream = tokio::net::TcpStream::connect(addr).await?;
+            send(&mut stream, header).await?;
+            Ok(stream.into())
+        }
+        #[cfg(unix)]
+        PeerAddr::Unix(addr) => {
+            let path = addr
+                .as_pathname()
+                .ok_or_else(|| io::Error::other("unnamed unix socket"))?;
+            let mut stream = tokio::net::UnixStream::connect(path).await?;
+            send(&mut stream, header).await?;
+            Ok(stream.into())
+        }
+    }
+}
+
+async fn send<S: AsyncWrite + Unpin>(stream: &mut S, header: &[u8]) -> io::Result<()> {
+    stream.write_all(header).await?;
+    stream.flush().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(value: &str) -> SocketAddr {
+        value.parse().unwrap()
+    }
+
+    async fn parse(bytes: &[u8], mode: ProxyProtocolMode) -> io::Result<ProxyHeader> {
+        let mut stream = bytes;
+        read_header(&mut stream, mode).await
+    }
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let cases = [
+            ("203.0.113.7:51000", "10.0.0.1:8080"),
+            ("[2001:db8::7]:51000", "[2001:db8::1]:8080"),
+        ];
+        for version in [ProxyProtocolVersion::V1, ProxyProtocolVersion::V2] {
+            for (src, dst) in cases {
+                let mut bytes = encode_header(version, addr(src), addr(dst));
+                bytes.extend_from_slice(b"GET / HTTP/1.1\r\n");
+                let mut stream = bytes.as_slice();
+                let header = read_header(&mut stream, ProxyProtocolMode::Required)
+                    .await
+                    .unwrap();
+                assert_eq!(header.source, Some(addr(src)), "{:?}", version);
+                assert!(header.preface.is_empty());
+                assert_eq!(stream, b"GET / HTTP/1.1\r\n", "只消费头部字节");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mixed_family_mapped_to_v6() {
+        let bytes = encode_header(
+            ProxyProtocolVersion::V1,
+            addr("203.0.113.7:51000"),
+            addr("[2001:db8::1]:8080"),
+        );
+        assert!(bytes.starts_with(b"PROXY TCP6 ::ffff:203.0.113.7 "));
+        let bytes = encode_header(
+            ProxyProtocolVersion::V2,
+            addr("203.0.113.7:51000"),
+            addr("[2001:db8::1]:8080"),
+        );
+        let header = parse(&bytes, ProxyProtocolMode::Required).await.unwrap();
+        assert_eq!(header.source, Some(addr("[::ffff:203.0.113.7]:51000")));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_and_local() {
+        let header = parse(b"PROXY UNKNOWN\r\n", ProxyProtocolMode::Required)
+            .await
+            .unwrap();
+        assert_eq!(header.source, None);
+
+        let mut local = V2_SIGNATURE.to_vec();
+        local.extend_from_slice(&[0x20, 0x00, 0x00, 0x00]);
+        let header = parse(&local, ProxyProtocolMode::Required).await.unwrap();
+        assert_eq!(header.source, None, "LOCAL 命令不携带地址");
+    }
+
+    #[tokio::test]
+    async fn test_missing_header() {
+        let header = parse(b"GET / HTTP/1.1\r\n", ProxyProtocolMode::Optional)
+            .await
+            .unwrap();
+        assert_eq!(header.source, None);
+        assert_eq!(header.preface, b"G".to_vec(), "已读出的前缀需交还");
+
+        assert!(
+            parse(b"GET / HTTP/1.1\r\n", ProxyProtocolMode::Required)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_truncated_header() {
+        for version in [ProxyProtocolVersion::V1, ProxyProtocolVersion::V2] {
+            let bytes = encode_header(version, addr("203.0.113.7:1"), addr("10.0.0.1:2"));
+            for len in 1..bytes.len() {
+                assert!(
+                    parse(&bytes[..len], ProxyProtocolMode::Optional)
+                        .await
+                        .is_err(),
+                    "{:?} 截断到 {} 字节",
+                    version,
+                    len
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_malformed_header() {
+        let malformed: [&[u8]; 5] = [
+            b"PROXY TCP4 203.0.113.7 10.0.0.1 51000\r\n",
+            b"PROXY TCP4 not-an-ip 10.0.0.1 51000 8080\r\n",
+            b"PROXY TCP4 203.0.113.7 10.0.0.1 99999 8080\r\n",
+            b"PROXY UDP4 203.0.113.7 10.0.0.1 51000 8080\r\n",
+            b"PROXY \xff\xfe\r\n",
+        ];
+        for bytes in malformed {
+            assert!(
+                parse(bytes, ProxyProtocolMode::Required).await.is_err(),
+                "{:?}",
+                String::from_utf8_lossy(bytes)
+            );
+        }
+
+        let mut too_long = b"PROXY TCP4 ".to_vec();
+        too_long.extend(std::iter::repeat_n(b'1', V1_MAX_LEN));
+        too_long.extend_from_slice(b"\r\n");
+        assert!(
+            parse(&too_long, ProxyProtocolMode::Required).await.is_err(),
+            "v1 头超长"
+        );
+
+        let mut bad_version = V2_SIGNATURE.to_vec();
+        bad_version.extend_from_slice(&[0x11, 0x11, 0x00, 0x0c]);
+        bad_version.extend_from_slice(&[0; 12]);
+        assert!(
+            parse(&bad_version, ProxyProtocolMode::Required)
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_trusted_peers_only() {
+        let config = ProxyProtocolConfig {
+            mode: ProxyProtocolMode::Optional,
+            trusted_cidrs: vec![IpCidr::parse("10.0.0.0/8").unwrap()],
+        };
+        let trusted = Some("10.1.2.3".parse().unwrap());
+        let untrusted = Some("203.0.113.7".parse().unwrap());
+        assert_eq!(config.mode_for(trusted), Some(ProxyProtocolMode::Optional));
+        assert_eq!(
+            config.mode_for(untrusted),
+            Some(ProxyProtocolMode::Off),
+            "不可信对端的 PROXY 头不被解析"
+        );
+        assert_eq!(config.mode_for(None), Some(ProxyProtocolMode::Off));
+
+        let required = ProxyProtocolConfig {
+            mode: ProxyProtocolMode::Required,
+            ..config
+        };
+        assert_eq!(
+            required.mode_for(trusted),
+            Some(ProxyProtocolMode::Required)
+        );
+        assert_eq!(required.mode_for(untrusted), None, "required 下拒绝连接");
+    }
+
+    #[tokio::test]
+    async fn test_connector_sends_header_first() {
+        let header = encode_header(
+            ProxyProtocolVersion::V2,
+            addr("203.0.113.7:51000"),
+            addr("10.0.0.1:8080"),
+        );
+        let connector = ProxyHeaderConnect::new(header.clone());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target = PeerAddr::Inet(listener.local_addr().unwrap());
+        let (stream, accepted) = tokio::join!(connector.connect(&target), listener.accept());
+        let mut stream = stream.unwrap();
+        let (mut backend, _) = accepted.unwrap();
+        stream.write_all(b"GET / HTTP/1.1\r\n").await.unwrap();
+        stream.flush().await.unwrap();
+        drop(stream);
+
+        let mut received = Vec::new();
+        backend.read_to_end(&mut received).await.unwrap();
+        let mut rest = received.as_slice();
+        let parsed = read_header(&mut rest, ProxyProtocolMode::Required)
+            .await
+            .unwrap();
+        assert_eq!(parsed.source, Some(addr("203.0.113.7:51000")));
+        assert_eq!(rest, b"GET / HTTP/1.1\r\n", "请求数据紧随 PROXY 头之后");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_connector_unix_socket() {
+        let dir = std::env::temp_dir().join(format!("pp-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("upstream.sock");
+        let listener = tokio::net::UnixListener::bind(&path).unwrap();
+        let connector = ProxyHeaderConnect::new(b"PROXY UNKNOWN\r\n".to_vec());
+
+        let target = format!("unix:{}", path.display())
+            .parse::<PeerAddr>()
+            .unwrap();
+        let (stream, accepted) = tokio::join!(connector.connect(&target), listener.accept());
+        drop(stream.unwrap());
+        let (mut backend, _) = accepted.unwrap();
+        let mut received = Vec::new();
+        backend.read_to_end(&mut received).await.unwrap();
+        assert_eq!(received, b"PROXY UNKNOWN\r\n");
+
+        let missing = format!("unix:{}", dir.join("missing.sock").display())
+            .parse::<PeerAddr>()
+            .unwrap();
+        assert!(connector.connect(&missing).await.is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,317 @@
+use crate::proxy::{RouteKind, RouteRule};
+use regex::RegexSet;
+use std::collections::HashMap;
+use tracing::warn;
+
+/// 监听器内 HTTP 路由的预编译索引：按主机（精确 / 单级通配 / 正则）与路径（前缀基数树 / 正则）
+/// 两个维度分别求出候选路由，取交集后按优先级顺序交给完整匹配器复核。
+///
+/// 索引只负责缩小候选范围，不改变匹配语义：未设置某一维度条件的路由在该维度上总是候选。
+pub struct RouteIndex {
+    exact_hosts: HashMap<String, Vec<usize>>,
+    wildcard_hosts: HashMap<String, Vec<usize>>,
+    host_regex: Option<(RegexSet, Vec<usize>)>,
+    any_host: RouteSet,
+    prefixes: PrefixNode,
+    path_regex: Option<(RegexSet, Vec<usize>)>,
+    any_path: RouteSet,
+}
+
+impl RouteIndex {
+    /// `rules` 须已按优先级排序，索引中的位置即优先级顺序
+    pub fn build(rules: &[RouteRule]) -> Self {
+        let len = rules.len();
+        let mut index = Self {
+            exact_hosts: HashMap::new(),
+            wildcard_hosts: HashMap::new(),
+            host_regex: None,
+            any_host: RouteSet::new(len),
+            prefixes: PrefixNode::default(),
+            path_regex: None,
+            any_path: RouteSet::new(len),
+        };
+        let mut host_regex = Vec::new();
+        let mut path_regex = Vec::new();
+
+        for (pos, rule) in rules.iter().enumerate() {
+            match rule.kind {
+                RouteKind::Port => {
+                    index.any_host.insert(pos);
+                    index.any_path.insert(pos);
+                    continue;
+                }
+                RouteKind::Path | RouteKind::Ws => {}
+                // L4 路由不参与 HTTP 匹配
+                RouteKind::Tcp | RouteKind::Sni => continue,
+            }
+
+            let matcher = &rule.matcher;
+            match (matcher.host(), matcher.host_regex()) {
+                (Some(host), _) => match host.strip_prefix("*.") {
+                    Some(suffix) => push(&mut index.wildcard_hosts, suffix, pos),
+                    None => push(&mut index.exact_hosts, host, pos),
+                },
+                (None, Some(expr)) => host_regex.push((expr.to_string(), pos)),
+                (None, None) => index.any_host.insert(pos),
+            }
+            match (matcher.path_prefix(), matcher.path_regex()) {
+                (Some(prefix), _) => index.prefixes.insert(prefix.as_bytes(), pos),
+                (None, Some(expr)) => path_regex.push((expr.to_string(), pos)),
+                (None, None) => index.any_path.insert(pos),
+            }
+        }
+
+        index.host_regex = compile(host_regex, &mut index.any_host, "host_regex");
+        index.path_regex = compile(path_regex, &mut index.any_path, "path_regex");
+        index
+    }
+
+    /// 求出候选路由集合；`host` 为规范化后的请求主机，缺失时为空串
+    pub fn candidates(&self, host: &str, path: &str) -> RouteSet {
+        let mut hosts = self.any_host.clone();
+        if let Some(positions) = self.exact_hosts.get(host) {
+            hosts.extend(positions);
+        }
+        if let Some((label, suffix)) = host.split_once('.')
+            && !label.is_empty()
+            && let Some(positions) = self.wildcard_hosts.get(suffix)
+        {
+            hosts.extend(positions);
+        }
+        if let Some((set, positions)) = &self.host_regex {
+            for i in set.matches(host).iter() {
+                hosts.insert(positions[i]);
+            }
+        }
+
+        let mut paths = self.any_path.clone();
+        self.prefixes.collect(path.as_bytes(), &mut paths);
+        if let Some((set, positions)) = &self.path_regex {
+            for i in set.matches(path).iter() {
+                paths.insert(positions[i]);
+            }
+        }
+
+        hosts.intersect(&paths);
+        hosts
+    }
+}
+
+fn push(map: &mut HashMap<String, Vec<usize>>, key: &str, pos: usize) {
+    map.entry(key.to_string()).or_default().push(pos);
+}
+
+/// 编译 RegexSet；失败时（如超出大小限制）退化为对应维度的无条件候选，由完整匹配器兜底
+fn compile(
+    entries: Vec<(String, usize)>,
+    fallback: &mut RouteSet,
+    name: &str,
+) -> Option<(RegexSet, Vec<usize>)> {
+    if entries.is_empty() {
+        return None;
+    }
+    match RegexSet::new(entries.iter().map(|(expr, _)| expr)) {
+        Ok(set) => Some((set, entries.into_iter().map(|(_, pos)| pos).collect())),
+        Err(err) => {
+            warn!(
+                "failed to build {} index, falling back to scan: {}",
+                name, err
+            );
+            for (_, pos) in entries {
+                fallback.insert(pos);
+            }
+            None
+        }
+    }
+}
+
+/// 路由位置集合（位图），按位置升序即优先级顺序迭代
+#[derive(Clone)]
+pub struct RouteSet {
+    words: Vec<u64>,
+}
+
+impl RouteSet {
+    fn new(len: usize) -> Self {
+        Self {
+            words: vec![0; len.div_ceil(64)],
+        }
+    }
+
+    fn insert(&mut self, pos: usize) {
+        self.words[pos / 64] |= 1 << (pos % 64);
+    }
+
+    fn extend(&mut self, positions: &[usize]) {
+        for pos in positions {
+            self.insert(*pos);
+        }
+    }
+
+    fn intersect(&mut self, other: &RouteSet) {
+        for (word, other) in self.words.iter_mut().zip(&other.words) {
+            *word &= other;
+        }
+    }
+
+    pub fn positions(self) -> impl Iterator<Item = usize> {
+        self.words.into_iter().enumerate().flat_map(|(i, word)| {
+            let mut bits = word;
+            std::iter::from_fn(move || {
+                if bits == 0 {
+                    return None;
+                }
+                let bit = bits.trailing_zeros() as usize;
+                bits &= bits - 1;
+                Some(i * 64 + bit)
+            })
+        })
+    }
+}
+
+/// 路径前缀基数树：边为压缩后的字节串，同一节点的子边首字节互不相同
+#[derive(Default)]
+struct PrefixNode {
+    routes: Vec<usize>,
+    children: Vec<(Vec<u8>, PrefixNode)>,
+}
+
+impl PrefixNode {
+    fn insert(&mut self, key: &[u8], pos: usize) {
+        if key.is_empty() {
+            self.routes.push(pos);
+            return;
+        }
+        for (label, child) in &mut self.children {
+            let common = common_prefix_len(label, key);
+            if common == 0 {
+                continue;
+            }
+            if common < label.len() {
+                let rest = label.split_off(common);
+                let old = std::mem::take(child);
+                child.children.push((rest, old));
+            }
+            child.insert(&key[common..], pos);
+            return;
+        }
+        let mut node = PrefixNode::default();
+        node.routes.push(pos);
+        self.children.push((key.to_vec(), node));
+    }
+
+    /// 收集所有前缀为 `path` 前缀的路由
+    fn collect(&self, mut path: &[u8], out: &mut RouteSet) {
+        let mut node = self;
+        loop {
+            out.extend(&node.routes);
+            let next = node
+                .children
+                .iter()
+                .find(|(label, _)| path.starts_with(label));
+            match next {
+                Some((label, child)) => {
+                    path = &path[label.len()..];
+                    node = child;
+                }
+                None => return,
+            }
+        }
+    }
+}
+
+fn common_prefix_len(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limits::RequestLimits;
+    use crate::proxy::RouteMatcher;
+    use crate::ws::WsSettings;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    fn rule(kind: RouteKind, match_expr: serde_json::Value) -> RouteRule {
+        RouteRule {
+            id: Uuid::new_v4(),
+            upstream_pool_id: Uuid::nil(),
+            priority: 0,
+            matcher: RouteMatcher::from_json(&match_expr).unwrap(),
+            kind,
+            error_pages: None,
+            maintenance: None,
+            limits: RequestLimits::default(),
+            websocket: WsSettings::from_options(None),
+            fault: None,
+            ext_auth: None,
+            security_headers: None,
+        }
+    }
+
+    fn candidates(index: &RouteIndex, host: &str, path: &str) -> Vec<usize> {
+        index.candidates(host, path).positions().collect()
+    }
+
+    #[test]
+    fn test_priority_order_across_buckets() {
+        // 位置即优先级顺序，各维度的精确、通配、前缀与正则条件交错排列
+        let rules = vec![
+            rule(RouteKind::Path, json!({"path_regex": "^/api/v[0-9]+/"})),
+            rule(
+                RouteKind::Path,
+                json!({"host": "api.example.com", "path_prefix": "/api"}),
+            ),
+            rule(RouteKind::Path, json!({"host": "*.example.com"})),
+            rule(RouteKind::Path, json!({"host_regex": "^api\\."})),
+            rule(RouteKind::Path, json!({"path_prefix": "/"})),
+            rule(RouteKind::Tcp, json!({})),
+            rule(RouteKind::Port, json!({})),
+        ];
+        let index = RouteIndex::build(&rules);
+
+        assert_eq!(
+            candidates(&index, "api.example.com", "/api/v1/users"),
+            vec![0, 1, 2, 3, 4, 6]
+        );
+        assert_eq!(
+            candidates(&index, "www.example.com", "/static/app.js"),
+            vec![2, 4, 6]
+        );
+        assert_eq!(
+            candidates(&index, "api.other.org", "/api/v2/"),
+            vec![0, 3, 4, 6]
+        );
+        assert_eq!(
+            candidates(&index, "example.com", "/"),
+            vec![4, 6],
+            "单级通配不匹配裸域名"
+        );
+        assert_eq!(candidates(&index, "", ""), vec![6]);
+    }
+
+    #[test]
+    fn test_prefix_tree_split() {
+        let rules = vec![
+            rule(RouteKind::Path, json!({"path_prefix": "/api/users"})),
+            rule(RouteKind::Path, json!({"path_prefix": "/app"})),
+            rule(RouteKind::Path, json!({"path_prefix": "/api"})),
+            rule(RouteKind::Ws, json!({"path_prefix": "/a"})),
+        ];
+        let index = RouteIndex::build(&rules);
+        assert_eq!(candidates(&index, "", "/api/users/1"), vec![0, 2, 3]);
+        assert_eq!(candidates(&index, "", "/apple"), vec![1, 3]);
+        assert_eq!(candidates(&index, "", "/api"), vec![2, 3]);
+        assert_eq!(candidates(&index, "", "/b"), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_route_set_beyond_one_word() {
+        let mut set = RouteSet::new(130);
+        for pos in [129, 0, 64, 63] {
+            set.insert(pos);
+        }
+        assert_eq!(set.positions().collect::<Vec<_>>(), vec![0, 63, 64, 129]);
+    }
+}
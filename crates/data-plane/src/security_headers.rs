@@ -0,0 +1,269 @@
+use gateway_common::models::{HstsOptions, SecurityHeadersOptions};
+use http::header::{self, HeaderName, HeaderValue};
+use pingora::http::ResponseHeader;
+use std::sync::Arc;
+use tracing::warn;
+
+const DEFAULT_HSTS_MAX_AGE: u64 = 31_536_000;
+
+/// 上游响应中暴露服务端实现的头
+const SERVER_HEADERS: [HeaderName; 2] = [header::SERVER, HeaderName::from_static("x-powered-by")];
+
+/// 安全响应头策略（运行时）
+#[derive(Debug)]
+pub struct SecurityHeaders {
+    headers: Vec<(HeaderName, HeaderValue)>,
+    hide_server_headers: bool,
+    override_upstream: bool,
+}
+
+impl SecurityHeaders {
+    /// 依次以 https 默认值、监听器配置、路由配置逐项覆盖；未启用或没有任何效果时返回 None
+    pub fn resolve(
+        owner: &str,
+        https: bool,
+        layers: &[Option<&SecurityHeadersOptions>],
+    ) -> Option<Arc<Self>> {
+        let mut merged = if https {
+            https_defaults()
+        } else {
+            SecurityHeadersOptions::default()
+        };
+        for layer in layers.iter().flatten() {
+            overlay(&mut merged, layer);
+        }
+        if merged.enabled == Some(false) {
+            return None;
+        }
+
+        let mut values = Vec::new();
+        if https && let Some(hsts) = merged.hsts.as_ref().and_then(hsts_value) {
+            values.push((header::STRICT_TRANSPORT_SECURITY, hsts));
+        }
+        let fields = [
+            (header::X_CONTENT_TYPE_OPTIONS, merged.content_type_options),
+            (header::X_FRAME_OPTIONS, merged.frame_options),
+            (header::REFERRER_POLICY, merged.referrer_policy),
+            (
+                header::CONTENT_SECURITY_POLICY,
+                merged.content_security_policy,
+            ),
+            (
+                HeaderName::from_static("permissions-policy"),
+                merged.permissions_policy,
+            ),
+        ];
+        for (name, value) in fields {
+            if let Some(value) = value.filter(|value| !value.is_empty()) {
+                values.push((name, value));
+            }
+        }
+
+        let mut headers = Vec::with_capacity(values.len());
+        for (name, value) in values {
+            match HeaderValue::from_str(&value) {
+                Ok(value) => headers.push((name, value)),
+                Err(_) => warn!(
+                    "invalid security header {} for {}, skipped",
+                    name.as_str(),
+                    owner
+                ),
+            }
+        }
+        let policy = Self {
+            headers,
+            hide_server_headers: merged.hide_server_headers.unwrap_or(false),
+            override_upstream: merged.override_upstream.unwrap_or(false),
+        };
+        (!policy.headers.is_empty() || policy.hide_server_headers).then(|| Arc::new(policy))
+    }
+
+    /// 写入响应头；上游已设置的同名头默认保留
+    pub fn apply(&self, response: &mut ResponseHeader) -> Result<(), Box<pingora::Error>> {
+        if self.hide_server_headers {
+            for name in &SERVER_HEADERS {
+                response.remove_header(name);
+            }
+        }
+        for (name, value) in &self.headers {
+            if self.override_upstream || !response.headers.contains_key(name) {
+                response.insert_header(name.clone(), value.clone())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// https 监听器的默认策略；Content-Security-Policy 与 Permissions-Policy 依赖具体应用，不提供默认值
+fn https_defaults() -> SecurityHeadersOptions {
+    SecurityHeadersOptions {
+        enabled: Some(true),
+        hsts: Some(HstsOptions {
+            enabled: Some(true),
+            max_age_secs: Some(DEFAULT_HSTS_MAX_AGE),
+            include_subdomains: Some(false),
+            preload: Some(false),
+        }),
+        content_type_options: Some("nosniff".to_string()),
+        frame_options: Some("SAMEORIGIN".to_string()),
+        referrer_policy: Some("strict-origin-when-cross-origin".to_string()),
+        content_security_policy: None,
+        permissions_policy: None,
+        hide_server_headers: Some(true),
+        override_upstream: Some(false),
+    }
+}
+
+fn overlay(base: &mut SecurityHeadersOptions, layer: &SecurityHeadersOptions) {
+    fn set<T: Clone>(base: &mut Option<T>, layer: &Option<T>) {
+        if layer.is_some() {
+            base.clone_from(layer);
+        }
+    }
+    set(&mut base.enabled, &layer.enabled);
+    if let Some(hsts) = &layer.hsts {
+        let base = base.hsts.get_or_insert_with(HstsOptions::default);
+        set(&mut base.enabled, &hsts.enabled);
+        set(&mut base.max_age_secs, &hsts.max_age_secs);
+        set(&mut base.include_subdomains, &hsts.include_subdomains);
+        set(&mut base.preload, &hsts.preload);
+    }
+    set(&mut base.content_type_options, &layer.content_type_options);
+    set(&mut base.frame_options, &layer.frame_options);
+    set(&mut base.referrer_policy, &layer.referrer_policy);
+    set(
+        &mut base.content_security_policy,
+        &layer.content_security_policy,
+    );
+    set(&mut base.permissions_policy, &layer.permissions_policy);
+    set(&mut base.hide_server_headers, &layer.hide_server_headers);
+    set(&mut base.override_upstream, &layer.override_upstream);
+}
+
+fn hsts_value(hsts: &HstsOptions) -> Option<String> {
+    if hsts.enabled == Some(false) {
+        return None;
+    }
+    let mut value = format!(
+        "max-age={}",
+        hsts.max_age_secs.unwrap_or(DEFAULT_HSTS_MAX_AGE)
+    );
+    if hsts.include_subdomains == Some(true) {
+        value.push_str("; includeSubDomains");
+    }
+    if hsts.preload == Some(true) {
+        value.push_str("; preload");
+    }
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(value: serde_json::Value) -> SecurityHeadersOptions {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn response() -> ResponseHeader {
+        let mut response = ResponseHeader::build(200, None).unwrap();
+        response.insert_header("server", "nginx").unwrap();
+        response.insert_header("x-frame-options", "DENY").unwrap();
+        response
+    }
+
+    #[test]
+    fn test_hsts_only_on_https() {
+        let https = SecurityHeaders::resolve("listener", true, &[]).unwrap();
+        let mut header = response();
+        https.apply(&mut header).unwrap();
+        assert_eq!(
+            header.headers["strict-transport-security"],
+            "max-age=31536000"
+        );
+        assert_eq!(header.headers["x-content-type-options"], "nosniff");
+        assert!(
+            !header.headers.contains_key("server"),
+            "https 默认隐藏 Server"
+        );
+
+        assert!(
+            SecurityHeaders::resolve("listener", false, &[]).is_none(),
+            "http 没有默认策略"
+        );
+        let layer = options(serde_json::json!({
+            "hsts": { "enabled": true, "include_subdomains": true },
+            "frame_options": "DENY",
+        }));
+        let http = SecurityHeaders::resolve("listener", false, &[Some(&layer)]).unwrap();
+        let mut header = ResponseHeader::build(200, None).unwrap();
+        http.apply(&mut header).unwrap();
+        assert!(
+            !header.headers.contains_key("strict-transport-security"),
+            "明文监听器不发送 HSTS"
+        );
+        assert_eq!(header.headers["x-frame-options"], "DENY");
+    }
+
+    #[test]
+    fn test_layers_override_in_order() {
+        let listener = options(serde_json::json!({
+            "hsts": { "max_age_secs": 600, "preload": true },
+            "content_security_policy": "default-src 'self'",
+        }));
+        let route = options(serde_json::json!({
+            "hsts": { "include_subdomains": true },
+            "content_security_policy": "",
+            "hide_server_headers": false,
+        }));
+        let policy =
+            SecurityHeaders::resolve("route", true, &[Some(&listener), Some(&route)]).unwrap();
+        let mut header = response();
+        policy.apply(&mut header).unwrap();
+        assert_eq!(
+            header.headers["strict-transport-security"], "max-age=600; includeSubDomains; preload",
+            "HSTS 逐项合并"
+        );
+        assert!(
+            !header.headers.contains_key("content-security-policy"),
+            "空值关闭上层的设置"
+        );
+        assert_eq!(header.headers["server"], "nginx");
+
+        let disabled = options(serde_json::json!({ "enabled": false }));
+        assert!(
+            SecurityHeaders::resolve("route", true, &[Some(&listener), Some(&disabled)]).is_none()
+        );
+    }
+
+    #[test]
+    fn test_upstream_header_kept_unless_override() {
+        let policy = SecurityHeaders::resolve("listener", true, &[]).unwrap();
+        let mut header = response();
+        policy.apply(&mut header).unwrap();
+        assert_eq!(
+            header.headers["x-frame-options"], "DENY",
+            "上游已设置的头默认保留"
+        );
+
+        let layer = options(serde_json::json!({ "override_upstream": true }));
+        let policy = SecurityHeaders::resolve("listener", true, &[Some(&layer)]).unwrap();
+        let mut header = response();
+        policy.apply(&mut header).unwrap();
+        assert_eq!(
+            header.headers["x-frame-options"], "SAMEORIGIN",
+            "配置覆盖上游"
+        );
+        assert_eq!(header.headers.get_all("x-frame-options").iter().count(), 1);
+    }
+
+    #[test]
+    fn test_invalid_value_skipped() {
+        let layer = options(serde_json::json!({
+            "referrer_policy": "no-referrer\n",
+            "hide_server_headers": false,
+        }));
+        let policy = SecurityHeaders::resolve("route", false, &[Some(&layer)]);
+        assert!(policy.is_none(), "无效取值跳过后没有任何效果");
+    }
+}
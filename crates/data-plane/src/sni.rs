@@ -0,0 +1,278 @@
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+const RECORD_HEADER_LEN: usize = 5;
+const CONTENT_TYPE_HANDSHAKE: u8 = 0x16;
+const HANDSHAKE_CLIENT_HELLO: u8 = 0x01;
+const EXTENSION_SERVER_NAME: u16 = 0x0000;
+const NAME_TYPE_HOST_NAME: u8 = 0x00;
+const MAX_RECORD_LEN: usize = 16 * 1024 + 2048;
+const HANDSHAKE_HEADER_LEN: usize = 4;
+/// ClientHello 可跨多个 TLS 记录（如较大的后量子密钥交换参数），读取总量不超过该上限
+const MAX_CLIENT_HELLO_LEN: usize = 64 * 1024;
+
+/// 读取承载 ClientHello 的 TLS 记录到 `buf`，直到握手消息完整，不终止 TLS；
+/// `buf` 中已有的字节视为记录开头
+pub async fn read_client_hello<S>(stream: &mut S, buf: &mut Vec<u8>) -> io::Result<()>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut end = 0;
+    let mut handshake = Vec::new();
+    loop {
+        fill(stream, buf, end + RECORD_HEADER_LEN).await?;
+        let header = &buf[end..end + RECORD_HEADER_LEN];
+        if header[0] != CONTENT_TYPE_HANDSHAKE {
+            return Err(invalid("not a TLS handshake record"));
+        }
+        let len = u16::from_be_bytes([header[3], header[4]]) as usize;
+        if len > MAX_RECORD_LEN {
+            return Err(invalid("TLS record too large"));
+        }
+        fill(stream, buf, end + RECORD_HEADER_LEN + len).await?;
+        handshake.extend_from_slice(&buf[end + RECORD_HEADER_LEN..end + RECORD_HEADER_LEN + len]);
+        end += RECORD_HEADER_LEN + len;
+        if let Some(total) = message_len(&handshake) {
+            if total > MAX_CLIENT_HELLO_LEN {
+                return Err(invalid("TLS ClientHello too large"));
+            }
+            if handshake.len() >= total {
+                return Ok(());
+            }
+        }
+        if end >= MAX_CLIENT_HELLO_LEN {
+            return Err(invalid("TLS ClientHello too large"));
+        }
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+async fn fill<S>(stream: &mut S, buf: &mut Vec<u8>, len: usize) -> io::Result<()>
+where
+    S: AsyncRead + Unpin,
+{
+    if buf.len() >= len {
+        return Ok(());
+    }
+    let start = buf.len();
+    buf.resize(len, 0);
+    stream.read_exact(&mut buf[start..]).await?;
+    Ok(())
+}
+
+/// 拼接连续握手记录的载荷；遇到非握手记录或不完整的记录时停止
+fn handshake_bytes(mut records: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    while records.len() >= RECORD_HEADER_LEN && records[0] == CONTENT_TYPE_HANDSHAKE {
+        let len = u16::from_be_bytes([records[3], records[4]]) as usize;
+        let Some(fragment) = records.get(RECORD_HEADER_LEN..RECORD_HEADER_LEN + len) else {
+            break;
+        };
+        out.extend_from_slice(fragment);
+        records = &records[RECORD_HEADER_LEN + len..];
+    }
+    out
+}
+
+/// 握手消息总长度（含 4 字节消息头）；不足消息头时返回 None
+fn message_len(handshake: &[u8]) -> Option<usize> {
+    let header = handshake.get(..HANDSHAKE_HEADER_LEN)?;
+    let len = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+    Some(HANDSHAKE_HEADER_LEN + len)
+}
+
+/// 从 TLS 记录（可跨多个记录）中的 ClientHello 解析 SNI；消息不完整或未携带 SNI 时返回 None
+pub fn parse_sni(records: &[u8]) -> Option<String> {
+    let handshake = handshake_bytes(records);
+    let message = handshake.get(..message_len(&handshake)?)?;
+    let mut r = Reader::new(message);
+    if r.u8()? != HANDSHAKE_CLIENT_HELLO {
+        return None;
+    }
+    let _length = r.bytes(3)?;
+    let _version = r.bytes(2)?;
+    let _random = r.bytes(32)?;
+    let session_id_len = r.u8()? as usize;
+    r.bytes(session_id_len)?;
+    let cipher_suites_len = r.u16()? as usize;
+    r.bytes(cipher_suites_len)?;
+    let compression_len = r.u8()? as usize;
+    r.bytes(compression_len)?;
+
+    let extensions_len = r.u16()? as usize;
+    let mut extensions = Reader::new(r.bytes(extensions_len)?);
+    while let Some(kind) = extensions.u16() {
+        let len = extensions.u16()? as usize;
+        let data = extensions.bytes(len)?;
+        if kind != EXTENSION_SERVER_NAME {
+            continue;
+        }
+        let mut names = Reader::new(data);
+        let list_len = names.u16()? as usize;
+        let mut list = Reader::new(names.bytes(list_len)?);
+        while let Some(name_type) = list.u8() {
+            let name_len = list.u16()? as usize;
+            let name = list.bytes(name_len)?;
+            if name_type == NAME_TYPE_HOST_NAME {
+                let name = std::str::from_utf8(name).ok()?;
+                return Some(name.trim_end_matches('.').to_ascii_lowercase());
+            }
+        }
+        return None;
+    }
+    None
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.data.len() < len {
+            return None;
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Some(head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.bytes(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 客户端以 SNI `Example.COM` 发起握手时抓取的 ClientHello（单个记录）
+    const CLIENT_HELLO: &[u8] = include_bytes!("../testdata/client_hello.bin");
+
+    /// 将 ClientHello 的握手消息按 `size` 字节拆分到多个记录
+    fn fragment(record: &[u8], size: usize) -> Vec<u8> {
+        let mut out = Vec::new();
+        for chunk in record[RECORD_HEADER_LEN..].chunks(size) {
+            out.extend_from_slice(&record[..3]);
+            out.extend_from_slice(&(chunk.len() as u16).to_be_bytes());
+            out.extend_from_slice(chunk);
+        }
+        out
+    }
+
+    /// 构造只含给定扩展的最小 ClientHello 记录
+    fn client_hello(extensions: &[(u16, Vec<u8>)]) -> Vec<u8> {
+        let mut body = vec![0x03, 0x03];
+        body.extend_from_slice(&[0; 32]);
+        body.push(0);
+        body.extend_from_slice(&[0x00, 0x02, 0x13, 0x01]);
+        body.extend_from_slice(&[0x01, 0x00]);
+        let mut ext = Vec::new();
+        for (kind, data) in extensions {
+            ext.extend_from_slice(&kind.to_be_bytes());
+            ext.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            ext.extend_from_slice(data);
+        }
+        body.extend_from_slice(&(ext.len() as u16).to_be_bytes());
+        body.extend_from_slice(&ext);
+
+        let mut message = vec![HANDSHAKE_CLIENT_HELLO];
+        message.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+        message.extend_from_slice(&body);
+        let mut record = vec![CONTENT_TYPE_HANDSHAKE, 0x03, 0x01];
+        record.extend_from_slice(&(message.len() as u16).to_be_bytes());
+        record.extend_from_slice(&message);
+        record
+    }
+
+    fn server_name(name: &str) -> (u16, Vec<u8>) {
+        let mut entry = vec![NAME_TYPE_HOST_NAME];
+        entry.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        entry.extend_from_slice(name.as_bytes());
+        let mut data = (entry.len() as u16).to_be_bytes().to_vec();
+        data.extend_from_slice(&entry);
+        (EXTENSION_SERVER_NAME, data)
+    }
+
+    #[test]
+    fn test_parse_captured_client_hello() {
+        assert_eq!(parse_sni(CLIENT_HELLO).as_deref(), Some("example.com"));
+    }
+
+    #[test]
+    fn test_parse_fragmented_client_hello() {
+        let records = fragment(CLIENT_HELLO, 100);
+        assert!(records.len() > CLIENT_HELLO.len(), "拆分为多个记录");
+        assert_eq!(parse_sni(&records).as_deref(), Some("example.com"));
+    }
+
+    #[test]
+    fn test_parse_truncated() {
+        for len in 0..CLIENT_HELLO.len() {
+            assert_eq!(parse_sni(&CLIENT_HELLO[..len]), None, "截断到 {} 字节", len);
+        }
+        let records = fragment(CLIENT_HELLO, 100);
+        assert_eq!(parse_sni(&records[..records.len() - 1]), None);
+    }
+
+    #[test]
+    fn test_parse_missing_extension() {
+        assert_eq!(parse_sni(&client_hello(&[])), None);
+        assert_eq!(
+            parse_sni(&client_hello(&[(0x0010, vec![0, 3, 2, b'h', b'2'])])),
+            None
+        );
+        assert_eq!(
+            parse_sni(&client_hello(&[
+                (0x0010, vec![0, 3, 2, b'h', b'2']),
+                server_name("api.example.com."),
+            ]))
+            .as_deref(),
+            Some("api.example.com")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_fragmented_client_hello() {
+        let records = fragment(CLIENT_HELLO, 100);
+        let mut input = records.clone();
+        input.extend_from_slice(b"application data");
+        let mut stream = input.as_slice();
+        let mut buf = Vec::new();
+        read_client_hello(&mut stream, &mut buf).await.unwrap();
+        assert_eq!(buf, records, "读到 ClientHello 完整为止，不多读");
+        assert_eq!(stream, b"application data");
+    }
+
+    #[tokio::test]
+    async fn test_read_rejects_invalid_input() {
+        let mut buf = Vec::new();
+        let mut stream: &[u8] = b"GET / HTTP/1.1\r\n";
+        assert!(read_client_hello(&mut stream, &mut buf).await.is_err());
+
+        let mut buf = Vec::new();
+        let mut stream = &CLIENT_HELLO[..CLIENT_HELLO.len() - 1];
+        assert!(
+            read_client_hello(&mut stream, &mut buf).await.is_err(),
+            "连接在 ClientHello 完整前关闭"
+        );
+
+        // 声明的 ClientHello 长度超过上限
+        let mut oversized = vec![CONTENT_TYPE_HANDSHAKE, 0x03, 0x01, 0x00, 0x04];
+        oversized.extend_from_slice(&[HANDSHAKE_CLIENT_HELLO, 0x01, 0x00, 0x00]);
+        let mut buf = Vec::new();
+        let mut stream = oversized.as_slice();
+        assert!(read_client_hello(&mut stream, &mut buf).await.is_err());
+    }
+}
@@ -1,12 +1,21 @@
 use anyhow::Result;
 use gateway_common::entities::certificates::Model as Certificate;
 use gateway_common::entities::tls_policies::Model as TlsPolicy;
+use gateway_common::models::TlsPolicyOptions;
 use gateway_common::snapshot::Snapshot;
+use pingora::tls::hash::MessageDigest;
+use pingora::tls::x509::{X509, X509NameRef, X509Ref};
 use rcgen::CertificateParams;
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
+use std::sync::{OnceLock, RwLock};
+use tracing::warn;
 use uuid::Uuid;
 
+/// 缺省的服务端 ALPN 列表（wire format），仅协商 http/1.1
+pub const ALPN_HTTP1: &[u8] = b"\x08http/1.1";
+
 #[derive(Debug, Clone)]
 pub struct TlsKeyPairPem {
     pub cert_pem: Vec<u8>,
@@ -54,3 +63,124 @@ pub fn tls_pem_for_policy(snapshot: &Snapshot, policy_id: Uuid) -> Option<TlsKey
         key_pem: cert.key_pem.clone().into_bytes(),
     })
 }
+
+fn policy_options(snapshot: &Snapshot, policy_id: Uuid) -> Option<TlsPolicyOptions> {
+    let policy = snapshot.tls_policies.iter().find(|p| p.id == policy_id)?;
+    match serde_json::from_value(policy.options.clone()?) {
+        Ok(options) => Some(options),
+        Err(err) => {
+            warn!("invalid options for tls policy {}: {}", policy.id, err);
+            None
+        }
+    }
+}
+
+/// TLS 策略配置的 ALPN 列表编码为 wire format（长度前缀）；未配置时返回 None
+pub fn alpn_for_policy(snapshot: &Snapshot, policy_id: Uuid) -> Option<Vec<u8>> {
+    let options = policy_options(snapshot, policy_id)?;
+    let mut wire = Vec::new();
+    for proto in options.alpn? {
+        if proto.is_empty() || proto.len() > 255 {
+            continue;
+        }
+        wire.push(proto.len() as u8);
+        wire.extend_from_slice(proto.as_bytes());
+    }
+    (!wire.is_empty()).then_some(wire)
+}
+
+/// TLS 策略配置的客户端证书 CA；未配置或无法解析时返回 None
+pub fn client_ca_for_policy(snapshot: &Snapshot, policy_id: Uuid) -> Option<Vec<X509>> {
+    let pem = policy_options(snapshot, policy_id)?.client_ca_pem?;
+    match X509::stack_from_pem(pem.as_bytes()) {
+        Ok(cas) if !cas.is_empty() => Some(cas),
+        Ok(_) => None,
+        Err(err) => {
+            warn!(
+                "invalid client_ca_pem for tls policy {}: {}",
+                policy_id, err
+            );
+            None
+        }
+    }
+}
+
+/// 已缓存的客户端证书主题数量上限，超出后整体清空重新积累
+const MAX_CLIENT_CERT_SUBJECTS: usize = 10_000;
+
+/// 校验通过的客户端证书主题，按证书 DER 的 SHA-256 摘要索引（与 Pingora `SslDigest::cert_digest` 相同）
+fn client_cert_subjects() -> &'static RwLock<HashMap<Vec<u8>, String>> {
+    static SUBJECTS: OnceLock<RwLock<HashMap<Vec<u8>, String>>> = OnceLock::new();
+    SUBJECTS.get_or_init(Default::default)
+}
+
+/// 握手校验客户端证书时记录证书主题，供请求阶段按 TLS 摘要查找
+pub fn remember_client_cert(cert: &X509Ref) {
+    let Ok(digest) = cert.digest(MessageDigest::sha256()) else {
+        return;
+    };
+    let subject = format_subject(cert.subject_name());
+    let mut subjects = client_cert_subjects()
+        .write()
+        .unwrap_or_else(|err| err.into_inner());
+    if subjects.len() >= MAX_CLIENT_CERT_SUBJECTS && !subjects.contains_key(digest.as_ref()) {
+        subjects.clear();
+    }
+    subjects.insert(digest.to_vec(), subject);
+}
+
+/// 按 TLS 摘要中的对端证书摘要查找客户端证书主题；未提供证书时为 `None`
+pub fn client_cert_subject(cert_digest: &[u8]) -> Option<String> {
+    if cert_digest.is_empty() {
+        return None;
+    }
+    client_cert_subjects()
+        .read()
+        .unwrap_or_else(|err| err.into_inner())
+        .get(cert_digest)
+        .cloned()
+}
+
+/// 证书主题按证书中的顺序格式化为 `CN=...,O=...`
+pub fn format_subject(name: &X509NameRef) -> String {
+    name.entries()
+        .map(|entry| {
+            let key = entry.object().nid().short_name().unwrap_or("UNKNOWN");
+            let value = entry
+                .data()
+                .as_utf8()
+                .map(|value| value.to_string())
+                .unwrap_or_default();
+            format!("{}={}", key, value)
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_cert_subject_by_digest() {
+        let mut params = CertificateParams::new(vec!["client.example".to_string()]).unwrap();
+        params
+            .distinguished_name
+            .push(rcgen::DnType::CommonName, "partner");
+        params
+            .distinguished_name
+            .push(rcgen::DnType::OrganizationName, "Example");
+        let key_pair = rcgen::KeyPair::generate().unwrap();
+        let cert = params.self_signed(&key_pair).unwrap();
+        let cert = X509::from_der(cert.der()).unwrap();
+        let digest = cert.digest(MessageDigest::sha256()).unwrap();
+
+        assert_eq!(client_cert_subject(&digest), None, "握手前没有记录");
+        remember_client_cert(&cert);
+        assert_eq!(
+            client_cert_subject(&digest).as_deref(),
+            Some("CN=partner,O=Example")
+        );
+        assert_eq!(client_cert_subject(&[]), None, "未提供证书");
+    }
+}
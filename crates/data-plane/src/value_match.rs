@@ -0,0 +1,228 @@
+use gateway_common::models::ValueMatch;
+use pingora::http::RequestHeader;
+use regex::{Regex, RegexBuilder};
+
+/// 编译后的头 / 查询参数 / Cookie 匹配条件
+#[derive(Clone)]
+pub struct ValueMatcher {
+    present: Option<bool>,
+    values: Option<Vec<String>>,
+    prefix: Option<String>,
+    suffix: Option<String>,
+    regex: Option<Regex>,
+    ignore_case: bool,
+    invert: bool,
+    /// 字符串形式的精确匹配把缺失的键视为空串，保持旧版本 `"key": ""` 可匹配缺失键的行为
+    missing_as_empty: bool,
+}
+
+impl ValueMatcher {
+    /// 正则无法编译时返回 None
+    pub fn compile(value: &ValueMatch) -> Option<Self> {
+        let rule = value.to_rule();
+        let ignore_case = rule.ignore_case;
+        let fold = |value: String| {
+            if ignore_case {
+                value.to_lowercase()
+            } else {
+                value
+            }
+        };
+        let regex = match &rule.regex {
+            Some(expr) => Some(
+                RegexBuilder::new(expr)
+                    .case_insensitive(ignore_case)
+                    .build()
+                    .ok()?,
+            ),
+            None => None,
+        };
+        Some(Self {
+            present: rule.present,
+            values: rule
+                .values
+                .map(|values| values.into_iter().map(fold).collect()),
+            prefix: rule.prefix.map(fold),
+            suffix: rule.suffix.map(fold),
+            regex,
+            ignore_case,
+            invert: rule.invert,
+            missing_as_empty: matches!(value, ValueMatch::Exact(_)),
+        })
+    }
+
+    pub fn matches(&self, actual: Option<&str>) -> bool {
+        self.evaluate(actual) != self.invert
+    }
+
+    fn evaluate(&self, actual: Option<&str>) -> bool {
+        if self.present == Some(false) {
+            return actual.is_none();
+        }
+        let Some(actual) = actual.or(self.missing_as_empty.then_some("")) else {
+            return false;
+        };
+        if let Some(regex) = &self.regex
+            && !regex.is_match(actual)
+        {
+            return false;
+        }
+        let folded;
+        let actual = if self.ignore_case {
+            folded = actual.to_lowercase();
+            folded.as_str()
+        } else {
+            actual
+        };
+        if let Some(values) = &self.values
+            && !values.iter().any(|value| value == actual)
+        {
+            return false;
+        }
+        if let Some(prefix) = &self.prefix
+            && !actual.starts_with(prefix.as_str())
+        {
+            return false;
+        }
+        if let Some(suffix) = &self.suffix
+            && !actual.ends_with(suffix.as_str())
+        {
+            return false;
+        }
+        true
+    }
+}
+
+/// 按名称读取请求 Cookie（合并多个 `cookie` 头，取第一个同名 Cookie）
+pub fn cookie<'a>(header: &'a RequestHeader, name: &str) -> Option<&'a str> {
+    header
+        .headers
+        .get_all("cookie")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|pair| {
+            let (key, value) = pair.trim().split_once('=')?;
+            (key.trim() == name).then(|| value.trim().trim_matches('"'))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gateway_common::models::ValueMatchRule;
+
+    fn matcher(rule: ValueMatchRule) -> ValueMatcher {
+        ValueMatcher::compile(&ValueMatch::Rule(rule)).unwrap()
+    }
+
+    #[test]
+    fn test_exact_and_any_of() {
+        let exact = ValueMatcher::compile(&ValueMatch::Exact("beta".to_string())).unwrap();
+        assert!(exact.matches(Some("beta")));
+        assert!(!exact.matches(Some("Beta")), "默认区分大小写");
+        assert!(!exact.matches(None));
+        let empty = ValueMatcher::compile(&ValueMatch::Exact(String::new())).unwrap();
+        assert!(empty.matches(None), "字符串形式的空值匹配缺失的键");
+        assert!(empty.matches(Some("")));
+        assert!(!empty.matches(Some("x")));
+
+        let any = ValueMatcher::compile(&ValueMatch::AnyOf(vec!["a".to_string(), "b".to_string()]))
+            .unwrap();
+        assert!(any.matches(Some("b")));
+        assert!(!any.matches(Some("c")));
+        let any_empty = ValueMatcher::compile(&ValueMatch::AnyOf(vec![String::new()])).unwrap();
+        assert!(!any_empty.matches(None), "数组形式要求键存在");
+    }
+
+    #[test]
+    fn test_present() {
+        let absent = matcher(ValueMatchRule {
+            present: Some(false),
+            ..Default::default()
+        });
+        assert!(absent.matches(None));
+        assert!(!absent.matches(Some("")), "空值也视为存在");
+
+        let present = matcher(ValueMatchRule {
+            present: Some(true),
+            ..Default::default()
+        });
+        assert!(present.matches(Some("")));
+        assert!(!present.matches(None));
+    }
+
+    #[test]
+    fn test_ignore_case_with_regex() {
+        let rule = matcher(ValueMatchRule {
+            regex: Some("^mobile-(ios|android)$".to_string()),
+            prefix: Some("MOBILE-".to_string()),
+            ignore_case: true,
+            ..Default::default()
+        });
+        assert!(rule.matches(Some("Mobile-IOS")));
+        assert!(rule.matches(Some("mobile-android")));
+        assert!(!rule.matches(Some("mobile-web")));
+
+        let sensitive = matcher(ValueMatchRule {
+            regex: Some("^mobile-".to_string()),
+            ..Default::default()
+        });
+        assert!(!sensitive.matches(Some("Mobile-ios")));
+
+        assert!(
+            ValueMatcher::compile(&ValueMatch::Rule(ValueMatchRule {
+                regex: Some("(".to_string()),
+                ..Default::default()
+            }))
+            .is_none(),
+            "正则无法编译"
+        );
+    }
+
+    #[test]
+    fn test_invert() {
+        let not_internal = matcher(ValueMatchRule {
+            suffix: Some(".internal".to_string()),
+            invert: true,
+            ..Default::default()
+        });
+        assert!(not_internal.matches(Some("api.example.com")));
+        assert!(!not_internal.matches(Some("db.internal")));
+        assert!(
+            not_internal.matches(None),
+            "键不存在时取值条件不满足，取反后满足"
+        );
+
+        let not_absent = matcher(ValueMatchRule {
+            present: Some(false),
+            invert: true,
+            ..Default::default()
+        });
+        assert!(not_absent.matches(Some("x")));
+        assert!(!not_absent.matches(None));
+    }
+
+    #[test]
+    fn test_cookie() {
+        let mut header = RequestHeader::build("GET", b"/", None).unwrap();
+        header
+            .append_header("cookie", "session=abc; theme=\"dark mode\"")
+            .unwrap();
+        header
+            .append_header("cookie", "lang=zh; session=ignored")
+            .unwrap();
+        header.append_header("cookie", "flag; empty=").unwrap();
+
+        assert_eq!(
+            cookie(&header, "session"),
+            Some("abc"),
+            "取第一个同名 Cookie"
+        );
+        assert_eq!(cookie(&header, "theme"), Some("dark mode"), "去掉引号");
+        assert_eq!(cookie(&header, "lang"), Some("zh"), "合并多个 cookie 头");
+        assert_eq!(cookie(&header, "empty"), Some(""));
+        assert_eq!(cookie(&header, "flag"), None, "没有取值的项不是 Cookie");
+        assert_eq!(cookie(&header, "Session"), None, "名称区分大小写");
+    }
+}
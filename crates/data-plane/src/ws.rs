@@ -0,0 +1,526 @@
+use bytes::{Bytes, BytesMut};
+use gateway_common::models::WebSocketOptions;
+use std::collections::HashMap;
+use std::os::fd::{BorrowedFd, OwnedFd};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+/// 检查超时与保活的周期
+const TICK: Duration = Duration::from_secs(1);
+/// 关闭帧排队后等待关闭握手完成的时间（含等待客户端方向的帧边界），超时后直接断开上游连接
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_DRAIN_TIMEOUT_SECS: u64 = 30;
+
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+/// RFC 6455 关闭码：端点离开（网关下线连接）
+const CLOSE_GOING_AWAY: u16 = 1001;
+
+/// ws 路由的连接管理配置（运行时）
+#[derive(Clone, Copy, Debug)]
+pub struct WsSettings {
+    max_connections: Option<usize>,
+    idle_timeout: Option<Duration>,
+    max_lifetime: Option<Duration>,
+    ping_interval: Option<Duration>,
+    drain_timeout: Duration,
+}
+
+impl WsSettings {
+    pub fn from_options(options: Option<&WebSocketOptions>) -> Self {
+        let secs = |value: Option<u64>| value.filter(|n| *n > 0).map(Duration::from_secs);
+        let options = options.cloned().unwrap_or_default();
+        Self {
+            max_connections: options
+                .max_connections
+                .filter(|n| *n > 0)
+                .map(|n| n as usize),
+            idle_timeout: secs(options.idle_timeout_secs),
+            max_lifetime: secs(options.max_lifetime_secs),
+            ping_interval: secs(options.ping_interval_secs),
+            drain_timeout: Duration::from_secs(
+                options
+                    .drain_timeout_secs
+                    .unwrap_or(DEFAULT_DRAIN_TIMEOUT_SECS),
+            ),
+        }
+    }
+}
+
+/// 全部 WS 连接的登记表，跨配置版本存在，用于并发上限与下线排空
+#[derive(Default)]
+struct Registry {
+    next_id: AtomicU64,
+    conns: Mutex<HashMap<u64, Arc<WsConn>>>,
+    active: Mutex<HashMap<Uuid, usize>>,
+}
+
+fn registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+    REGISTRY.get_or_init(Registry::default)
+}
+
+struct WsConn {
+    id: u64,
+    route_id: Uuid,
+    settings: WsSettings,
+    state: Mutex<ConnState>,
+    notify: Notify,
+}
+
+struct ConnState {
+    /// 上游连接的描述符副本：仅用于关闭握手超时后强制断开，不经此写入数据，连接结束时释放
+    upstream: Option<OwnedFd>,
+    target: Option<String>,
+    established: Option<Instant>,
+    last_activity: Instant,
+    last_ping: Instant,
+    request_frames: FrameTracker,
+    response_frames: FrameTracker,
+    /// 等待在客户端方向的帧边界处追加的控制帧；控制帧只经 Pingora 的请求体转发写出
+    pending: Vec<u8>,
+    drain_deadline: Option<Instant>,
+    close_sent: Option<Instant>,
+    closed: bool,
+}
+
+/// 占用一个 WS 连接名额；随请求上下文释放
+pub struct WsGuard {
+    conn: Arc<WsConn>,
+}
+
+/// 为 ws 路由占用连接名额，达到路由并发上限时返回 None
+pub fn acquire(route_id: Uuid, settings: WsSettings) -> Option<WsGuard> {
+    let registry = registry();
+    {
+        let mut active = registry.active.lock().unwrap();
+        let count = active.entry(route_id).or_default();
+        if settings.max_connections.is_some_and(|max| *count >= max) {
+            return None;
+        }
+        *count += 1;
+    }
+    crate::metrics::ws_active_inc(&route_id.to_string());
+
+    let now = Instant::now();
+    let conn = Arc::new(WsConn {
+        id: registry.next_id.fetch_add(1, Ordering::Relaxed),
+        route_id,
+        settings,
+        state: Mutex::new(ConnState {
+            upstream: None,
+            target: None,
+            established: None,
+            last_activity: now,
+            last_ping: now,
+            request_frames: FrameTracker::default(),
+            response_frames: FrameTracker::default(),
+            pending: Vec::new(),
+            drain_deadline: None,
+            close_sent: None,
+            closed: false,
+        }),
+        notify: Notify::new(),
+    });
+    registry.conns.lock().unwrap().insert(conn.id, conn.clone());
+    Some(WsGuard { conn })
+}
+
+/// 新配置生效后排空不再由配置承载的连接：`keep(route_id, target)` 为 false 时，
+/// 在路由的排空时间后发送关闭帧
+pub fn drain(keep: impl Fn(Uuid, &str) -> bool) {
+    let conns: Vec<Arc<WsConn>> = registry().conns.lock().unwrap().values().cloned().collect();
+    for conn in conns {
+        let mut state = conn.state.lock().unwrap();
+        if state.established.is_none() || state.drain_deadline.is_some() {
+            continue;
+        }
+        let Some(target) = state.target.as_deref() else {
+            continue;
+        };
+        if !keep(conn.route_id, target) {
+            debug!(
+                "draining websocket connection on route {} to {}",
+                conn.route_id, target
+            );
+            state.drain_deadline = Some(Instant::now() + conn.settings.drain_timeout);
+        }
+    }
+}
+
+impl WsGuard {
+    /// 记录上游连接，描述符副本在连接结束前保持有效
+    pub fn set_upstream(&self, fd: std::os::fd::RawFd, target: &str) {
+        // SAFETY: fd 由 Pingora 持有且在回调期间有效，这里只复制不接管
+        let fd = unsafe { BorrowedFd::borrow_raw(fd) };
+        let mut state = self.conn.state.lock().unwrap();
+        match fd.try_clone_to_owned() {
+            Ok(fd) => state.upstream = Some(fd),
+            Err(err) => warn!("failed to duplicate websocket upstream fd: {}", err),
+        }
+        state.target = Some(target.to_string());
+    }
+
+    /// 升级完成（101）后开始计时，并启动超时 / 保活 / 排空检查
+    pub fn establish(&self) {
+        let now = Instant::now();
+        {
+            let mut state = self.conn.state.lock().unwrap();
+            state.established = Some(now);
+            state.last_activity = now;
+            state.last_ping = now;
+        }
+        tokio::spawn(watch(self.conn.clone()));
+    }
+
+    /// 客户端到上游方向的数据；有待发送的控制帧且本段数据结束于帧边界时追加在其后。
+    /// 客户端已结束（`None`）时不再追加
+    pub fn on_request_body(&self, body: &mut Option<Bytes>) {
+        let Some(chunk) = body.as_ref() else {
+            return;
+        };
+        let mut state = self.conn.state.lock().unwrap();
+        if state.request_frames.feed(chunk) {
+            state.last_activity = Instant::now();
+        }
+        if !state.pending.is_empty() && state.request_frames.at_boundary() {
+            let mut out = BytesMut::with_capacity(chunk.len() + state.pending.len());
+            out.extend_from_slice(chunk);
+            out.extend_from_slice(&std::mem::take(&mut state.pending));
+            *body = Some(out.freeze());
+        }
+    }
+
+    /// 上游到客户端方向的数据
+    pub fn on_response_body(&self, body: &Option<Bytes>) {
+        if let Some(chunk) = body {
+            let mut state = self.conn.state.lock().unwrap();
+            if state.response_frames.feed(chunk) {
+                state.last_activity = Instant::now();
+            }
+        }
+    }
+}
+
+impl Drop for WsGuard {
+    fn drop(&mut self) {
+        {
+            let mut state = self.conn.state.lock().unwrap();
+            state.closed = true;
+            state.upstream = None;
+        }
+        self.conn.notify.notify_one();
+
+        let registry = registry();
+        registry.conns.lock().unwrap().remove(&self.conn.id);
+        let mut active = registry.active.lock().unwrap();
+        if let Some(count) = active.get_mut(&self.conn.route_id) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                active.remove(&self.conn.route_id);
+            }
+        }
+        crate::metrics::ws_active_dec(&self.conn.route_id.to_string());
+    }
+}
+
+/// 每个已升级连接一个检查任务：到达最大存活时间、空闲超时或排空期限时代客户端排队关闭帧，
+/// 由上游回应关闭帧完成关闭握手；按间隔向上游排队 Ping，上游的 Pong 经网关送达客户端。
+/// 排队的控制帧在客户端方向下一段数据的帧边界处随请求体转发，客户端一直静默时关闭帧无法送出，
+/// 关闭握手超时后直接断开上游连接
+async fn watch(conn: Arc<WsConn>) {
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(TICK) => {}
+            _ = conn.notify.notified() => {}
+        }
+        let mut state = conn.state.lock().unwrap();
+        if state.closed {
+            return;
+        }
+        let now = Instant::now();
+        if let Some(sent) = state.close_sent {
+            if now.duration_since(sent) >= CLOSE_TIMEOUT {
+                debug!("websocket close handshake timed out, shutting down upstream");
+                state.shutdown();
+                return;
+            }
+            continue;
+        }
+
+        let settings = &conn.settings;
+        let reason = if state.drain_deadline.is_some_and(|deadline| now >= deadline) {
+            Some("draining")
+        } else if settings.max_lifetime.is_some_and(|max| {
+            state
+                .established
+                .is_some_and(|since| now.duration_since(since) >= max)
+        }) {
+            Some("max lifetime reached")
+        } else if settings
+            .idle_timeout
+            .is_some_and(|idle| now.duration_since(state.last_activity) >= idle)
+        {
+            Some("idle timeout")
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            debug!(
+                "closing websocket connection on route {}: {}",
+                conn.route_id, reason
+            );
+            // 关闭帧之后不再发送其他控制帧
+            state.pending = control_frame(OPCODE_CLOSE, &close_payload(reason));
+            state.close_sent = Some(now);
+            continue;
+        }
+        if settings
+            .ping_interval
+            .is_some_and(|interval| now.duration_since(state.last_ping) >= interval)
+        {
+            // 上一个 Ping 尚未送出时不再重复排队
+            if state.pending.is_empty() {
+                state.pending = control_frame(OPCODE_PING, b"");
+            }
+            state.last_ping = now;
+        }
+    }
+}
+
+impl ConnState {
+    /// 关闭上游套接字的读写两端：不写入任何数据，Pingora 随后读到 EOF 并按正常流程结束会话
+    fn shutdown(&mut self) {
+        if let Some(fd) = self.upstream.take()
+            && let Err(err) = shutdown_socket(&fd)
+        {
+            debug!("failed to shut down websocket upstream: {}", err);
+        }
+    }
+}
+
+fn shutdown_socket(fd: &OwnedFd) -> std::io::Result<()> {
+    use std::os::fd::{AsRawFd, FromRawFd};
+    // SAFETY: fd 为本连接持有的描述符副本；ManuallyDrop 保证不会关闭该描述符，
+    // 这里只调用 shutdown(2)，对 TCP 与 Unix 套接字相同
+    let stream =
+        std::mem::ManuallyDrop::new(unsafe { std::net::TcpStream::from_raw_fd(fd.as_raw_fd()) });
+    stream.shutdown(std::net::Shutdown::Both)
+}
+
+/// 客户端方向的控制帧（客户端发出的帧必须掩码）
+fn control_frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let key = &Uuid::new_v4().into_bytes()[..4];
+    let mut frame = Vec::with_capacity(6 + payload.len());
+    frame.push(0x80 | opcode);
+    frame.push(0x80 | payload.len() as u8);
+    frame.extend_from_slice(key);
+    frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ key[i % 4]));
+    frame
+}
+
+fn close_payload(reason: &str) -> Vec<u8> {
+    let mut payload = CLOSE_GOING_AWAY.to_be_bytes().to_vec();
+    payload.extend_from_slice(reason.as_bytes());
+    payload
+}
+
+/// 逐段解析 WebSocket 帧头，跟踪当前是否处于帧边界
+#[derive(Default)]
+struct FrameTracker {
+    header: Vec<u8>,
+    remaining: u64,
+}
+
+impl FrameTracker {
+    /// 返回本段数据中是否出现数据帧（文本 / 二进制 / 续帧）
+    fn feed(&mut self, mut data: &[u8]) -> bool {
+        let mut data_frame = false;
+        while !data.is_empty() {
+            if self.remaining > 0 {
+                let n = self.remaining.min(data.len() as u64) as usize;
+                data = &data[n..];
+                self.remaining -= n as u64;
+                continue;
+            }
+            self.header.push(data[0]);
+            data = &data[1..];
+            if frame_header_len(&self.header) == Some(self.header.len()) {
+                data_frame |= (self.header[0] & 0x0f) < OPCODE_CLOSE;
+                self.remaining = frame_payload_len(&self.header);
+                self.header.clear();
+            }
+        }
+        data_frame
+    }
+
+    fn at_boundary(&self) -> bool {
+        self.header.is_empty() && self.remaining == 0
+    }
+}
+
+fn frame_header_len(header: &[u8]) -> Option<usize> {
+    let second = *header.get(1)?;
+    let extended = match second & 0x7f {
+        126 => 2,
+        127 => 8,
+        _ => 0,
+    };
+    let mask = if second & 0x80 != 0 { 4 } else { 0 };
+    Some(2 + extended + mask)
+}
+
+fn frame_payload_len(header: &[u8]) -> u64 {
+    match header[1] & 0x7f {
+        126 => u16::from_be_bytes([header[2], header[3]]) as u64,
+        127 => u64::from_be_bytes(header[2..10].try_into().unwrap_or_default()),
+        len => len as u64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 构造帧头：`len` 按 7 位 / 16 位 / 64 位长度编码，`mask` 为 true 时带 4 字节掩码键
+    fn frame(opcode: u8, payload: &[u8], mask: bool) -> Vec<u8> {
+        let mut out = vec![0x80 | opcode];
+        let mask_bit = if mask { 0x80 } else { 0 };
+        let len = payload.len();
+        if len < 126 {
+            out.push(mask_bit | len as u8);
+        } else if len <= u16::MAX as usize {
+            out.push(mask_bit | 126);
+            out.extend_from_slice(&(len as u16).to_be_bytes());
+        } else {
+            out.push(mask_bit | 127);
+            out.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+        if mask {
+            out.extend_from_slice(&[1, 2, 3, 4]);
+        }
+        out.extend_from_slice(payload);
+        out
+    }
+
+    #[test]
+    fn test_frame_tracker_split_header() {
+        let data = frame(0x1, b"hello", true);
+        let mut tracker = FrameTracker::default();
+        assert!(tracker.at_boundary());
+        // 逐字节送入，帧头与掩码键被拆分
+        for (i, byte) in data.iter().enumerate() {
+            let data_frame = tracker.feed(std::slice::from_ref(byte));
+            assert_eq!(data_frame, i == 5, "帧头在第 6 字节解析完成");
+            assert_eq!(tracker.at_boundary(), i == data.len() - 1, "位置 {}", i);
+        }
+    }
+
+    #[test]
+    fn test_frame_tracker_extended_lengths() {
+        let mut tracker = FrameTracker::default();
+        let medium = frame(0x2, &[0u8; 300], false);
+        assert_eq!(medium[1], 126);
+        assert!(!tracker.feed(&medium[..3]), "16 位长度未读完，帧头尚未解析");
+        assert!(!tracker.at_boundary());
+        assert!(tracker.feed(&medium[3..]));
+        assert!(tracker.at_boundary());
+
+        let large = frame(0x2, &vec![0u8; 70_000], true);
+        assert_eq!(large[1] & 0x7f, 127);
+        assert!(tracker.feed(&large[..large.len() - 1]));
+        assert!(!tracker.at_boundary(), "还差 1 字节负载");
+        tracker.feed(&large[large.len() - 1..]);
+        assert!(tracker.at_boundary());
+    }
+
+    #[test]
+    fn test_frame_tracker_control_frames() {
+        let mut tracker = FrameTracker::default();
+        let mut data = frame(OPCODE_PING, b"", true);
+        data.extend(frame(OPCODE_CLOSE, &close_payload("bye"), true));
+        assert!(!tracker.feed(&data), "控制帧不计为数据活动");
+        assert!(tracker.at_boundary());
+
+        data = frame(0x0, b"continued", false);
+        data.extend(frame(OPCODE_PING, b"", false));
+        assert!(tracker.feed(&data), "续帧计为数据活动");
+        assert!(tracker.at_boundary());
+    }
+
+    #[test]
+    fn test_control_frame_masked() {
+        let payload = close_payload("draining");
+        let frame = control_frame(OPCODE_CLOSE, &payload);
+        assert_eq!(frame[0], 0x80 | OPCODE_CLOSE, "FIN 置位");
+        assert_eq!(
+            frame[1],
+            0x80 | payload.len() as u8,
+            "客户端方向的帧必须掩码"
+        );
+        assert_eq!(frame.len(), 6 + payload.len());
+        let key = &frame[2..6];
+        let unmasked: Vec<u8> = frame[6..]
+            .iter()
+            .enumerate()
+            .map(|(i, b)| b ^ key[i % 4])
+            .collect();
+        assert_eq!(unmasked, payload);
+        assert_eq!(&unmasked[..2], &CLOSE_GOING_AWAY.to_be_bytes());
+
+        let mut tracker = FrameTracker::default();
+        tracker.feed(&frame);
+        assert!(tracker.at_boundary(), "控制帧自身完整");
+    }
+
+    #[test]
+    fn test_acquire_max_connections() {
+        let route_id = Uuid::new_v4();
+        let settings = WsSettings::from_options(Some(&WebSocketOptions {
+            max_connections: Some(2),
+            ..Default::default()
+        }));
+        let first = acquire(route_id, settings).unwrap();
+        let second = acquire(route_id, settings).unwrap();
+        assert!(acquire(route_id, settings).is_none(), "达到路由并发上限");
+        assert!(
+            acquire(Uuid::new_v4(), settings).is_some(),
+            "上限按路由计数"
+        );
+
+        drop(first);
+        let third = acquire(route_id, settings);
+        assert!(third.is_some(), "释放后可再次占用");
+        drop(second);
+        drop(third);
+        assert!(!registry().active.lock().unwrap().contains_key(&route_id));
+    }
+
+    #[test]
+    fn test_pending_frame_at_boundary() {
+        let guard = acquire(Uuid::new_v4(), WsSettings::from_options(None)).unwrap();
+        let ping = control_frame(OPCODE_PING, b"");
+        guard.conn.state.lock().unwrap().pending = ping.clone();
+
+        let data = frame(0x1, b"hello", true);
+        let mut body = Some(Bytes::copy_from_slice(&data[..4]));
+        guard.on_request_body(&mut body);
+        assert_eq!(body.as_deref(), Some(&data[..4]), "帧中间不插入");
+
+        let mut body = Some(Bytes::copy_from_slice(&data[4..]));
+        guard.on_request_body(&mut body);
+        let mut expected = data[4..].to_vec();
+        expected.extend_from_slice(&ping);
+        assert_eq!(body.as_deref(), Some(expected.as_slice()), "帧边界处追加");
+        assert!(guard.conn.state.lock().unwrap().pending.is_empty());
+
+        guard.conn.state.lock().unwrap().pending = ping;
+        let mut body = None;
+        guard.on_request_body(&mut body);
+        assert!(body.is_none(), "客户端已结束时不追加");
+    }
+}
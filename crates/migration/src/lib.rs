@@ -3,6 +3,12 @@ use sea_orm_migration::prelude::*;
 mod m20260118_000001_init;
 mod m20260118_000002_acme;
 mod m20260120_000004_audit_logs_text;
+mod m20260126_000005_listener_options;
+mod m20260127_000006_upstream_pool_options;
+mod m20260128_000007_tls_policy_options;
+mod m20260129_000008_route_options;
+mod m20260130_000009_upstream_target_drain;
+mod m20260131_000010_upstream_target_priority;
 
 pub struct Migrator;
 
@@ -13,6 +19,12 @@ impl MigratorTrait for Migrator {
             Box::new(m20260118_000001_init::Migration),
             Box::new(m20260118_000002_acme::Migration),
             Box::new(m20260120_000004_audit_logs_text::Migration),
+            Box::new(m20260126_000005_listener_options::Migration),
+            Box::new(m20260127_000006_upstream_pool_options::Migration),
+            Box::new(m20260128_000007_tls_policy_options::Migration),
+            Box::new(m20260129_000008_route_options::Migration),
+            Box::new(m20260130_000009_upstream_target_drain::Migration),
+            Box::new(m20260131_000010_upstream_target_priority::Migration),
         ]
     }
 }
@@ -182,6 +182,8 @@ export default function Listeners() {
               >
                 <option value="http">HTTP</option>
                 <option value="https">HTTPS</option>
+                <option value="tcp">TCP</option>
+                <option value="tls_passthrough">TLS 透传</option>
               </select>
             </div>
             {form.protocol === "https" && (
@@ -20,21 +20,27 @@ import { toast } from "sonner";
 
 type MatchBuilder = {
   host: string;
+  host_regex: string;
   path_prefix: string;
   path_regex: string;
   method: string;
   headers: string;
   query: string;
+  cookies: string;
+  source_cidrs: string;
   ws: boolean;
 };
 
 const emptyBuilder: MatchBuilder = {
   host: "",
+  host_regex: "",
   path_prefix: "",
   path_regex: "",
   method: "",
   headers: "",
   query: "",
+  cookies: "",
+  source_cidrs: "",
   ws: false
 };
 
@@ -114,11 +120,14 @@ export default function RoutesPage() {
       const parsed = row.match_expr as any;
       setBuilder({
         host: parsed.host ?? "",
+        host_regex: parsed.host_regex ?? "",
         path_prefix: parsed.path_prefix ?? "",
         path_regex: parsed.path_regex ?? "",
         method: Array.isArray(parsed.method) ? parsed.method.join(",") : "",
         headers: parsed.headers ? JSON.stringify(parsed.headers, null, 2) : "",
         query: parsed.query ? JSON.stringify(parsed.query, null, 2) : "",
+        cookies: parsed.cookies ? JSON.stringify(parsed.cookies, null, 2) : "",
+        source_cidrs: Array.isArray(parsed.source_cidrs) ? parsed.source_cidrs.join(",") : "",
         ws: Boolean(parsed.ws)
       });
     } catch {
@@ -131,6 +140,7 @@ export default function RoutesPage() {
   const buildMatchFromBuilder = () => {
     const match: Record<string, unknown> = {};
     if (builder.host) match.host = builder.host;
+    if (builder.host_regex) match.host_regex = builder.host_regex;
     if (builder.path_prefix) match.path_prefix = builder.path_prefix;
     if (builder.path_regex) match.path_regex = builder.path_regex;
     if (builder.method) {
@@ -142,6 +152,12 @@ export default function RoutesPage() {
     if (builder.query) {
       match.query = JSON.parse(builder.query);
     }
+    if (builder.cookies) {
+      match.cookies = JSON.parse(builder.cookies);
+    }
+    if (builder.source_cidrs) {
+      match.source_cidrs = builder.source_cidrs.split(",").map((c) => c.trim()).filter(Boolean);
+    }
     if (form.type === "ws") {
       match.ws = true;
     } else if (builder.ws) {
@@ -154,7 +170,8 @@ export default function RoutesPage() {
     let parsed: Record<string, unknown> = {};
     setError("");
 
-    if (form.type === "port") {
+    const matchless = form.type === "port" || form.type === "tcp";
+    if (matchless) {
       parsed = {};
     } else {
       if (form.match_expr.trim().length === 0) {
@@ -174,12 +191,12 @@ export default function RoutesPage() {
       }
     }
 
-    if (form.type !== "port") {
-      const host = (parsed as any).host;
+    if (!matchless && form.type !== "sni") {
+      const host = (parsed as any).host || (parsed as any).host_regex;
       const pathPrefix = (parsed as any).path_prefix;
       const pathRegex = (parsed as any).path_regex;
       if (!host && !pathPrefix && !pathRegex) {
-        setError("路径/WS 路由必须至少包含 host / path_prefix / path_regex 其中之一");
+        setError("路径/WS 路由必须至少包含 host / host_regex / path_prefix / path_regex 其中之一");
         return;
       }
       if (form.type === "ws") {
@@ -270,6 +287,8 @@ export default function RoutesPage() {
                 <option value="port">端口</option>
                 <option value="path">路径</option>
                 <option value="ws">WS</option>
+                <option value="tcp">TCP</option>
+                <option value="sni">SNI</option>
               </select>
             </div>
             <div>
@@ -305,7 +324,7 @@ export default function RoutesPage() {
                 可先使用下方规则构建器生成 JSON，再粘贴到此处。
               </div>
             </div>
-            {form.type !== "port" && (
+            {form.type !== "port" && form.type !== "tcp" && (
             <div className="rounded-lg border border-[var(--stroke-strong)] bg-[var(--bg)] p-3">
                 <div className="text-sm font-medium">规则构建器</div>
                 <div className="mt-3 grid gap-3 md:grid-cols-2">
@@ -316,6 +335,15 @@ export default function RoutesPage() {
                       onChange={(e) => setBuilder({ ...builder, host: e.target.value })}
                     />
                   </div>
+                  {form.type !== "sni" && (
+                    <div>
+                      <Label>Host Regex</Label>
+                      <Input
+                        value={builder.host_regex}
+                        onChange={(e) => setBuilder({ ...builder, host_regex: e.target.value })}
+                      />
+                    </div>
+                  )}
                   <div>
                     <Label>Path Prefix</Label>
                     <Input
@@ -342,7 +370,7 @@ export default function RoutesPage() {
                     <Textarea
                       value={builder.headers}
                       onChange={(e) => setBuilder({ ...builder, headers: e.target.value })}
-                      placeholder='{"x-env":"prod"}'
+                      placeholder='{"x-env":"prod","x-tier":["gold","silver"],"x-debug":{"present":false}}'
                     />
                   </div>
                   <div>
@@ -353,6 +381,22 @@ export default function RoutesPage() {
                       placeholder='{"v":"1"}'
                     />
                   </div>
+                  <div>
+                    <Label>Source CIDRs（逗号分隔）</Label>
+                    <Input
+                      value={builder.source_cidrs}
+                      onChange={(e) => setBuilder({ ...builder, source_cidrs: e.target.value })}
+                      placeholder="10.0.0.0/8,192.168.0.0/16"
+                    />
+                  </div>
+                  <div>
+                    <Label>Cookies (JSON)</Label>
+                    <Textarea
+                      value={builder.cookies}
+                      onChange={(e) => setBuilder({ ...builder, cookies: e.target.value })}
+                      placeholder='{"canary":{"values":["1","true"],"ignore_case":true}}'
+                    />
+                  </div>
                 </div>
                 <div className="mt-3 flex items-center gap-2">
                   <input
@@ -19,7 +19,7 @@ import { Label } from "@/shadcn/ui/label";
 import { toast } from "sonner";
 
 const emptyPool = { name: "", policy: "round_robin", health_check: "" };
-const emptyTarget = { address: "", weight: 1, enabled: true };
+const emptyTarget = { address: "", weight: 1, enabled: true, priority: 0 };
 
 export default function Upstreams() {
   const queryClient = useQueryClient();
@@ -155,7 +155,12 @@ export default function Upstreams() {
 
   const openTargetEdit = (row: UpstreamTarget) => {
     setEditingTarget(row);
-    setTargetForm({ address: row.address, weight: row.weight, enabled: row.enabled });
+    setTargetForm({
+      address: row.address,
+      weight: row.weight,
+      enabled: row.enabled,
+      priority: row.priority
+    });
     setTargetOpen(true);
   };
 
@@ -163,7 +168,8 @@ export default function Upstreams() {
     const payload = {
       address: targetForm.address,
       weight: Number(targetForm.weight),
-      enabled: targetForm.enabled
+      enabled: targetForm.enabled,
+      priority: Number(targetForm.priority)
     };
     if (editingTarget) {
       updateTarget.mutate({ id: editingTarget.id, payload });
@@ -182,6 +188,7 @@ export default function Upstreams() {
   const targetColumns = [
     { key: "address", title: "地址" },
     { key: "weight", title: "权重" },
+    { key: "priority", title: "层级" },
     { key: "enabled", title: "状态", render: (row: UpstreamTarget) => (row.enabled ? "启用" : "停用") }
   ];
 
@@ -299,6 +306,15 @@ export default function Upstreams() {
                 onChange={(e) => setTargetForm({ ...targetForm, weight: Number(e.target.value) })}
               />
             </div>
+            <div>
+              <Label>层级（0 为主用，数值越大越靠后）</Label>
+              <Input
+                type="number"
+                min={0}
+                value={targetForm.priority}
+                onChange={(e) => setTargetForm({ ...targetForm, priority: Number(e.target.value) })}
+              />
+            </div>
             <div className="flex items-center gap-2">
               <input
                 id="target-enabled"
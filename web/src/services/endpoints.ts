@@ -6,6 +6,7 @@ import type {
   UpstreamTarget,
   TlsPolicy,
   ConfigVersion,
+  Maintenance,
   NodeStatus,
   AuditLog,
   PublishedSnapshot
@@ -25,7 +26,12 @@ export const endpoints = {
         body: JSON.stringify(payload)
       }),
     remove: (id: string) =>
-      api<{ deleted: boolean }>(`/api/v1/listeners/${id}`, { method: "DELETE" })
+      api<{ deleted: boolean }>(`/api/v1/listeners/${id}`, { method: "DELETE" }),
+    setMaintenance: (id: string, payload: Maintenance) =>
+      api<ConfigVersion>(`/api/v1/listeners/${id}/maintenance`, {
+        method: "PUT",
+        body: JSON.stringify(payload)
+      })
   },
   routes: {
     list: (listenerId?: string) =>
@@ -41,7 +47,12 @@ export const endpoints = {
         body: JSON.stringify(payload)
       }),
     remove: (id: string) =>
-      api<{ deleted: boolean }>(`/api/v1/routes/${id}`, { method: "DELETE" })
+      api<{ deleted: boolean }>(`/api/v1/routes/${id}`, { method: "DELETE" }),
+    setMaintenance: (id: string, payload: Maintenance) =>
+      api<ConfigVersion>(`/api/v1/routes/${id}/maintenance`, {
+        method: "PUT",
+        body: JSON.stringify(payload)
+      })
   },
   upstreams: {
     list: () => api<UpstreamPool[]>("/api/v1/upstreams"),
@@ -72,7 +83,12 @@ export const endpoints = {
         body: JSON.stringify(payload)
       }),
     remove: (id: string) =>
-      api<{ deleted: boolean }>(`/api/v1/targets/${id}`, { method: "DELETE" })
+      api<{ deleted: boolean }>(`/api/v1/targets/${id}`, { method: "DELETE" }),
+    setDraining: (id: string, draining: boolean) =>
+      api<ConfigVersion>(`/api/v1/targets/${id}/drain`, {
+        method: "PUT",
+        body: JSON.stringify({ draining })
+      })
   },
   tls: {
     list: () => api<TlsPolicy[]>("/api/v1/tls/policies"),
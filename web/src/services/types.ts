@@ -5,6 +5,7 @@ export type Listener = {
   protocol: string;
   tls_policy_id?: string | null;
   enabled: boolean;
+  options?: Record<string, unknown> | null;
 };
 
 export type Route = {
@@ -15,6 +16,14 @@ export type Route = {
   priority: number;
   upstream_pool_id: string;
   enabled: boolean;
+  options?: Record<string, unknown> | null;
+};
+
+export type Maintenance = {
+  enabled: boolean;
+  message?: string | null;
+  retry_after?: number | null;
+  bypass_cidrs?: string[];
 };
 
 export type UpstreamPool = {
@@ -22,6 +31,7 @@ export type UpstreamPool = {
   name: string;
   policy: string;
   health_check?: Record<string, unknown> | null;
+  options?: Record<string, unknown> | null;
 };
 
 export type UpstreamTarget = {
@@ -30,6 +40,8 @@ export type UpstreamTarget = {
   address: string;
   weight: number;
   enabled: boolean;
+  priority: number;
+  drained_at?: string | null;
 };
 
 export type TlsPolicy = {
@@ -37,6 +49,7 @@ export type TlsPolicy = {
   mode: string;
   domains: string[];
   status: string;
+  options?: Record<string, unknown> | null;
 };
 
 export type ConfigVersion = {
@@ -55,6 +68,15 @@ export type NodeStatus = {
   consistent?: boolean;
   heartbeat_at: string;
   metadata?: Record<string, unknown> | null;
+  draining_targets?: DrainingTarget[];
+};
+
+export type DrainingTarget = {
+  pool_id: string;
+  target_id: string;
+  address: string;
+  inflight: number;
+  remaining_secs: number;
 };
 
 export type AuditLog = {